@@ -0,0 +1,113 @@
+/// how many of the latest samples the levels are worked out from.
+/// about 12ms at 44.1kHz
+pub const AUDIO_WINDOW: usize = 512;
+/// the upper end of the bass and mid bands, in Hz. treble is the rest
+pub const BASS_MAX_HZ: f32 = 250.0;
+pub const MID_MAX_HZ: f32 = 4000.0;
+
+/// how loud the live audio input is, overall and per band. all 0 when
+/// there's no input, eg: while exporting
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AudioLevels {
+    /// root mean square of the samples, 0 - 1
+    pub amplitude: f32,
+    /// roughly the amplitude of the sines within each band
+    pub bass: f32,
+    pub mid: f32,
+    pub treble: f32,
+}
+
+impl AudioLevels {
+    /// the levels of mono samples in -1..1, recorded at `sample_rate` Hz.
+    /// only the last AUDIO_WINDOW samples are looked at
+    pub fn analyze(samples: &[f32], sample_rate: f32) -> Self {
+        let samples = &samples[samples.len().saturating_sub(AUDIO_WINDOW)..];
+        let n = samples.len();
+        if n == 0 || sample_rate <= 0.0 {
+            return Self::default();
+        }
+        let amplitude = (samples.iter().map(|s| s * s).sum::<f32>() / n as f32).sqrt();
+        // a plain dft over the bins. the window is small enough that an
+        // fft isn't worth it
+        let mut bands = [0.0f32; 3];
+        let bin_hz = sample_rate / n as f32;
+        for k in 1..=n / 2 {
+            let (mut re, mut im) = (0.0f32, 0.0f32);
+            let step = std::f32::consts::TAU * k as f32 / n as f32;
+            for (i, s) in samples.iter().enumerate() {
+                let angle = step * i as f32;
+                re += s * angle.cos();
+                im -= s * angle.sin();
+            }
+            // the amplitude of the sine at this bin
+            let magnitude = 2.0 * re.hypot(im) / n as f32;
+            let hz = k as f32 * bin_hz;
+            let band = if hz <= BASS_MAX_HZ { 0 } else if hz <= MID_MAX_HZ { 1 } else { 2 };
+            bands[band] += magnitude * magnitude;
+        }
+        Self {
+            amplitude,
+            bass: bands[0].sqrt(),
+            mid: bands[1].sqrt(),
+            treble: bands[2].sqrt(),
+        }
+    }
+}
+
+/// live capture from the default input device. there's no capture
+/// backend yet, the usual ones link libasound a second time next to
+/// macroquad's own copy. so `open` always fails and the levels stay at 0
+pub struct AudioInput;
+
+impl AudioInput {
+    pub fn open() -> Result<Self, String> {
+        Err("Live audio input isn't supported yet".into())
+    }
+    pub fn levels(&self) -> AudioLevels {
+        AudioLevels::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 44100.0;
+
+    /// a sine that fits a whole number of times in the window,
+    /// so its energy lands in a single bin
+    fn sine(bin: usize, amplitude: f32) -> Vec<f32> {
+        (0..AUDIO_WINDOW)
+            .map(|i| amplitude * (std::f32::consts::TAU * (bin * i) as f32 / AUDIO_WINDOW as f32).sin())
+            .collect()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn silence_and_no_samples_are_zero() {
+        assert_eq!(AudioLevels::analyze(&[], SAMPLE_RATE), AudioLevels::default());
+        assert_eq!(AudioLevels::analyze(&[0.0; AUDIO_WINDOW], SAMPLE_RATE), AudioLevels::default());
+    }
+
+    #[test]
+    fn sines_land_in_their_band() {
+        // a bin is ~86Hz wide
+        let bass = AudioLevels::analyze(&sine(2, 0.5), SAMPLE_RATE);
+        assert_close(bass.bass, 0.5);
+        assert_close(bass.mid, 0.0);
+        assert_close(bass.treble, 0.0);
+        // the rms of a sine is its amplitude / sqrt(2)
+        assert_close(bass.amplitude, 0.5 / 2f32.sqrt());
+
+        let mid = AudioLevels::analyze(&sine(20, 0.25), SAMPLE_RATE);
+        assert_close(mid.mid, 0.25);
+        assert_close(mid.bass, 0.0);
+
+        let treble = AudioLevels::analyze(&sine(100, 1.0), SAMPLE_RATE);
+        assert_close(treble.treble, 1.0);
+        assert_close(treble.mid, 0.0);
+    }
+}
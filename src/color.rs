@@ -1,3 +1,5 @@
+use macroquad::prelude::Color;
+
 /// Color represented in HSL
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Default)]
 pub struct Hsl {
@@ -39,8 +41,141 @@ impl Hsl {
          percent_to_byte(hue_to_rgb(p, q, h)),
          percent_to_byte(hue_to_rgb(p, q, h - 1.0 / 3.0)))
     }
+    pub fn from_rgb(r: u8, g: u8, b: u8) -> Hsl {
+        let r = r as f64 / 255.0;
+        let g = g as f64 / 255.0;
+        let b = b as f64 / 255.0;
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let l = (max + min) / 2.0;
+
+        if max == min {
+            // Achromatic, i.e., grey.
+            return Hsl { h: 0.0, s: 0.0, l };
+        }
+
+        let d = max - min;
+        let s = if l > 0.5 {
+            d / (2.0 - max - min)
+        } else {
+            d / (max + min)
+        };
+
+        Hsl { h: rgb_to_hue(r, g, b, max, d), s, l }
+    }
 }
 
+/// Color represented in HSV
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Default)]
+pub struct Hsv {
+    /// Hue in 0-360 degree
+    pub h: f64,
+    /// Saturation in 0...1 (percent)
+    pub s: f64,
+    /// Value (brightness) in 0...1 (percent)
+    pub v: f64,
+}
+
+impl Hsv {
+    pub fn new(h: f32, s: f32, v: f32) -> Hsv {
+        Hsv {
+            h: h as _,
+            s: s as _,
+            v: v as _,
+        }
+    }
+    pub fn hsv_to_rgb(&self) -> (u8, u8, u8) {
+        if self.s == 0.0 {
+            // Achromatic, i.e., grey.
+            let v = percent_to_byte(self.v);
+            return (v, v, v);
+        }
+
+        // which of the 6 sectors of the color wheel we are in
+        let h = (self.h / 60.0).rem_euclid(6.0);
+        let sector = h.floor();
+        let f = h - sector;
+        let s = self.s;
+        let v = self.v;
+
+        let p = v * (1.0 - s);
+        let q = v * (1.0 - s * f);
+        let t = v * (1.0 - s * (1.0 - f));
+
+        let (r, g, b) = match sector as u8 {
+            0 => (v, t, p),
+            1 => (q, v, p),
+            2 => (p, v, t),
+            3 => (p, q, v),
+            4 => (t, p, v),
+            _ => (v, p, q),
+        };
+        (percent_to_byte(r), percent_to_byte(g), percent_to_byte(b))
+    }
+    pub fn from_rgb(r: u8, g: u8, b: u8) -> Hsv {
+        let r = r as f64 / 255.0;
+        let g = g as f64 / 255.0;
+        let b = b as f64 / 255.0;
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+
+        if max == min {
+            // Achromatic, i.e., grey.
+            return Hsv { h: 0.0, s: 0.0, v: max };
+        }
+
+        let d = max - min;
+        Hsv { h: rgb_to_hue(r, g, b, max, d), s: d / max, v: max }
+    }
+}
+
+/// Hue in 0-360 degree of an rgb color (each channel in 0...1).
+/// `max` is the largest channel, and `d` is the difference
+/// between the largest and smallest channel, which must not be 0.
+fn rgb_to_hue(r: f64, g: f64, b: f64, max: f64, d: f64) -> f64 {
+    let h = if max == r {
+        (g - b) / d + if g < b { 6.0 } else { 0.0 }
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+    h * 60.0
+}
+
+/// Interpolate between two colors, channel by channel.
+/// t of 0 gives `a`, and t of 1 gives `b`
+pub fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    Color::new(
+        a.r + (b.r - a.r) * t,
+        a.g + (b.g - a.g) * t,
+        a.b + (b.b - a.b) * t,
+        a.a + (b.a - a.a) * t,
+    )
+}
+
+/// parses `#RRGGBB` or `#RRGGBBAA`. the `#` is optional
+pub fn parse_hex(hex: &str) -> Result<Color, String> {
+    let digits = hex.trim();
+    let digits = digits.strip_prefix('#').unwrap_or(digits);
+    if (digits.len() != 6 && digits.len() != 8) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid hex color {:?}, expected #RRGGBB or #RRGGBBAA", hex));
+    }
+    // all ascii, so every 2 bytes is one channel
+    let channel = |i: usize| u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).unwrap();
+    let alpha = if digits.len() == 8 { channel(3) } else { 255 };
+    Ok(Color::from_rgba(channel(0), channel(1), channel(2), alpha))
+}
+
+/// `#RRGGBB`, or `#RRGGBBAA` if the color isn't fully opaque
+pub fn to_hex(color: Color) -> String {
+    let [r, g, b, a] = [color.r, color.g, color.b, color.a].map(|c| percent_to_byte(c as f64));
+    if a == 255 {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    } else {
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
 
 fn percent_to_byte(percent: f64) -> u8 {
     (percent * 255.0).round() as u8
@@ -68,4 +203,99 @@ fn hue_to_rgb(p: f64, q: f64, t: f64) -> f64 {
     } else {
         p
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn from_rgb_known_colors() {
+        let red = Hsl::from_rgb(255, 0, 0);
+        assert_close(red.h, 0.0);
+        assert_close(red.s, 1.0);
+        assert_close(red.l, 0.5);
+
+        let green = Hsl::from_rgb(0, 255, 0);
+        assert_close(green.h, 120.0);
+
+        let blue = Hsl::from_rgb(0, 0, 255);
+        assert_close(blue.h, 240.0);
+
+        let grey = Hsl::from_rgb(128, 128, 128);
+        assert_close(grey.s, 0.0);
+        assert_close(grey.l, 0.5);
+    }
+
+    #[test]
+    fn rgb_round_trips() {
+        let colors = [
+            (255, 0, 0),
+            (0, 255, 0),
+            (0, 0, 255),
+            (255, 255, 255),
+            (0, 0, 0),
+            (128, 128, 128),
+            (255, 165, 0),
+            (75, 0, 130),
+            (12, 200, 180),
+        ];
+        for (r, g, b) in colors {
+            let (r2, g2, b2) = Hsl::from_rgb(r, g, b).hsl_to_rgb();
+            assert!((r as i32 - r2 as i32).abs() <= 1, "{:?} -> {:?}", (r, g, b), (r2, g2, b2));
+            assert!((g as i32 - g2 as i32).abs() <= 1, "{:?} -> {:?}", (r, g, b), (r2, g2, b2));
+            assert!((b as i32 - b2 as i32).abs() <= 1, "{:?} -> {:?}", (r, g, b), (r2, g2, b2));
+        }
+    }
+
+    #[test]
+    fn hex_parsing() {
+        assert_eq!(parse_hex("#ff8000").unwrap(), Color::from_rgba(255, 128, 0, 255));
+        assert_eq!(parse_hex("FF800080").unwrap(), Color::from_rgba(255, 128, 0, 128));
+        assert_eq!(parse_hex(" #000000 ").unwrap(), Color::from_rgba(0, 0, 0, 255));
+        assert!(parse_hex("#ff80").is_err());
+        assert!(parse_hex("#gg8000").is_err());
+        assert!(parse_hex("").is_err());
+        assert!(parse_hex("#ff800").is_err());
+        // multi byte characters don't panic
+        assert!(parse_hex("#ff80é").is_err());
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        for hex in ["#ff8000", "#000000", "#12abef80"] {
+            assert_eq!(to_hex(parse_hex(hex).unwrap()), hex);
+        }
+    }
+
+    #[test]
+    fn hsv_achromatic() {
+        assert_eq!(Hsv::new(0.0, 0.0, 0.0).hsv_to_rgb(), (0, 0, 0));
+        assert_eq!(Hsv::new(200.0, 0.0, 1.0).hsv_to_rgb(), (255, 255, 255));
+        assert_eq!(Hsv::new(90.0, 0.0, 0.5).hsv_to_rgb(), (128, 128, 128));
+        let grey = Hsv::from_rgb(128, 128, 128);
+        assert_close(grey.s, 0.0);
+        assert_close(grey.v, 128.0 / 255.0);
+    }
+
+    #[test]
+    fn hsv_saturated_hues() {
+        assert_eq!(Hsv::new(0.0, 1.0, 1.0).hsv_to_rgb(), (255, 0, 0));
+        assert_eq!(Hsv::new(60.0, 1.0, 1.0).hsv_to_rgb(), (255, 255, 0));
+        assert_eq!(Hsv::new(120.0, 1.0, 1.0).hsv_to_rgb(), (0, 255, 0));
+        assert_eq!(Hsv::new(240.0, 1.0, 1.0).hsv_to_rgb(), (0, 0, 255));
+        assert_eq!(Hsv::new(360.0, 1.0, 1.0).hsv_to_rgb(), (255, 0, 0));
+        assert_eq!(Hsv::new(30.0, 1.0, 1.0).hsv_to_rgb(), (255, 128, 0));
+
+        let orange = Hsv::from_rgb(255, 128, 0);
+        assert_close(orange.h, 30.12);
+        assert_close(orange.s, 1.0);
+        assert_close(orange.v, 1.0);
+        let (r, g, b) = orange.hsv_to_rgb();
+        assert_eq!((r, g, b), (255, 128, 0));
+    }
+}
@@ -1,3 +1,6 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::hash::Hash;
 
 #[derive(Default)]
 pub struct Node<T: Default> {
@@ -10,6 +13,11 @@ pub struct Node<T: Default> {
 #[derive(Default)]
 pub struct Graph<T: Default> {
     pub nodes: Vec<Node<T>>,
+    /// maps a node's value to its index in nodes.
+    /// filled in lazily by index_of
+    index: HashMap<T, usize>,
+    /// how many of the nodes have been put into the index so far
+    indexed_len: usize,
 }
 
 impl Graph<()> {
@@ -32,6 +40,8 @@ impl From<usize> for Node<usize> {
 impl<T: Default> Graph<T> {
     pub fn reset(&mut self) {
         self.nodes = vec![];
+        self.index.clear();
+        self.indexed_len = 0;
     }
     pub fn add(&mut self, n: impl Into<Node<T>>) -> usize {
         let index = self.nodes.len();
@@ -44,58 +54,128 @@ impl<T: Default> Graph<T> {
             self.nodes[b].is_dependent_of.push(a);
         });
     }
+    /// index of the first node with this value
+    pub fn index_of(&mut self, value: &T) -> Option<usize>
+        where T: Eq + Hash + Clone
+    {
+        // only index the nodes that were added since the last lookup
+        for i in self.indexed_len..self.nodes.len() {
+            self.index.entry(self.nodes[i].value.clone()).or_insert(i);
+        }
+        self.indexed_len = self.nodes.len();
+        self.index.get(value).copied()
+    }
     /// a depends on b
     pub fn add_dependency(&mut self, a: T, b: T)
-        where T: PartialEq
+        where T: Eq + Hash + Clone
     {
-        // find index of A:
-        if let Some(a_ind) = self.nodes.iter().position(|n| n.value == a) {
-            if let Some(b_ind) = self.nodes.iter().position(|n| n.value == b) {
+        if let Some(a_ind) = self.index_of(&a) {
+            if let Some(b_ind) = self.index_of(&b) {
                 self.nodes[a_ind].depends_on.push(b_ind);
                 self.nodes[b_ind].is_dependent_of.push(a_ind);
             }
         }
     }
     pub fn does_transient_dependency_exist(&self, depends_on: &Vec<usize>, i: usize) -> bool {
+        let mut visited = HashSet::new();
+        self.transient_dependency_search(depends_on, i, &mut visited)
+    }
+    /// visited holds every node whose dependencies were already searched,
+    /// so shared ancestors are only walked once and cycles terminate
+    fn transient_dependency_search(&self, depends_on: &[usize], i: usize, visited: &mut HashSet<usize>) -> bool {
         for node_i in depends_on {
             if &i == node_i { return true }
+            if !visited.insert(*node_i) { continue }
 
             let node = &self.nodes[*node_i];
-            if self.does_transient_dependency_exist(&node.depends_on, i) {
+            if self.transient_dependency_search(&node.depends_on, i, visited) {
                 return true;
             }
         }
         false
     }
-    pub fn find_insert_index(&self, existing_list: &mut Vec<&Node<T>>, i: usize) -> usize {
-        for (j, existing) in existing_list.iter().enumerate() {
-            if self.does_transient_dependency_exist(&existing.depends_on, i) {
-                return j;
+    /// removes a single "a depends on b" edge, if it exists
+    pub fn remove_dependency(&mut self, a: T, b: T)
+        where T: Eq + Hash + Clone
+    {
+        if let Some(a_ind) = self.index_of(&a) {
+            if let Some(b_ind) = self.index_of(&b) {
+                let a_deps = &mut self.nodes[a_ind].depends_on;
+                if let Some(i) = a_deps.iter().position(|x| *x == b_ind) {
+                    a_deps.remove(i);
+                }
+                let b_dependents = &mut self.nodes[b_ind].is_dependent_of;
+                if let Some(i) = b_dependents.iter().position(|x| *x == a_ind) {
+                    b_dependents.remove(i);
+                }
             }
         }
-        return existing_list.len();
     }
     pub fn calculate_order<'a>(&'a self) -> Vec<&'a Node<T>> {
-        let mut existing_list: Vec<&'a Node<T>> = vec![];
-        for (node_i, node) in self.nodes.iter().enumerate() {
-            let insert_index = self.find_insert_index(&mut existing_list, node_i);
-            existing_list.insert(insert_index, node);
-            // if node is not a dependency of anything existing: push
-            // if node is a dependency of something else, insert right before it.
+        self.calculate_order_indices().into_iter().map(|i| &self.nodes[i]).collect()
+    }
+    /// topological sort (Kahn's algorithm). nodes that are ready at the
+    /// same time come out by the earliest added node that depends on them
+    /// (or themselves). so like the old insertion sort, a node goes right
+    /// before what depends on it, and nodes that nothing depends on keep
+    /// the order they were added in.
+    /// nodes that are part of a cycle can never become ready, so they are
+    /// put at the end. use try_calculate_order_indices to detect that.
+    pub fn calculate_order_indices(&self) -> Vec<usize> {
+        let (mut order, cyclic) = self.kahn_order();
+        order.extend(cyclic);
+        order
+    }
+    /// like calculate_order_indices, but errors with the indices of every
+    /// node that is in a cycle, or that depends on a node in a cycle
+    pub fn try_calculate_order_indices(&self) -> Result<Vec<usize>, Vec<usize>> {
+        let (order, cyclic) = self.kahn_order();
+        if cyclic.is_empty() {
+            Ok(order)
+        } else {
+            Err(cyclic)
         }
-        existing_list
-    }
-    pub fn calculate_order_indices<'a>(&'a self) -> Vec<usize> {
-        let mut existing_list: Vec<&'a Node<T>> = vec![];
-        let mut out_list: Vec<usize> = vec![];
-        for (node_i, node) in self.nodes.iter().enumerate() {
-            let insert_index = self.find_insert_index(&mut existing_list, node_i);
-            existing_list.insert(insert_index, node);
-            out_list.insert(insert_index, node_i);
-            // if node is not a dependency of anything existing: push
-            // if node is a dependency of something else, insert right before it.
+    }
+    /// returns (the sorted nodes, the nodes that could not be sorted)
+    fn kahn_order(&self) -> (Vec<usize>, Vec<usize>) {
+        let anchors = self.anchors();
+        let mut in_degree: Vec<usize> = self.nodes.iter().map(|n| n.depends_on.len()).collect();
+        let mut ready: BinaryHeap<Reverse<(usize, usize)>> = (0..self.nodes.len())
+            .filter(|i| in_degree[*i] == 0)
+            .map(|i| Reverse((anchors[i], i)))
+            .collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(Reverse((_, node_i))) = ready.pop() {
+            order.push(node_i);
+            for dependent_i in self.nodes[node_i].is_dependent_of.iter() {
+                in_degree[*dependent_i] -= 1;
+                if in_degree[*dependent_i] == 0 {
+                    ready.push(Reverse((anchors[*dependent_i], *dependent_i)));
+                }
+            }
         }
-        out_list
+        // anything with dependencies left over is stuck behind a cycle
+        let cyclic = (0..self.nodes.len()).filter(|i| in_degree[*i] > 0).collect();
+        (order, cyclic)
+    }
+    /// for every node, the index of the earliest added node out of itself
+    /// and everything that depends on it. worked out from the nodes that
+    /// nothing depends on down to their dependencies. nodes in a cycle are
+    /// never finished, so they don't pass theirs on
+    fn anchors(&self) -> Vec<usize> {
+        let mut anchors: Vec<usize> = (0..self.nodes.len()).collect();
+        let mut out_degree: Vec<usize> = self.nodes.iter().map(|n| n.is_dependent_of.len()).collect();
+        let mut done: Vec<usize> = (0..self.nodes.len()).filter(|i| out_degree[*i] == 0).collect();
+        while let Some(node_i) = done.pop() {
+            for dependency_i in self.nodes[node_i].depends_on.iter() {
+                anchors[*dependency_i] = anchors[*dependency_i].min(anchors[node_i]);
+                out_degree[*dependency_i] -= 1;
+                if out_degree[*dependency_i] == 0 {
+                    done.push(*dependency_i);
+                }
+            }
+        }
+        anchors
     }
     pub fn is_order_valid<'a>(&'a self, order: &Vec<&'a Node<T>>) -> bool {
         let mut prior_node_addresses = vec![];
@@ -117,6 +197,20 @@ impl<T: Default> Graph<T> {
 
         true
     }
+    /// returns the first (node, dependency) pair in the order where
+    /// the dependency does not come before the node that depends on it
+    pub fn find_invalid_dependency(&self, order: &[usize]) -> Option<(usize, usize)> {
+        let mut seen = vec![false; self.nodes.len()];
+        for node_i in order {
+            for dependency_i in self.nodes[*node_i].depends_on.iter() {
+                if !seen[*dependency_i] {
+                    return Some((*node_i, *dependency_i));
+                }
+            }
+            seen[*node_i] = true;
+        }
+        None
+    }
 }
 
 impl DependencyChain for Vec<(usize, usize)> {
@@ -241,6 +335,130 @@ mod tests {
         assert_eq!(g.is_order_valid(&order), false);
     }
 
+    #[test]
+    fn find_invalid_dependency_reports_pair() {
+        let mut g = Graph::new_debug();
+        let a = g.add("A");
+        let b = g.add("B");
+        g.specify_dependencies(a.on(b));
+        assert_eq!(g.find_invalid_dependency(&g.calculate_order_indices()), None);
+        // running A first is wrong because it depends on B
+        assert_eq!(g.find_invalid_dependency(&[a, b]), Some((a, b)));
+    }
+
+    #[test]
+    fn add_dependency_many_nodes() {
+        // a few hundred nodes, each depending on the one added before it.
+        // add_dependency looks nodes up by value, so this should stay fast
+        let mut g: Graph<usize> = Graph::default();
+        let n = 500;
+        for value in 0..n {
+            g.add(value * 10);
+        }
+        for value in 1..n {
+            g.add_dependency(value * 10, (value - 1) * 10);
+        }
+        // values that aren't in the graph are ignored
+        g.add_dependency(3, 7);
+        for i in 1..n {
+            assert_eq!(g.nodes[i].depends_on, vec![i - 1]);
+            assert_eq!(g.nodes[i - 1].is_dependent_of, vec![i]);
+        }
+        assert!(g.nodes[0].depends_on.is_empty());
+        // nodes added after lookups were done are still found
+        let extra = g.add(n * 10);
+        g.add_dependency(n * 10, 0);
+        assert_eq!(g.nodes[extra].depends_on, vec![0]);
+        assert_eq!(g.index_of(&(n * 10)), Some(extra));
+    }
+
+    #[test]
+    fn cycles_are_detected() {
+        // D -> A -> B -> C -> A
+        let mut g = Graph::new_debug();
+        let a = g.add("A");
+        let b = g.add("B");
+        let c = g.add("C");
+        let d = g.add("D");
+        let e = g.add("E");
+        g.specify_dependencies([d.on(a), a.on(b), b.on(c), c.on(a)]);
+        assert_eq!(g.try_calculate_order_indices(), Err(vec![a, b, c, d]));
+        // everything still shows up in the non-checked order
+        let order = g.calculate_order_indices();
+        assert_eq!(order, vec![e, a, b, c, d]);
+    }
+
+    #[test]
+    fn order_matches_the_old_insertion_order() {
+        // A depends on C, and B is on its own. C goes right before A,
+        // and B still draws after A like it did before the Kahn sort
+        let mut g = Graph::new_debug();
+        let a = g.add("A");
+        let b = g.add("B");
+        let c = g.add("C");
+        g.specify_dependencies(a.on(c));
+        assert_eq!(g.calculate_order_indices(), vec![c, a, b]);
+
+        // D and E depend on F, G is on its own and H depends on D.
+        // everything D needs goes before it, then E, G and H in the order
+        // they were added
+        let mut g = Graph::new_debug();
+        let d = g.add("D");
+        let e = g.add("E");
+        let f = g.add("F");
+        let gg = g.add("G");
+        let h = g.add("H");
+        g.specify_dependencies([d.on(f), e.on(f), h.on(d)]);
+        assert_eq!(g.calculate_order_indices(), vec![f, d, e, gg, h]);
+    }
+
+    #[test]
+    fn transient_dependency_search_terminates_on_cycles() {
+        // A -> B -> C -> B, and D is not reachable from A
+        let mut g = Graph::new_debug();
+        let a = g.add("A");
+        let b = g.add("B");
+        let c = g.add("C");
+        let d = g.add("D");
+        g.specify_dependencies([a.on(b), b.on(c), c.on(b)]);
+        let a_deps = g.nodes[a].depends_on.clone();
+        assert!(!g.does_transient_dependency_exist(&a_deps, d));
+        assert!(g.does_transient_dependency_exist(&a_deps, c));
+    }
+
+    #[test]
+    fn transient_dependency_full_cycle() {
+        // A -> B -> C -> A
+        let mut g = Graph::new_debug();
+        let a = g.add("A");
+        let b = g.add("B");
+        let c = g.add("C");
+        let d = g.add("D");
+        g.specify_dependencies([a.on(b), b.on(c), c.on(a)]);
+        let a_deps = g.nodes[a].depends_on.clone();
+        // A depends on itself through the cycle
+        assert!(g.does_transient_dependency_exist(&a_deps, a));
+        assert!(!g.does_transient_dependency_exist(&a_deps, d));
+    }
+
+    #[test]
+    fn deep_chain() {
+        // each node depends on the one after it, so the order has
+        // to be completely reversed. this used to recurse down the whole
+        // chain for every insertion
+        let mut g = Graph::new_debug();
+        let n = 500;
+        let nodes: Vec<usize> = (0..n).map(|_| g.add("")).collect();
+        for pair in nodes.windows(2) {
+            g.specify_dependencies(pair[0].on(pair[1]));
+        }
+        let order = g.calculate_order_indices();
+        let expected: Vec<usize> = nodes.iter().rev().copied().collect();
+        assert_eq!(order, expected);
+        assert_eq!(g.try_calculate_order_indices(), Ok(expected));
+        assert!(g.is_order_valid(&g.calculate_order()));
+    }
+
     #[test]
     fn complex_deps2() {
         // A
@@ -293,7 +511,7 @@ mod tests {
         ]);
 
         let order = g.calculate_order();
-        // assert_eq!(true, g.is_order_valid(&order));
+        assert!(g.is_order_valid(&order));
         for i in order {
             println!("{:?}", i.name);
         }
@@ -1,4 +1,8 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use macroquad::prelude::*;
 
@@ -7,6 +11,108 @@ pub const CONNECTION_SIZE: f32 = 10.0;
 pub const CONNECTION_SPACING: f32 = 28.0;
 pub const FONT_SIZE: u16 = 32;
 pub const FONT_SIZE_F32: f32 = FONT_SIZE as f32;
+/// blocks dragged while holding shift snap to multiples of this
+pub const SNAP_GRID_SIZE: f32 = 20.0;
+/// space between blocks placed by BlockContext::auto_layout
+pub const LAYOUT_SPACING_X: f32 = 40.0;
+pub const LAYOUT_SPACING_Y: f32 = 80.0;
+/// smaller than FONT_SIZE so the values overlay doesn't cover the graph
+pub const VALUE_FONT_SIZE: f32 = 18.0;
+/// how close the mouse has to be to a connection line to select it
+pub const CONNECTION_HOVER_DISTANCE: f32 = 4.0;
+/// how far the graph can be zoomed out/in
+pub const MIN_ZOOM: f32 = 0.1;
+pub const MAX_ZOOM: f32 = 4.0;
+/// how much one step of the mouse wheel zooms
+pub const ZOOM_STEP: f32 = 1.1;
+/// space kept around the blocks by BlockContext::zoom_to_fit
+pub const ZOOM_FIT_MARGIN: f32 = 40.0;
+/// sticky notes are drawn with the values font, a line per row
+pub const NOTE_LINE_HEIGHT: f32 = VALUE_FONT_SIZE;
+pub const NOTE_PADDING: f32 = 6.0;
+/// the always-on port labels are cut to this many characters so that
+/// they fit within CONNECTION_SPACING
+pub const PORT_LABEL_CHARS: usize = 4;
+pub const PORT_LABEL_FONT_SIZE: f32 = 12.0;
+/// the gap between nodes stacked down the side of a tall block
+pub const TALL_PORT_SPACING: f32 = 8.0;
+/// tall blocks are at least this wide, so that the labels of the
+/// inputs and outputs on either side fit next to each other inside it
+pub const TALL_BLOCK_MIN_WIDTH: f32 = 100.0;
+pub const NOTE_COLOR: Color = Color::new(1.0, 0.95, 0.6, 1.0);
+
+/// the menu shown after dropping a number onto a point input
+pub const INSERT_PT_COMBINE_TEXT: &str = "insert PtCombine here";
+
+/// a number output that was dropped onto a point input (or the other way
+/// around). instead of failing to connect, a menu offers to put a
+/// PtCombine in between. positions are in the graph
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PendingInsert {
+    pub output: Id,
+    pub output_parent: Id,
+    pub output_pos: (f32, f32),
+    pub input: Id,
+    pub input_parent: Id,
+    pub input_pos: (f32, f32),
+    /// where the menu is drawn
+    pub menu_pos: (f32, f32),
+}
+
+/// where the graph of blocks is shown on the screen.
+/// a point p in the graph is drawn at p * zoom + offset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GraphCamera {
+    pub offset: (f32, f32),
+    pub zoom: f32,
+    /// the last screen position of the mouse while panning
+    pub panning_from: Option<(f32, f32)>,
+}
+
+impl Default for GraphCamera {
+    fn default() -> Self {
+        Self { offset: (0.0, 0.0), zoom: 1.0, panning_from: None }
+    }
+}
+
+impl GraphCamera {
+    pub fn to_screen(self, pt: (f32, f32)) -> (f32, f32) {
+        (pt.0 * self.zoom + self.offset.0, pt.1 * self.zoom + self.offset.1)
+    }
+    pub fn to_graph(self, pt: (f32, f32)) -> (f32, f32) {
+        ((pt.0 - self.offset.0) / self.zoom, (pt.1 - self.offset.1) / self.zoom)
+    }
+    /// zooms by `factor`, keeping the graph point under `screen_pt` in place
+    pub fn zoom_around(&mut self, screen_pt: (f32, f32), factor: f32) {
+        let anchor = self.to_graph(screen_pt);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.offset = (screen_pt.0 - anchor.0 * self.zoom, screen_pt.1 - anchor.1 * self.zoom);
+    }
+    /// the camera that shows all of `bounds` (in the graph), centered
+    /// in `area` (on the screen) with `margin` around it
+    pub fn fit(bounds: Rect, area: Rect, margin: f32) -> Self {
+        let available = ((area.w - 2.0 * margin).max(1.0), (area.h - 2.0 * margin).max(1.0));
+        let zoom = (available.0 / bounds.w.max(1.0))
+            .min(available.1 / bounds.h.max(1.0))
+            .clamp(MIN_ZOOM, MAX_ZOOM);
+        let (bounds_center, area_center) = (bounds.center(), area.center());
+        Self {
+            offset: (area_center.x - bounds_center.x * zoom, area_center.y - bounds_center.y * zoom),
+            zoom,
+            panning_from: None,
+        }
+    }
+    /// the macroquad camera to draw the graph with
+    pub fn camera2d(&self) -> Camera2D {
+        let (s_width, s_height) = (screen_width(), screen_height());
+        let center = self.to_graph((s_width / 2.0, s_height / 2.0));
+        Camera2D {
+            zoom: vec2(2.0 * self.zoom / s_width, -2.0 * self.zoom / s_height),
+            target: vec2(center.0, center.1),
+            ..Default::default()
+        }
+    }
+}
 
 pub trait Boundable {
     fn get_bounds(&self) -> (f32, f32, f32, f32);
@@ -18,16 +124,39 @@ impl Boundable for (f32, f32, f32, f32) {
 }
 impl Boundable for &DraggableBlock {
     fn get_bounds(&self) -> (f32, f32, f32, f32) {
-        (self.x, self.y, self.width, BLOCK_HEIGHT)
+        (self.x, self.y, self.width, self.height())
     }
 }
 
+pub fn snap_to_grid(v: f32, grid_size: f32) -> f32 {
+    (v / grid_size).round() * grid_size
+}
+
+/// shortest distance from p to the line segment a-b
+pub fn distance_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len_squared = dx * dx + dy * dy;
+    let t = if len_squared == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len_squared).clamp(0.0, 1.0)
+    };
+    let (closest_x, closest_y) = (a.0 + t * dx, a.1 + t * dy);
+    ((p.0 - closest_x).powi(2) + (p.1 - closest_y).powi(2)).sqrt()
+}
+
 pub fn mouse_within_bounds<B: Boundable>(b: B) -> bool {
     mouse_within_bounds_offset(b).is_some()
 }
 
 pub fn mouse_within_bounds_offset<B: Boundable>(b: B) -> Option<(f32, f32)> {
-    let (mx, my) = mouse_position();
+    within_bounds_offset(mouse_position(), b)
+}
+
+/// like mouse_within_bounds_offset, for a point that isn't
+/// necessarily the mouse in screen space
+pub fn within_bounds_offset<B: Boundable>(pt: (f32, f32), b: B) -> Option<(f32, f32)> {
+    let (mx, my) = pt;
     let (x, y, w, h) = b.get_bounds();
     let within_bounds = mx >= x && mx < x + w && my >= y && my < y + h;
     if within_bounds {
@@ -45,6 +174,60 @@ pub struct BlockConnectionNode {
     pub connection_type: ConnectionType,
     pub is_being_hovered: bool,
     pub is_dragging_line: bool,
+    /// if true, the value panel only allows whole numbers for this node
+    pub is_integer: bool,
+    /// if true, the value panel lets the user edit list values by hand.
+    /// otherwise lists are only set dynamically via connections
+    pub is_list_editable: bool,
+    /// if true, the value panel shows this boolean as a button. it's only
+    /// true for the run right after a click, for one off actions
+    pub is_button: bool,
+    /// if true, this input takes any kind of list. connecting one gives
+    /// it (and the block's `follows_list` outputs) that list's type
+    pub is_any_list: bool,
+    /// for outputs whose type depends on the list going into
+    /// the block's `is_any_list` input
+    pub follows_list: Option<ListFollow>,
+}
+
+/// how an output can be connected to an input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Direct,
+    /// a number output can go into a point input through a PtCombine
+    ThroughPtCombine,
+}
+
+/// how an output of type `output` can go into an input of type `input`,
+/// None if it can't. any list can go into an any-list input, its block
+/// is retyped to match afterwards, see BlockContext::sync_list_types
+pub fn link_kind(input: &InputValue, input_any_list: bool, output: &InputValue) -> Option<LinkKind> {
+    if input_any_list && output.is_list() {
+        return Some(LinkKind::Direct);
+    }
+    match (input, output) {
+        (InputValue::Point(_), InputValue::Point(_)) |
+        (InputValue::Color(_), InputValue::Color(_)) |
+        (InputValue::Selection(_), InputValue::Selection(_)) |
+        (InputValue::ListNumbers(_), InputValue::ListNumbers(_)) |
+        (InputValue::ListPoints(_), InputValue::ListPoints(_)) |
+        (InputValue::ListColors(_), InputValue::ListColors(_)) |
+        (InputValue::Boolean(_), InputValue::Boolean(_)) |
+        (InputValue::Text(_), InputValue::Text(_)) => Some(LinkKind::Direct),
+        // an expression input still takes numbers
+        (InputValue::Number(_) | InputValue::Expression(_), InputValue::Number(_) | InputValue::Expression(_)) => Some(LinkKind::Direct),
+        (InputValue::Point(_), InputValue::Number(_)) => Some(LinkKind::ThroughPtCombine),
+        _ => None,
+    }
+}
+
+/// what an output becomes when its block's any-list input is retyped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFollow {
+    /// the same kind of list
+    List,
+    /// one value of the list
+    Element,
 }
 
 impl BlockConnectionNode {
@@ -57,6 +240,11 @@ impl BlockConnectionNode {
             name: s.as_ref().into(),
             is_being_hovered: false,
             is_dragging_line: false,
+            is_integer: false,
+            is_list_editable: false,
+            is_button: false,
+            is_any_list: false,
+            follows_list: None,
         }
     }
     pub fn new_with_input_type<S: AsRef<str>>(s: S, input_type: InputValue, connection_type: ConnectionType) -> Self {
@@ -68,14 +256,59 @@ impl BlockConnectionNode {
             name: s.as_ref().into(),
             is_being_hovered: false,
             is_dragging_line: false,
+            is_integer: false,
+            is_list_editable: false,
+            is_button: false,
+            is_any_list: false,
+            follows_list: None,
         }
     }
+    pub fn integer(mut self) -> Self {
+        self.is_integer = true;
+        self
+    }
+    pub fn list_editable(mut self) -> Self {
+        self.is_list_editable = true;
+        self
+    }
+    pub fn button(mut self) -> Self {
+        self.is_button = true;
+        self
+    }
+    pub fn any_list(mut self) -> Self {
+        self.is_any_list = true;
+        self
+    }
+    pub fn follows_list(mut self, follow: ListFollow) -> Self {
+        self.follows_list = Some(follow);
+        self
+    }
     pub fn get_text(&self) -> String {
         format!("({}) {}", self.id.0, self.name)
     }
-    pub fn draw(&self, x: f32, y: f32) {
+    /// the short name drawn next to the node when port labels are on
+    pub fn label(&self) -> String {
+        self.name.chars().take(PORT_LABEL_CHARS).collect()
+    }
+    /// `mouse` is the mouse position in the graph. if `label_side` is set,
+    /// the short label is drawn on the far side of the node from the block,
+    /// or inside the block for nodes on its left/right side
+    pub fn draw(&self, x: f32, y: f32, mouse: (f32, f32), label_side: Option<PortSide>) {
         let color = if self.is_being_hovered { GREEN } else { GRAY };
         draw_rectangle(x, y, CONNECTION_SIZE, CONNECTION_SIZE, color);
+        if let Some(side) = label_side {
+            let label = self.label();
+            let (label_x, label_y) = match side {
+                PortSide::Top => (x, y - 2.0),
+                PortSide::Bottom => (x, y + CONNECTION_SIZE + PORT_LABEL_FONT_SIZE),
+                PortSide::Left => (x + CONNECTION_SIZE + 2.0, y + CONNECTION_SIZE),
+                PortSide::Right => {
+                    let measured = measure_text(&label, None, PORT_LABEL_FONT_SIZE as u16, 1.0);
+                    (x - measured.width - 2.0, y + CONNECTION_SIZE)
+                }
+            };
+            draw_text(&label, label_x, label_y, PORT_LABEL_FONT_SIZE, DARKGRAY);
+        }
         if self.is_being_hovered {
             let padding = 2.0;
             let x = x - padding;
@@ -86,15 +319,14 @@ impl BlockConnectionNode {
             draw_text(&self.get_text(), x - measured.width, y + measured.offset_y, FONT_SIZE_F32, BLACK);
         }
         if self.is_dragging_line {
-            let (mx, my) = mouse_position();
-            draw_line(x, y, mx, my, 1.0, BLACK);
+            draw_line(x, y, mouse.0, mouse.1, 1.0, BLACK);
         }
     }
     /// returns if connections have changed
     pub fn update(&mut self, x: f32, y: f32, block_context: &mut BlockContext) -> bool {
         let mut connections_changed = false;
         let bounds = (x, y, CONNECTION_SIZE, CONNECTION_SIZE);
-        if mouse_within_bounds(bounds) {
+        if !block_context.egui_wants_pointer && within_bounds_offset(block_context.mouse(), bounds).is_some() {
             self.is_being_hovered = true;
             if is_mouse_button_pressed(MouseButton::Left) {
                 if block_context.can_drag(self.id) {
@@ -114,7 +346,7 @@ impl BlockConnectionNode {
         if is_mouse_button_released(MouseButton::Left) {
             block_context.release_drag(self.id);
             if self.is_dragging_line {
-                block_context.can_connect(self.parent_id, self.id, self.connection_type, &self.value, (x, y));
+                block_context.can_connect(self.parent_id, self.id, self.connection_type, &self.value, self.is_any_list, (x, y));
                 connections_changed = true;
             }
             self.is_dragging_line = false;
@@ -133,14 +365,26 @@ impl std::fmt::Display for Id {
     }
 }
 
+impl From<Id> for u64 {
+    fn from(id: Id) -> u64 {
+        id.0 as u64
+    }
+}
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
 pub fn get_id() -> Id {
-    static mut CURRENT_ID: usize = 0;
-    let new_id = unsafe {
-        let out = Id(CURRENT_ID);
-        CURRENT_ID += 1;
-        out
-    };
-    new_id
+    Id(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+/// resets the id counter so the next id handed out is one past the
+/// largest of the given ids (or 0 if there are none).
+/// only call this when every existing block is being replaced, eg: when
+/// loading a project whose ids were assigned in a previous run. ids from
+/// before that are handed out again
+pub fn reset_id_counter(ids: impl IntoIterator<Item = Id>) {
+    let next = ids.into_iter().map(|id| id.0 + 1).max().unwrap_or(0);
+    NEXT_ID.store(next, Ordering::Relaxed);
 }
 
 impl From<Id> for Node<Id> {
@@ -160,6 +404,18 @@ pub enum OutputResult {
     Iteration(Vec<InputValue>),
 }
 
+impl OutputResult {
+    pub fn describe(&self) -> String {
+        match self {
+            OutputResult::SingleValue(v) => v.describe(),
+            OutputResult::Iteration(values) => match values.first() {
+                Some(first) => format!("{} items, first {}", values.len(), first.describe()),
+                None => "0 items".into(),
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum InputResult<'a> {
     SingleValue(&'a InputValue),
@@ -177,15 +433,51 @@ pub struct BlockContext {
     /// key is the id of the input
     /// value is the id of the block of the output
     pub inputs: HashMap<Id, Id>,
+    /// key is the id of a connected input
+    /// value is the id of the block that input belongs to
+    pub input_parents: HashMap<Id, Id>,
     pub graph: Graph<Id>,
     /// the indices are within the graph
     pub graph_order: Vec<usize>,
     /// easy way to get a block from the blocks vec via its id
     pub block_ids: HashMap<Id, usize>,
+    /// the (input, output) key of the connection under the mouse.
+    /// clicking it removes the connection
+    pub hovered_connection: Option<(Id, Id)>,
+    /// when true, `run` keeps the outputs of the last successful run in
+    /// `last_outputs`, and `draw` labels every output node with its value
+    pub show_values: bool,
+    /// when true, `run` keeps `last_outputs` like with show_values, but
+    /// nothing is labeled. set while the item is open in the value panel,
+    /// which previews some of the outputs
+    pub keep_outputs: bool,
+    /// when true, every node has its short name drawn next to it,
+    /// not just the hovered one. toggled with L
+    pub show_port_labels: bool,
+    /// keyed by output node id. a RefCell because `run` only borrows self
+    pub last_outputs: RefCell<HashMap<Id, OutputResult>>,
+    /// set every update. blocks and nodes ignore the mouse while it's true
+    pub egui_wants_pointer: bool,
+    /// pans with the middle mouse button, zooms with the mouse wheel
+    pub camera: GraphCamera,
+    /// set when a number was dropped onto a point input. the next
+    /// click either picks the menu item or closes the menu
+    pub pending_insert: Option<PendingInsert>,
+    /// when set, `run` only runs this block and the blocks it depends on.
+    /// toggled with S while hovering a block
+    pub soloed: Option<Id>,
+    /// blocks picked by clicking on them, ctrl click adds or removes one.
+    /// the arrow keys nudge these (or the hovered block if none are picked)
+    pub selected: HashSet<Id>,
+    /// blocks that Delete was pressed on while other blocks depend on
+    /// them. pressing Delete again on the same blocks removes them
+    pub pending_delete: Option<Vec<Id>>,
+    /// messages for the user that aren't errors. drained by the caller
+    pub notices: Vec<String>,
 }
 
-fn run_fn_noop(_inputs: &Vec<&InputValue>, _ctx: &mut BlockRunContext) -> Option<Vec<OutputResult>> {
-    None
+fn run_fn_noop(_inputs: &Vec<&InputValue>, _ctx: &mut BlockRunContext) -> Result<Option<Vec<OutputResult>>, String> {
+    Ok(None)
 }
 
 impl BlockContext {
@@ -204,8 +496,10 @@ impl BlockContext {
             for output in b.outputs.iter_mut() {
                 output.parent_id = b_id;
             }
+            if b.note.is_none() {
+                graph.add(b_id);
+            }
             out_blocks.push(Some(b));
-            graph.add(b_id);
             block_ids.insert(b_id, i);
             i += 1;
         }
@@ -215,10 +509,23 @@ impl BlockContext {
             blocks: out_blocks,
             connections: HashMap::new(),
             inputs: HashMap::new(),
+            input_parents: HashMap::new(),
             graph,
             graph_order,
             block_ids,
             input_output: HashMap::new(),
+            hovered_connection: None,
+            show_values: false,
+            keep_outputs: false,
+            show_port_labels: false,
+            last_outputs: RefCell::new(HashMap::new()),
+            egui_wants_pointer: false,
+            camera: GraphCamera::default(),
+            pending_insert: None,
+            soloed: None,
+            selected: HashSet::new(),
+            pending_delete: None,
+            notices: vec![],
         }
     }
 
@@ -231,15 +538,97 @@ impl BlockContext {
             output.parent_id = b_id;
         }
         let block_index = self.blocks.len();
+        // notes never run, so they aren't part of the graph
+        if b.note.is_none() {
+            self.graph.add(b_id);
+        }
         self.blocks.push(Some(b));
-        self.graph.add(b_id);
         self.block_ids.insert(b_id, block_index);
         self.graph_order = self.graph.calculate_order_indices();
     }
 
+    /// a copy of every block and connection, with new ids throughout.
+    /// input values and the view are kept, the selection isn't
+    pub fn duplicate(&self) -> BlockContext {
+        let mut copy = BlockContext::new([]);
+        // old id -> new id, for blocks and nodes alike
+        let mut new_ids: HashMap<Id, Id> = HashMap::new();
+        for block in self.blocks.iter().flatten() {
+            let block_copy = block.duplicate();
+            new_ids.insert(block.id, block_copy.id);
+            for (old, new) in block.node_ids().into_iter().zip(block_copy.node_ids()) {
+                new_ids.insert(old, new);
+            }
+            copy.add_block(block_copy);
+        }
+        for ((input, output), pts) in self.connections.iter() {
+            let ids = (
+                new_ids.get(input),
+                self.input_parents.get(input).and_then(|p| new_ids.get(p)),
+                new_ids.get(output),
+                self.inputs.get(input).and_then(|p| new_ids.get(p)),
+            );
+            if let (Some(input), Some(input_parent), Some(output), Some(output_parent)) = ids {
+                copy.connect(*input, *input_parent, *output, *output_parent, *pts);
+            }
+        }
+        copy.graph_order = copy.graph.calculate_order_indices();
+        copy.camera = self.camera;
+        copy.camera.panning_from = None;
+        copy.show_values = self.show_values;
+        copy.show_port_labels = self.show_port_labels;
+        copy.soloed = self.soloed.and_then(|id| new_ids.get(&id).copied());
+        copy
+    }
+
+    /// the graph indices of the soloed block and everything it depends on,
+    /// directly or not. None if nothing is soloed
+    pub fn solo_set(&self) -> Option<HashSet<usize>> {
+        let soloed = self.soloed?;
+        let start = self.graph.nodes.iter().position(|n| n.value == soloed)?;
+        let mut set = HashSet::new();
+        let mut stack = vec![start];
+        while let Some(index) = stack.pop() {
+            if set.insert(index) {
+                stack.extend(self.graph.nodes[index].depends_on.iter().copied());
+            }
+        }
+        Some(set)
+    }
+    /// changes whenever a block, an input value, a connection or the solo
+    /// does. frozen timeline items use it to tell when to run again
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for block in self.blocks.iter().flatten() {
+            block.id.hash(&mut hasher);
+            for input in block.inputs.iter() {
+                input.id.hash(&mut hasher);
+                input.value.hash(&mut hasher);
+            }
+        }
+        let mut connections: Vec<(Id, Id)> = self.input_output.iter().map(|(i, o)| (*i, *o)).collect();
+        connections.sort_by_key(|(i, o)| (i.0, o.0));
+        connections.hash(&mut hasher);
+        self.soloed.hash(&mut hasher);
+        hasher.finish()
+    }
     pub fn run(&self, ctx: &mut BlockRunContext) -> Result<(), String> {
+        let outputs = self.run_collecting(ctx)?;
+        if self.show_values || self.keep_outputs {
+            *self.last_outputs.borrow_mut() = outputs;
+        }
+        Ok(())
+    }
+    /// same as `run`, but returns every output, keyed by output node id
+    pub fn run_collecting(&self, ctx: &mut BlockRunContext) -> Result<HashMap<Id, OutputResult>, String> {
         let mut previous_outputs: HashMap<Id, OutputResult> = HashMap::new();
+        let solo_set = self.solo_set();
         for graph_index in self.graph_order.iter() {
+            if let Some(solo_set) = &solo_set {
+                if !solo_set.contains(graph_index) {
+                    continue;
+                }
+            }
             let node = &self.graph.nodes[*graph_index];
             let id = node.value;
             let block_index = self.block_ids[&id];
@@ -285,13 +674,27 @@ impl BlockContext {
                             format!("My block {:?} depends on output node {:?}, but failed to find a value from the previous output map", block_id, output_id)
                         );
                     }
+                } else if let InputValue::Expression(src) = &input.value {
+                    // blocks only ever see the number it comes out to
+                    let x = ctx.eval_expression(src)
+                        .map_err(|e| format!("Block {} input {}: {}", block.name, input.name, e))?;
+                    // like 1/0. plenty of blocks can't take inf or NaN
+                    if !x.is_finite() {
+                        return Err(format!("Block {} input {}: expression is not a finite number", block.name, input.name));
+                    }
+                    let x = if input.is_integer { x.round() } else { x };
+                    this_input.push(InputResult::SingleValueOwned(InputValue::Number(x)));
                 } else {
                     // if there is none, then use the default value
                     this_input.push(InputResult::SingleValue(&input.value));
                 }
             }
 
-            let mut result_outputs = previous_outputs.clone();
+            // this block's outputs are collected separately because this_input
+            // is still borrowing from previous_outputs. only this block can write
+            // to its own output ids, so they are moved into previous_outputs
+            // once every iteration of this block has run.
+            let mut block_outputs: HashMap<Id, OutputResult> = HashMap::new();
             let (_, mut num_iterations) = has_iteration.unwrap_or((Id(0), 1));
             // flatten previous inputs to 1 item if this block wants them flattened
             if block.flatten_inputs {
@@ -322,11 +725,35 @@ impl BlockContext {
                                     }
                                     this_input_clone.push(InputResult::SingleValueOwned(InputValue::ListPoints(out)));
                                 }
-                                // TODO: give user error if they tried to flatten a non-flattenable type
-                                InputValue::Color(_) => todo!(),
-                                InputValue::Selection(_) => todo!(),
-                                InputValue::ListNumbers(_) => todo!(),
-                                InputValue::ListPoints(_) => todo!(),
+                                // each item is already a list, so concatenate them all
+                                InputValue::ListNumbers(_) => {
+                                    let mut out = vec![];
+                                    for val in x.iter() {
+                                        if let InputValue::ListNumbers(list) = val {
+                                            out.extend_from_slice(list);
+                                        }
+                                    }
+                                    this_input_clone.push(InputResult::SingleValueOwned(InputValue::ListNumbers(out)));
+                                }
+                                InputValue::ListPoints(_) => {
+                                    let mut out = vec![];
+                                    for val in x.iter() {
+                                        if let InputValue::ListPoints(list) = val {
+                                            out.extend_from_slice(list);
+                                        }
+                                    }
+                                    this_input_clone.push(InputResult::SingleValueOwned(InputValue::ListPoints(out)));
+                                }
+                                InputValue::Color(_) |
+                                InputValue::ListColors(_) |
+                                InputValue::Selection(_) |
+                                InputValue::Boolean(_) |
+                                InputValue::Text(_) |
+                                InputValue::Expression(_) => {
+                                    return Err(
+                                        format!("Block {} cannot flatten type {}", block.id.0, first.type_name())
+                                    );
+                                }
                             }
                         }
                         // if single value, we just put it as is.
@@ -353,15 +780,16 @@ impl BlockContext {
                         }
                     }
                 }
-                let res = (block.run_fn)(&input_vec, ctx);
+                let res = (block.run_fn)(&input_vec, ctx)
+                    .map_err(|e| format!("Block {} failed: {}", block.name, e))?;
                 if let Some(mut result) = res {
-                    // fill in the previous output map with this block's
+                    // fill in the output map with this block's
                     // values.
                     // the ids correspond to the indices of the inner result vec
                     for (result_index, result_value) in result.drain(..).enumerate() {
                         let result_id = block.outputs[result_index].id;
                         // if one exists before, we will need to append to it
-                        if let Some(previous_val) = result_outputs.get_mut(&result_id) {
+                        if let Some(previous_val) = block_outputs.get_mut(&result_id) {
                             match previous_val {
                                 OutputResult::SingleValue(v) => {
                                     let local_v = std::mem::replace(v, InputValue::Number(0.0));
@@ -389,18 +817,61 @@ impl BlockContext {
                             }
                         } else {
                             // otherwise just insert it
-                            result_outputs.insert(result_id, result_value);
+                            block_outputs.insert(result_id, result_value);
                         }
                     }
                 }
             }
-            previous_outputs = result_outputs;
+            previous_outputs.extend(block_outputs);
         }
-        Ok(())
+        Ok(previous_outputs)
+    }
+    /// a `block: output = value` line for every output in `outputs`,
+    /// in the order the blocks run
+    pub fn describe_outputs(&self, outputs: &HashMap<Id, OutputResult>) -> Vec<String> {
+        let mut lines = vec![];
+        for graph_index in self.graph_order.iter() {
+            let id = self.graph.nodes[*graph_index].value;
+            let block = match self.block_ids.get(&id).and_then(|i| self.blocks[*i].as_ref()) {
+                Some(b) => b,
+                None => continue,
+            };
+            for output in block.outputs.iter() {
+                if let Some(value) = outputs.get(&output.id) {
+                    lines.push(format!("{}: {} = {}", block.name, output.name, value.describe()));
+                }
+            }
+        }
+        lines
     }
 
-    pub fn update(&mut self) {
+    /// returns an error if a connection change made the block order invalid.
+    /// in that case the previous order is kept.
+    /// while `egui_wants_pointer` is true, nothing new can be hovered or
+    /// grabbed, but drags that already started still finish normally.
+    /// the same goes for key presses while `egui_wants_keyboard` is true
+    pub fn update(&mut self, egui_wants_pointer: bool, egui_wants_keyboard: bool) -> Result<(), String> {
+        self.egui_wants_pointer = egui_wants_pointer;
+        self.update_camera();
         let mut connections_changed = false;
+        if !egui_wants_pointer && self.pending_insert.is_some() {
+            let over_menu = self.insert_menu_bounds()
+                .is_some_and(|bounds| within_bounds_offset(self.mouse(), bounds).is_some());
+            if is_mouse_button_pressed(MouseButton::Left) {
+                let pending = self.pending_insert.take();
+                if let (true, Some(pending)) = (over_menu, pending) {
+                    self.insert_between(pending, crate::PtCombineBlock::to_draggable_block());
+                    connections_changed = true;
+                }
+            } else if is_key_pressed(KeyCode::Escape) {
+                self.pending_insert = None;
+            }
+            // the menu is on top of the blocks, so they don't get the mouse
+            if over_menu {
+                self.egui_wants_pointer = true;
+            }
+        }
+        let egui_wants_pointer = self.egui_wants_pointer;
         for i in 0..self.blocks.len() {
             let mut b = self.blocks[i].take();
             if let Some(block) = &mut b {
@@ -410,55 +881,428 @@ impl BlockContext {
             }
             self.blocks[i] = b;
         }
-        // if there were any connection changes, recalculate graph
-        if connections_changed {
-            self.graph.reset();
-            // first, need to add all of our blocks:
-            for b in self.blocks.iter() {
-                if let Some(b) = b {
-                    self.graph.add(b.id);
+        let hovered = if egui_wants_pointer {
+            None
+        } else {
+            let mouse = self.mouse();
+            self.blocks.iter().flatten().find(|b| within_bounds_offset(mouse, *b).is_some()).map(|b| b.id)
+        };
+        if !egui_wants_keyboard && is_key_pressed(KeyCode::S) {
+            if let Some(id) = hovered {
+                self.soloed = if self.soloed == Some(id) { None } else { Some(id) };
+            }
+        }
+        if !egui_wants_pointer && is_mouse_button_pressed(MouseButton::Left) {
+            let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+            self.click_select(hovered, ctrl);
+        }
+        if !egui_wants_keyboard && (is_key_pressed(KeyCode::Delete) || is_key_pressed(KeyCode::Backspace)) {
+            let mut targets: Vec<Id> = if self.selected.is_empty() {
+                hovered.into_iter().collect()
+            } else {
+                self.selected.iter().copied().collect()
+            };
+            targets.sort_by_key(|id| id.0);
+            if !targets.is_empty() {
+                self.request_delete(targets)?;
+            }
+        }
+        if !egui_wants_keyboard {
+            let step = if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) { 10.0 } else { 1.0 };
+            let mut nudge = (0.0, 0.0);
+            if is_key_pressed(KeyCode::Left) { nudge.0 -= step }
+            if is_key_pressed(KeyCode::Right) { nudge.0 += step }
+            if is_key_pressed(KeyCode::Up) { nudge.1 -= step }
+            if is_key_pressed(KeyCode::Down) { nudge.1 += step }
+            if nudge != (0.0, 0.0) {
+                if self.selected.is_empty() {
+                    if let Some(id) = hovered {
+                        self.move_blocks(&[id], nudge.0, nudge.1);
+                    }
+                } else {
+                    let selected: Vec<Id> = self.selected.iter().copied().collect();
+                    self.move_blocks(&selected, nudge.0, nudge.1);
                 }
             }
-            // next, for each block, find everything it depends on
-            for b in self.blocks.iter() {
-                let block = match b {
+        }
+        // clicking on a connection line cuts it. only if the click
+        // wasn't already used to drag a block or a node
+        self.hovered_connection = if egui_wants_pointer { None } else { self.connection_near(self.mouse()) };
+        if let Some((input, _)) = self.hovered_connection {
+            if self.currently_dragging.is_none() && is_mouse_button_pressed(MouseButton::Left) {
+                self.remove_connection(input);
+                self.hovered_connection = None;
+                connections_changed = true;
+            }
+        }
+        // connect/remove_connection keep the graph up to date,
+        // so only the order needs to be recalculated
+        if connections_changed {
+            self.sync_list_types();
+            self.recalculate_order()?;
+        }
+        Ok(())
+    }
+    /// call after loading a project made of these blocks so that
+    /// new ids never clash with the loaded ones. see `reset_id_counter`
+    pub fn reset_id_counter(&self) {
+        let ids = self.blocks.iter().flatten().flat_map(|block| {
+            std::iter::once(block.id)
+                .chain(block.inputs.iter().map(|n| n.id))
+                .chain(block.outputs.iter().map(|n| n.id))
+        });
+        reset_id_counter(ids);
+    }
+    /// the input or output node with this id
+    fn node(&self, id: Id) -> Option<&BlockConnectionNode> {
+        self.blocks.iter().flatten()
+            .flat_map(|block| block.inputs.iter().chain(block.outputs.iter()))
+            .find(|node| node.id == id)
+    }
+    /// gives every block with a connected any-list input the type of the
+    /// list going into it. connections out of its retyped outputs into
+    /// inputs that can't take the new type are removed
+    pub fn sync_list_types(&mut self) {
+        // a retyped output can retype the block after it, so
+        // this goes one block at a time until nothing changes
+        loop {
+            let mut retype = None;
+            for (i, block) in self.blocks.iter().enumerate() {
+                let block = match block {
                     Some(b) => b,
                     None => continue,
                 };
-                for input in block.inputs.iter() {
-                    // if there's an input connection of one of my input ids
-                    // that means i depend on the parent of that output
-                    if let Some(parent_id) = self.inputs.get(&input.id) {
-                        self.graph.add_dependency(block.id, *parent_id);
+                for input in block.inputs.iter().filter(|n| n.is_any_list) {
+                    let list = self.input_output.get(&input.id).and_then(|output| self.node(*output));
+                    if let Some(list) = list.map(|n| &n.value).filter(|v| v.is_list()) {
+                        if list.type_name() != input.value.type_name() && retype.is_none() {
+                            retype = Some((i, list.clone()));
+                        }
                     }
                 }
             }
-            self.graph_order = self.graph.calculate_order_indices();
-            // TODO: check if it's valid
-
-            // TODO: remove debugging
-            macroquad::logging::info!("New order:");
-            for graph_index in self.graph_order.iter() {
-                let node = &self.graph.nodes[*graph_index];
-                let id = node.value;
-                let block_index = self.block_ids[&id];
-                let block = &self.blocks[block_index];
-                if let Some(block) = block {
-                    macroquad::logging::info!("{}", block.name);
+            let (i, list) = match retype {
+                Some(x) => x,
+                None => return,
+            };
+            let changed = self.blocks[i].as_mut().map_or(vec![], |block| block.retype_list(&list));
+            let downstream: Vec<(Id, Id)> = self.connections.keys()
+                .filter(|(_, output)| changed.contains(output))
+                .copied()
+                .collect();
+            for (input, output) in downstream {
+                let misfit = match (self.node(input), self.node(output)) {
+                    (Some(input), Some(output)) if link_kind(&input.value, input.is_any_list, &output.value) != Some(LinkKind::Direct) => {
+                        Some((input.name.clone(), output.value.type_name()))
+                    }
+                    _ => None,
+                };
+                if let Some((name, type_name)) = misfit {
+                    self.remove_connection(input);
+                    self.notices.push(format!("Removed the connection into {}, it can't take a {}", name, type_name));
+                }
+            }
+        }
+    }
+    /// rebuilds the graph from scratch out of the current connections
+    /// rebuilds the graph from the blocks and connections. if the new
+    /// order is invalid, the previous graph and order are kept
+    pub fn recalculate_graph(&mut self) -> Result<(), String> {
+        self.sync_list_types();
+        let mut graph = Graph::default();
+        // first, need to add all of our blocks:
+        for b in self.blocks.iter().flatten().filter(|b| b.note.is_none()) {
+            graph.add(b.id);
+        }
+        // next, for each block, find everything it depends on
+        for b in self.blocks.iter() {
+            let block = match b {
+                Some(b) => b,
+                None => continue,
+            };
+            for input in block.inputs.iter() {
+                // if there's an input connection of one of my input ids
+                // that means i depend on the parent of that output
+                if let Some(parent_id) = self.inputs.get(&input.id) {
+                    graph.add_dependency(block.id, *parent_id);
                 }
             }
         }
+        let order = self.order_of(&graph)?;
+        self.graph = graph;
+        self.set_order(order);
+        Ok(())
+    }
+    pub fn recalculate_order(&mut self) -> Result<(), String> {
+        let order = self.order_of(&self.graph)?;
+        self.set_order(order);
+        Ok(())
+    }
+    /// the order `graph`'s blocks run in, or an error if there is no valid one
+    fn order_of(&self, graph: &Graph<Id>) -> Result<Vec<usize>, String> {
+        let block_name = |graph_index: usize| {
+            let id = graph.nodes[graph_index].value;
+            match &self.blocks[self.block_ids[&id]] {
+                Some(b) => b.name.clone(),
+                None => id.to_string(),
+            }
+        };
+        let order = match graph.try_calculate_order_indices() {
+            Ok(order) => order,
+            Err(cyclic) => {
+                let names: Vec<String> = cyclic.into_iter().map(block_name).collect();
+                let e = format!(
+                    "Blocks {} depend on a cycle and cannot be ordered. Keeping the previous order",
+                    names.join(", ")
+                );
+                macroquad::logging::error!("{}", e);
+                return Err(e);
+            }
+        };
+        if let Some((node_index, dependency_index)) = graph.find_invalid_dependency(&order) {
+            let e = format!(
+                "Invalid block order: {} depends on {} but would run before it. Keeping the previous order",
+                block_name(node_index), block_name(dependency_index)
+            );
+            macroquad::logging::error!("{}", e);
+            return Err(e);
+        }
+        Ok(order)
+    }
+    fn set_order(&mut self, order: Vec<usize>) {
+        self.graph_order = order;
+
+        // TODO: remove debugging
+        macroquad::logging::info!("New order:");
+        for graph_index in self.graph_order.iter() {
+            let node = &self.graph.nodes[*graph_index];
+            let id = node.value;
+            let block_index = self.block_ids[&id];
+            let block = &self.blocks[block_index];
+            if let Some(block) = block {
+                macroquad::logging::info!("{}", block.name);
+            }
+        }
     }
     pub fn draw(&mut self) {
-        for (_, (pta, ptb)) in self.connections.iter() {
+        set_camera(&self.camera.camera2d());
+        for (key, (pta, ptb)) in self.connections.iter() {
             let (x1, y1) = *pta;
             let (x2, y2) = *ptb;
-            draw_line(x1, y1, x2, y2, 1.0, BLACK);
+            if self.hovered_connection == Some(*key) {
+                draw_line(x1, y1, x2, y2, 3.0, RED);
+            } else {
+                draw_line(x1, y1, x2, y2, 1.0, BLACK);
+            }
         }
         for b in self.blocks.iter() {
             if let Some(block) = b {
-                block.draw();
+                block.draw(self.mouse(), self.show_port_labels);
+                if self.selected.contains(&block.id) {
+                    draw_rectangle_lines(block.x - 1.0, block.y - 1.0, block.width + 2.0, block.height() + 2.0, 2.0, BLUE);
+                }
+            }
+        }
+        if let Some(solo_set) = self.solo_set() {
+            for (index, node) in self.graph.nodes.iter().enumerate() {
+                let block = match self.block_ids.get(&node.value).and_then(|i| self.blocks[*i].as_ref()) {
+                    Some(b) => b,
+                    None => continue,
+                };
+                if Some(block.id) == self.soloed {
+                    draw_rectangle_lines(block.x - 2.0, block.y - 2.0, block.width + 4.0, block.height() + 4.0, 3.0, ORANGE);
+                } else if !solo_set.contains(&index) {
+                    // blocks that don't run while soloing are faded out
+                    draw_rectangle(block.x, block.y, block.width, block.height(), Color::new(1.0, 1.0, 1.0, 0.6));
+                }
+            }
+        }
+        if self.show_values {
+            self.draw_values();
+        }
+        if let Some((x, y, w, h)) = self.insert_menu_bounds() {
+            let hovered = within_bounds_offset(self.mouse(), (x, y, w, h)).is_some();
+            draw_rectangle(x, y, w, h, if hovered { LIGHTGRAY } else { WHITE });
+            draw_rectangle_lines(x, y, w, h, 1.0, BLACK);
+            draw_text(INSERT_PT_COMBINE_TEXT, x + 4.0, y + h - 8.0, VALUE_FONT_SIZE, BLACK);
+        }
+        set_default_camera();
+    }
+    /// writes the last value of every output under its block,
+    /// one line per output
+    pub fn draw_values(&self) {
+        let outputs = self.last_outputs.borrow();
+        // below the port labels, if they're shown
+        let first_line = if self.show_port_labels { 2.0 } else { 1.0 };
+        for block in self.blocks.iter().flatten() {
+            let mut line = first_line;
+            block.iter_connections(Outputs, |_, y, node| {
+                if let Some(value) = outputs.get(&node.id) {
+                    let text = format!("{}: {}", node.name, value.describe());
+                    let text_y = y + CONNECTION_SIZE + line * VALUE_FONT_SIZE;
+                    draw_text(&text, block.x, text_y, VALUE_FONT_SIZE, DARKBLUE);
+                    line += 1.0;
+                }
+            });
+        }
+    }
+    /// the mouse position in the graph
+    pub fn mouse(&self) -> (f32, f32) {
+        self.camera.to_graph(mouse_position())
+    }
+    fn update_camera(&mut self) {
+        let mouse = mouse_position();
+        if let Some(from) = self.camera.panning_from {
+            self.camera.offset.0 += mouse.0 - from.0;
+            self.camera.offset.1 += mouse.1 - from.1;
+        }
+        if is_mouse_button_released(MouseButton::Middle) {
+            self.camera.panning_from = None;
+        } else if self.camera.panning_from.is_some()
+            || (!self.egui_wants_pointer && is_mouse_button_pressed(MouseButton::Middle))
+        {
+            self.camera.panning_from = Some(mouse);
+        }
+        if self.egui_wants_pointer {
+            return;
+        }
+        // only the direction is used, the amount per step differs between platforms
+        let (_, wheel) = mouse_wheel();
+        if wheel > 0.0 {
+            self.camera.zoom_around(mouse, ZOOM_STEP);
+        } else if wheel < 0.0 {
+            self.camera.zoom_around(mouse, 1.0 / ZOOM_STEP);
+        }
+    }
+    /// the smallest rect around every block, including their nodes.
+    /// None if there are no blocks
+    pub fn blocks_bounds(&self) -> Option<Rect> {
+        let mut bounds: Option<Rect> = None;
+        for block in self.blocks.iter().flatten() {
+            let rect = block.outer_bounds();
+            bounds = Some(match bounds {
+                Some(b) => b.combine_with(rect),
+                None => rect,
+            });
+        }
+        bounds
+    }
+    /// pans and zooms so that every block is visible within `area`
+    /// of the screen
+    pub fn zoom_to_fit(&mut self, area: Rect) {
+        if let Some(bounds) = self.blocks_bounds() {
+            self.camera = GraphCamera::fit(bounds, area, ZOOM_FIT_MARGIN);
+        }
+    }
+    /// the (input, output) key of the closest connection within
+    /// CONNECTION_HOVER_DISTANCE (on the screen) of pt
+    pub fn connection_near(&self, pt: (f32, f32)) -> Option<(Id, Id)> {
+        let mut closest = None;
+        let mut closest_distance = CONNECTION_HOVER_DISTANCE / self.camera.zoom;
+        for (key, (pta, ptb)) in self.connections.iter() {
+            let distance = distance_to_segment(pt, *pta, *ptb);
+            if distance <= closest_distance {
+                closest_distance = distance;
+                closest = Some(*key);
+            }
+        }
+        closest
+    }
+    /// how many connections go from the given blocks into blocks
+    /// that aren't part of them
+    pub fn dependent_connections(&self, block_ids: &[Id]) -> usize {
+        self.connections.keys().filter(|(input, _)| {
+            let from_target = self.inputs.get(input).is_some_and(|id| block_ids.contains(id));
+            let into_target = self.input_parents.get(input).is_some_and(|id| block_ids.contains(id));
+            from_target && !into_target
+        }).count()
+    }
+    /// deletes the blocks right away if nothing else depends on them.
+    /// otherwise the first request only warns, and asking to delete the
+    /// same blocks again deletes them along with the dependent connections
+    pub fn request_delete(&mut self, block_ids: Vec<Id>) -> Result<(), String> {
+        let dependents = self.dependent_connections(&block_ids);
+        if dependents > 0 && self.pending_delete.as_ref() != Some(&block_ids) {
+            self.notices.push(format!(
+                "{} connections depend on this. Press Delete again to remove it anyway",
+                dependents
+            ));
+            self.pending_delete = Some(block_ids);
+            return Ok(());
+        }
+        self.pending_delete = None;
+        for id in block_ids.iter() {
+            self.remove_block(*id);
+        }
+        if dependents > 0 {
+            self.notices.push(format!("Removed {} connections that depended on this block", dependents));
+        }
+        self.recalculate_graph()
+    }
+    /// removes the block and every connection to or from it. the graph
+    /// still has to be recalculated afterwards, see `recalculate_graph`
+    pub fn remove_block(&mut self, id: Id) {
+        let block_index = match self.block_ids.remove(&id) {
+            Some(i) => i,
+            None => return,
+        };
+        let block = match self.blocks[block_index].take() {
+            Some(b) => b,
+            None => return,
+        };
+        let node_ids = block.node_ids();
+        let keys: Vec<(Id, Id)> = self.connections.keys()
+            .filter(|(input, output)| node_ids.contains(input) || node_ids.contains(output))
+            .copied()
+            .collect();
+        for (input, _) in keys {
+            // an input only ever has one connection, so removing
+            // by the input id removes exactly this connection
+            self.remove_connection(input);
+        }
+        self.selected.remove(&id);
+        if self.soloed == Some(id) {
+            self.soloed = None;
+        }
+        if self.hovered_connection.is_some_and(|(input, output)| node_ids.contains(&input) || node_ids.contains(&output)) {
+            self.hovered_connection = None;
+        }
+        let mut last_outputs = self.last_outputs.borrow_mut();
+        for node_id in node_ids.iter() {
+            last_outputs.remove(node_id);
+        }
+    }
+    /// clicking a block selects only it, ctrl clicking toggles it
+    /// in the selection. clicking on nothing clears the selection
+    pub fn click_select(&mut self, clicked: Option<Id>, ctrl: bool) {
+        match clicked {
+            Some(id) if ctrl => {
+                if !self.selected.remove(&id) {
+                    self.selected.insert(id);
+                }
+            }
+            // clicking a block that's already selected keeps the
+            // others selected too
+            Some(id) if self.selected.contains(&id) => {}
+            Some(id) => {
+                self.selected.clear();
+                self.selected.insert(id);
             }
+            None if ctrl => {}
+            None => self.selected.clear(),
+        }
+    }
+    /// moves the blocks with these ids, along with their ends of any connections
+    pub fn move_blocks(&mut self, block_ids: &[Id], diff_x: f32, diff_y: f32) {
+        for id in block_ids {
+            let block = match self.block_ids.get(id).and_then(|i| self.blocks[*i].as_mut()) {
+                Some(b) => b,
+                None => continue,
+            };
+            block.x += diff_x;
+            block.y += diff_y;
+            let ids = block.node_ids();
+            self.update_connection_positions(ids, diff_x, diff_y);
         }
     }
     pub fn update_connection_positions(&mut self, ids: Vec<Id>, diff_x: f32, diff_y: f32) {
@@ -475,6 +1319,63 @@ impl BlockContext {
             }
         }
     }
+    /// arranges the blocks top to bottom by how deep they are in the graph.
+    /// blocks with no dependencies form the first row, and every other
+    /// block goes one row below the deepest block it depends on. rows
+    /// (instead of columns) because outputs are on the bottom of a
+    /// block and inputs on the top. each row is as tall as its tallest block
+    pub fn auto_layout(&mut self, origin: (f32, f32)) {
+        let mut depths: HashMap<usize, usize> = HashMap::new();
+        // (block index, depth) in the order they're placed
+        let mut placed: Vec<(usize, usize)> = vec![];
+        // the tallest block in each row
+        let mut row_heights: Vec<f32> = vec![];
+        for graph_index in self.graph_order.clone() {
+            let node = &self.graph.nodes[graph_index];
+            // cycles are at the end of the order, so a dependency
+            // might not have a depth yet. treat those as the first row
+            let depth = node.depends_on.iter()
+                .map(|dep| depths.get(dep).map(|d| d + 1).unwrap_or(0))
+                .max()
+                .unwrap_or(0);
+            depths.insert(graph_index, depth);
+            let block_index = match self.block_ids.get(&node.value) {
+                Some(i) => *i,
+                None => continue,
+            };
+            let block = match &self.blocks[block_index] {
+                Some(b) => b,
+                None => continue,
+            };
+            if row_heights.len() <= depth {
+                row_heights.resize(depth + 1, BLOCK_HEIGHT);
+            }
+            row_heights[depth] = row_heights[depth].max(block.height());
+            placed.push((block_index, depth));
+        }
+        let mut row_y = Vec::with_capacity(row_heights.len());
+        let mut y = origin.1;
+        for height in row_heights.iter() {
+            row_y.push(y);
+            y += height + LAYOUT_SPACING_Y;
+        }
+        // x position of the next block in each row
+        let mut row_x: Vec<f32> = vec![origin.0; row_heights.len()];
+        for (block_index, depth) in placed {
+            let block = match &mut self.blocks[block_index] {
+                Some(b) => b,
+                None => continue,
+            };
+            let new_x = row_x[depth];
+            let new_y = row_y[depth];
+            row_x[depth] += block.width + LAYOUT_SPACING_X;
+            let (diff_x, diff_y) = (new_x - block.x, new_y - block.y);
+            block.x = new_x;
+            block.y = new_y;
+            let ids = block.node_ids();
+            self.update_connection_positions(ids, diff_x, diff_y);
+        }
+    }
     pub fn remove_connection(&mut self, id: Id) {
         let mut remove_key = None;
         for (ids, _) in self.connections.iter() {
@@ -484,6 +1385,9 @@ impl BlockContext {
             }
         }
         if let Some(key) = remove_key {
+            if let (Some(input_parent), Some(output_parent)) = (self.input_parents.remove(&key.0), self.inputs.get(&key.0)) {
+                self.graph.remove_dependency(input_parent, *output_parent);
+            }
             self.inputs.remove(&key.0);
             self.inputs.remove(&key.1);
             self.input_output.remove(&key.0);
@@ -491,50 +1395,126 @@ impl BlockContext {
             self.connections.remove(&key);
         }
     }
-    pub fn can_connect(&mut self, my_parent: Id, my_id: Id, my_type: ConnectionType, my_value_type: &InputValue, my_pos: (f32, f32)) {
+    pub fn can_connect(&mut self, my_parent: Id, my_id: Id, my_type: ConnectionType, my_value_type: &InputValue, my_any_list: bool, my_pos: (f32, f32)) {
         macroquad::logging::info!("Trying to connect!");
+        let mouse = self.mouse();
         for b in self.blocks.iter_mut() {
             if let Some(block) = b {
                 let mut found_connection = None;
+                let mut found_coercion = None;
                 // check if the position where the mouse currently is
                 // matches the opposite connection type of the current block.
                 // ie: if my_type is input, only allow connections to outputs
                 // and vice versa
                 block.iter_connections_opposite(my_type, |x, y, connection| {
                     // if the type does not match, do not allow the connection
-                    match (my_value_type, &connection.value) {
-                        (InputValue::Number(_), InputValue::Number(_)) |
-                        (InputValue::Point(_), InputValue::Point(_)) |
-                        (InputValue::Color(_), InputValue::Color(_)) |
-                        (InputValue::Selection(_), InputValue::Selection(_)) => {},
-                        (InputValue::ListNumbers(_), InputValue::ListNumbers(_)) => {},
-                        (InputValue::ListPoints(_), InputValue::ListPoints(_)) => {},
-                        _ => return,
+                    let kind = match my_type {
+                        Inputs => link_kind(my_value_type, my_any_list, &connection.value),
+                        Outputs => link_kind(&connection.value, connection.is_any_list, my_value_type),
                     };
                     let bounds = (x, y, CONNECTION_SIZE, CONNECTION_SIZE);
-                    if mouse_within_bounds(bounds) {
-                        found_connection = Some((connection.parent_id, (my_id, connection.id), (my_pos, (x, y))));
+                    if within_bounds_offset(mouse, bounds).is_none() {
+                        return;
+                    }
+                    let found = Some((connection.parent_id, (my_id, connection.id), (my_pos, (x, y))));
+                    match kind {
+                        Some(LinkKind::Direct) => found_connection = found,
+                        Some(LinkKind::ThroughPtCombine) => found_coercion = found,
+                        None => {}
                     }
                 });
                 if let Some((connection_parent, ids, pts)) = found_connection {
-                    let (input, output, parent) = match my_type {
-                        Inputs => (ids.0, ids.1, connection_parent), // i am the input
-                        Outputs => (ids.1, ids.0, my_parent), // the other node is the input
+                    let (input, input_parent, output, output_parent) = match my_type {
+                        Inputs => (ids.0, my_parent, ids.1, connection_parent), // i am the input
+                        Outputs => (ids.1, connection_parent, ids.0, my_parent), // the other node is the input
                     };
                     // prevent connecting to an existing input.
                     // each input can only have 1
                     if self.inputs.contains_key(&input) {
                         break;
                     }
+                    if self.would_create_cycle(input_parent, output_parent) {
+                        macroquad::logging::info!("Not connecting, the blocks would depend on each other");
+                        break;
+                    }
                     macroquad::logging::info!("Connected!");
-                    self.inputs.insert(input, parent);
-                    self.input_output.insert(input, output);
-                    self.connections.insert(ids, pts);
+                    let pts = match my_type {
+                        Inputs => pts,
+                        Outputs => (pts.1, pts.0),
+                    };
+                    self.connect(input, input_parent, output, output_parent, pts);
                     break;
                 }
+                if let Some((connection_parent, ids, pts)) = found_coercion {
+                    let (input, input_parent, input_pos, output, output_parent, output_pos) = match my_type {
+                        Inputs => (ids.0, my_parent, pts.0, ids.1, connection_parent, pts.1),
+                        Outputs => (ids.1, connection_parent, pts.1, ids.0, my_parent, pts.0),
+                    };
+                    // the same checks as a direct connection, the PtCombine
+                    // would only be in between
+                    if !self.inputs.contains_key(&input) && !self.would_create_cycle(input_parent, output_parent) {
+                        self.pending_insert = Some(PendingInsert {
+                            output,
+                            output_parent,
+                            output_pos,
+                            input,
+                            input_parent,
+                            input_pos,
+                            menu_pos: mouse,
+                        });
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    /// where the insert menu is drawn, in the graph. None if it isn't open
+    pub fn insert_menu_bounds(&self) -> Option<(f32, f32, f32, f32)> {
+        let pending = self.pending_insert?;
+        let measured = measure_text(INSERT_PT_COMBINE_TEXT, None, VALUE_FONT_SIZE as u16, 1.0);
+        let padding = 4.0;
+        Some((pending.menu_pos.0, pending.menu_pos.1, measured.width + 2.0 * padding, VALUE_FONT_SIZE + 2.0 * padding))
+    }
+    /// adds the block (a PtCombine) halfway between the nodes, feeds
+    /// the output into its first input, and connects its first output
+    /// to the input
+    pub fn insert_between(&mut self, pending: PendingInsert, mut combine: DraggableBlock) {
+        combine.x = (pending.output_pos.0 + pending.input_pos.0) / 2.0 - combine.width / 2.0;
+        combine.y = (pending.output_pos.1 + pending.input_pos.1) / 2.0 - BLOCK_HEIGHT / 2.0;
+        let combine_id = combine.id;
+        // the first node of each is at the block's left edge
+        let x_pos = (combine.x, combine.y - CONNECTION_SIZE);
+        let pt_pos = (combine.x, combine.y + BLOCK_HEIGHT);
+        let (x_input, pt_output) = (combine.inputs[0].id, combine.outputs[0].id);
+        self.add_block(combine);
+        self.connect(x_input, combine_id, pending.output, pending.output_parent, (x_pos, pending.output_pos));
+        self.connect(pending.input, pending.input_parent, pt_output, combine_id, (pending.input_pos, pt_pos));
+    }
+    /// true if making the `input_parent` block depend on the
+    /// `output_parent` block would create a cycle
+    pub fn would_create_cycle(&mut self, input_parent: Id, output_parent: Id) -> bool {
+        if input_parent == output_parent {
+            return true;
+        }
+        match (self.graph.index_of(&input_parent), self.graph.index_of(&output_parent)) {
+            (Some(input_i), Some(output_i)) => {
+                let output_deps = &self.graph.nodes[output_i].depends_on;
+                self.graph.does_transient_dependency_exist(output_deps, input_i)
             }
+            _ => false,
         }
     }
+    /// connects the output node to the input node. `pts` are the
+    /// (input, output) positions of the line drawn between them.
+    /// the dependency is added to the graph, but the order
+    /// is not recalculated, see `recalculate_order`
+    pub fn connect(&mut self, input: Id, input_parent: Id, output: Id, output_parent: Id, pts: ((f32, f32), (f32, f32))) {
+        self.graph.add_dependency(input_parent, output_parent);
+        self.inputs.insert(input, output_parent);
+        self.input_parents.insert(input, input_parent);
+        self.input_output.insert(input, output);
+        self.connections.insert((input, output), pts);
+    }
     pub fn can_drag(&mut self, id: Id) -> bool {
         if self.currently_dragging.is_none() {
             self.currently_dragging = Some(id.0);
@@ -551,6 +1531,9 @@ impl BlockContext {
     }
 }
 
+/// what a block does when it runs. see `DraggableBlock::run_fn`
+pub type RunFn = fn(inputs: &Vec<&InputValue>, ctx: &mut BlockRunContext) -> Result<Option<Vec<OutputResult>>, String>;
+
 pub struct DraggableBlock {
     pub id: Id,
     pub name: String,
@@ -563,7 +1546,14 @@ pub struct DraggableBlock {
     pub being_dragged_from: Option<(f32, f32)>,
     pub inputs: Vec<BlockConnectionNode>,
     pub outputs: Vec<BlockConnectionNode>,
-    pub run_fn: fn(inputs: &Vec<&InputValue>, ctx: &mut BlockRunContext) -> Option<Vec<OutputResult>>,
+    /// if true, the nodes are stacked down the sides of the block, inputs
+    /// on the left and outputs on the right, instead of in rows above and
+    /// below it. for blocks with too many inputs to fit in a row
+    pub tall: bool,
+    /// the text of a sticky note. notes have no nodes, never run, and
+    /// are left out of the graph. they're only there to be read
+    pub note: Option<String>,
+    pub run_fn: RunFn,
 }
 
 impl Default for DraggableBlock {
@@ -578,8 +1568,10 @@ impl Default for DraggableBlock {
             width: 100.0,
             flatten_inputs: false,
             being_dragged_from: None,
+            tall: false,
             inputs: vec![],
             outputs: vec![],
+            note: None,
             run_fn: run_fn_noop,
         }
     }
@@ -590,84 +1582,235 @@ pub enum ConnectionType {
     Inputs,
     Outputs,
 }
+
+/// the side of a block that a node sits on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PortSide {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
 use ConnectionType::*;
 
 use crate::{InputValue, dependency_resolution::{Graph, Node}, BlockRunContext};
 
 impl DraggableBlock {
-    pub fn iter_connections(&self, connection_type: ConnectionType, mut cb: impl FnMut(f32, f32, &BlockConnectionNode)) {
-        let mut x = self.x;
-        let mut y = self.y - CONNECTION_SIZE;
-        let iterator = match connection_type {
-            Inputs => self.inputs.iter(),
-            Outputs => {
-                y += BLOCK_HEIGHT + CONNECTION_SIZE;
-                self.outputs.iter()
-            },
+    /// a block with just these nodes and run function. the blocks' own
+    /// `to_draggable_block` measure text, which needs a window
+    #[cfg(test)]
+    pub fn for_test(inputs: Vec<BlockConnectionNode>, outputs: Vec<BlockConnectionNode>, run_fn: RunFn) -> Self {
+        Self { inputs, outputs, run_fn, ..Default::default() }
+    }
+    /// gives the any-list inputs and `follows_list` outputs the type of
+    /// `list`. returns the outputs whose type changed
+    pub fn retype_list(&mut self, list: &InputValue) -> Vec<Id> {
+        let (empty, element) = match list.empty_list() {
+            Some(x) => x,
+            None => return vec![],
         };
-        for input_connection in iterator {
-            cb(x, y, input_connection);
-            x += CONNECTION_SIZE + CONNECTION_SPACING;
-        }
-    }
-    pub fn iter_connections_opposite(&self, connection_type: ConnectionType, mut cb: impl FnMut(f32, f32, &BlockConnectionNode)) {
-        let mut x = self.x;
-        let mut y = self.y - CONNECTION_SIZE;
-        let iterator = match connection_type {
-            Outputs => self.inputs.iter(),
-            Inputs => {
-                y += BLOCK_HEIGHT + CONNECTION_SIZE;
-                self.outputs.iter()
-            },
+        for input in self.inputs.iter_mut().filter(|n| n.is_any_list) {
+            if input.value.type_name() != empty.type_name() {
+                input.value = empty.clone();
+            }
+        }
+        let mut changed = vec![];
+        for output in self.outputs.iter_mut() {
+            let value = match output.follows_list {
+                Some(ListFollow::List) => &empty,
+                Some(ListFollow::Element) => &element,
+                None => continue,
+            };
+            if output.value.type_name() != value.type_name() {
+                output.value = value.clone();
+                changed.push(output.id);
+            }
+        }
+        changed
+    }
+    /// where the node at `index` of the inputs or outputs is drawn.
+    /// nodes go in a row above (inputs) and below (outputs) the block,
+    /// or in a column down its sides if the block is tall
+    pub fn port_position(&self, connection_type: ConnectionType, index: usize) -> (f32, f32) {
+        let i = index as f32;
+        if self.tall {
+            let x = match connection_type {
+                Inputs => self.x - CONNECTION_SIZE,
+                Outputs => self.x + self.width,
+            };
+            (x, self.y + BLOCK_HEIGHT + i * (CONNECTION_SIZE + TALL_PORT_SPACING))
+        } else {
+            let y = match connection_type {
+                Inputs => self.y - CONNECTION_SIZE,
+                Outputs => self.y + BLOCK_HEIGHT,
+            };
+            (self.x + i * (CONNECTION_SIZE + CONNECTION_SPACING), y)
+        }
+    }
+    /// which side of the block the nodes are on
+    pub fn port_side(&self, connection_type: ConnectionType) -> PortSide {
+        match (self.tall, connection_type) {
+            (false, Inputs) => PortSide::Top,
+            (false, Outputs) => PortSide::Bottom,
+            (true, Inputs) => PortSide::Left,
+            (true, Outputs) => PortSide::Right,
+        }
+    }
+    pub fn iter_connections(&self, connection_type: ConnectionType, mut cb: impl FnMut(f32, f32, &BlockConnectionNode)) {
+        let nodes = match connection_type {
+            Inputs => &self.inputs,
+            Outputs => &self.outputs,
         };
-        for input_connection in iterator {
-            cb(x, y, input_connection);
-            x += CONNECTION_SIZE + CONNECTION_SPACING;
+        for (i, node) in nodes.iter().enumerate() {
+            let (x, y) = self.port_position(connection_type, i);
+            cb(x, y, node);
         }
     }
+    pub fn iter_connections_opposite(&self, connection_type: ConnectionType, cb: impl FnMut(f32, f32, &BlockConnectionNode)) {
+        let opposite = match connection_type {
+            Inputs => Outputs,
+            Outputs => Inputs,
+        };
+        self.iter_connections(opposite, cb);
+    }
     pub fn iter_connections_mut(&mut self, connection_type: ConnectionType, mut cb: impl FnMut(f32, f32, &mut BlockConnectionNode)) {
-        let mut x = self.x;
-        let mut y = self.y - CONNECTION_SIZE;
-        let iterator = match connection_type {
-            Inputs => self.inputs.iter_mut(),
-            Outputs => {
-                y += BLOCK_HEIGHT + CONNECTION_SIZE;
-                self.outputs.iter_mut()
-            },
+        let count = match connection_type {
+            Inputs => self.inputs.len(),
+            Outputs => self.outputs.len(),
         };
-        for input_connection in iterator {
-            cb(x, y, input_connection);
-            x += CONNECTION_SIZE + CONNECTION_SPACING;
+        let positions: Vec<(f32, f32)> = (0..count).map(|i| self.port_position(connection_type, i)).collect();
+        let nodes = match connection_type {
+            Inputs => &mut self.inputs,
+            Outputs => &mut self.outputs,
+        };
+        for (node, (x, y)) in nodes.iter_mut().zip(positions) {
+            cb(x, y, node);
         }
     }
 
+    /// a copy of this block with the same input values, but new ids for
+    /// it and its nodes. connections aren't copied
+    pub fn duplicate(&self) -> DraggableBlock {
+        let id = get_id();
+        let copy_node = |node: &BlockConnectionNode| BlockConnectionNode {
+            id: get_id(),
+            parent_id: id,
+            name: node.name.clone(),
+            value: node.value.clone(),
+            connection_type: node.connection_type,
+            is_being_hovered: false,
+            is_dragging_line: false,
+            is_integer: node.is_integer,
+            is_list_editable: node.is_list_editable,
+            is_button: node.is_button,
+            is_any_list: node.is_any_list,
+            follows_list: node.follows_list,
+        };
+        // block names start with their id
+        let name = match self.name.strip_prefix(&self.id.to_string()) {
+            Some(rest) => format!("{}{}", id, rest),
+            None => self.name.clone(),
+        };
+        DraggableBlock {
+            id,
+            name,
+            name_y_offset: self.name_y_offset,
+            color: self.color,
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            flatten_inputs: self.flatten_inputs,
+            being_dragged_from: None,
+            tall: self.tall,
+            inputs: self.inputs.iter().map(copy_node).collect(),
+            outputs: self.outputs.iter().map(copy_node).collect(),
+            note: self.note.clone(),
+            run_fn: self.run_fn,
+        }
+    }
+
+    /// the ids of all of this block's input and output nodes
+    pub fn node_ids(&self) -> Vec<Id> {
+        self.inputs.iter().chain(self.outputs.iter()).map(|n| n.id).collect()
+    }
+
     pub fn get_text(&self) -> &str {
         self.name.as_str()
     }
 
+    /// notes grow to fit their lines and tall blocks to fit their
+    /// nodes, every other block is BLOCK_HEIGHT tall
+    pub fn height(&self) -> f32 {
+        if let Some(note) = &self.note {
+            let lines = note.lines().count().max(1) as f32;
+            return (lines * NOTE_LINE_HEIGHT + 2.0 * NOTE_PADDING).max(BLOCK_HEIGHT);
+        }
+        if self.tall {
+            let ports = self.inputs.len().max(self.outputs.len()) as f32;
+            return BLOCK_HEIGHT + ports * (CONNECTION_SIZE + TALL_PORT_SPACING);
+        }
+        BLOCK_HEIGHT
+    }
+    /// the block along with its nodes
+    pub fn outer_bounds(&self) -> Rect {
+        if self.tall {
+            Rect::new(self.x - CONNECTION_SIZE, self.y, self.width + 2.0 * CONNECTION_SIZE, self.height())
+        } else {
+            Rect::new(self.x, self.y - CONNECTION_SIZE, self.width, self.height() + 2.0 * CONNECTION_SIZE)
+        }
+    }
+
     pub fn calculate_width(&mut self) {
+        if let Some(note) = &self.note {
+            let widest = note.lines()
+                .map(|line| measure_text(line, None, NOTE_LINE_HEIGHT as u16, 1.0).width)
+                .fold(0.0, f32::max);
+            self.width = (widest + 2.0 * NOTE_PADDING).max(BLOCK_HEIGHT);
+            return;
+        }
         let max = self.inputs.len().max(self.outputs.len());
         let text_measured = measure_text(&self.get_text(), None, FONT_SIZE, 1.0);
         self.name_y_offset = text_measured.offset_y;
-        self.width = (max as f32) * (CONNECTION_SIZE + CONNECTION_SPACING);
+        self.width = if self.tall {
+            TALL_BLOCK_MIN_WIDTH
+        } else {
+            (max as f32) * (CONNECTION_SIZE + CONNECTION_SPACING)
+        };
         if text_measured.width > self.width {
             self.width = text_measured.width;
         }
     }
-    pub fn draw(&self) {
+    /// `mouse` is the mouse position in the graph
+    pub fn draw(&self, mouse: (f32, f32), show_labels: bool) {
         let DraggableBlock { color, x, y, width, .. } = *self;
-        draw_rectangle(x, y, width, BLOCK_HEIGHT, color);
+        if let Some(note) = &self.note {
+            draw_rectangle(x, y, width, self.height(), color);
+            for (i, line) in note.lines().enumerate() {
+                let line_y = y + NOTE_PADDING + (i + 1) as f32 * NOTE_LINE_HEIGHT;
+                draw_text(line, x + NOTE_PADDING, line_y, NOTE_LINE_HEIGHT, BLACK);
+            }
+            return;
+        }
+        draw_rectangle(x, y, width, self.height(), color);
         draw_text(&self.get_text(), x, y + self.name_y_offset, FONT_SIZE_F32, BLACK);
-        self.iter_connections(Inputs, |x, y, input| input.draw(x, y));
-        self.iter_connections(Outputs, |x, y, input| input.draw(x, y));
+        let label_side = |connection_type| Some(self.port_side(connection_type)).filter(|_| show_labels);
+        self.iter_connections(Inputs, |x, y, input| input.draw(x, y, mouse, label_side(Inputs)));
+        self.iter_connections(Outputs, |x, y, input| input.draw(x, y, mouse, label_side(Outputs)));
     }
     /// returns true if there were any connection changes
     pub fn update(&mut self, block_context: &mut BlockContext) -> bool {
-        if let Some((x_off, y_off)) = mouse_within_bounds_offset(&*self) {
-            if self.being_dragged_from.is_none() && is_mouse_button_down(MouseButton::Left) {
-                if block_context.can_drag(self.id) {
-                    self.being_dragged_from = Some((x_off, y_off));
+        let offset = if block_context.egui_wants_pointer { None } else { within_bounds_offset(block_context.mouse(), &*self) };
+        if let Some((x_off, y_off)) = offset {
+            let alt = is_key_down(KeyCode::LeftAlt) || is_key_down(KeyCode::RightAlt);
+            if alt && block_context.currently_dragging.is_none() && is_mouse_button_pressed(MouseButton::Left) {
+                // alt dragging leaves this block in place and drags a copy of it
+                let mut copy = self.duplicate();
+                if block_context.can_drag(copy.id) {
+                    copy.being_dragged_from = Some((x_off, y_off));
+                    block_context.add_block(copy);
                 }
+            } else if self.being_dragged_from.is_none() && is_mouse_button_down(MouseButton::Left) && block_context.can_drag(self.id) {
+                self.being_dragged_from = Some((x_off, y_off));
             }
         }
         if is_mouse_button_released(MouseButton::Left) {
@@ -675,20 +1818,21 @@ impl DraggableBlock {
             self.being_dragged_from = None;
         }
         if let Some((x_off, y_off)) = self.being_dragged_from {
-            let (mx, my) = mouse_position();
+            let (mx, my) = block_context.mouse();
             let old_x = self.x;
             let old_y = self.y;
             self.x = mx - x_off;
             self.y = my - y_off;
+            if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                self.x = snap_to_grid(self.x, SNAP_GRID_SIZE);
+                self.y = snap_to_grid(self.y, SNAP_GRID_SIZE);
+            }
             // if my position changed, find all of the connections i have
             // with other blocks and update my part of the positions
             if old_x != self.x || old_y != self.y {
                 let diff_x = self.x - old_x;
                 let diff_y = self.y - old_y;
-                let mut ids = Vec::with_capacity(self.inputs.len() + self.outputs.len());
-                for i in self.inputs.iter() { ids.push(i.id) }
-                for i in self.outputs.iter() { ids.push(i.id) }
-                block_context.update_connection_positions(ids, diff_x, diff_y);
+                block_context.update_connection_positions(self.node_ids(), diff_x, diff_y);
             }
         }
         let mut needs_update = false;
@@ -705,3 +1849,895 @@ impl DraggableBlock {
         needs_update
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use ::rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use super::*;
+    use crate::{CircleBlock, FlattenPointsBlock, GridBlock, PtCombineBlock, RangeBlock};
+    use crate::sink::{run_headless, MacroquadSink, Primitive};
+
+    thread_local! {
+        /// what the source block outputs as an iteration
+        static SOURCE: RefCell<Vec<InputValue>> = const { RefCell::new(vec![]) };
+        /// every value the sink block received
+        static RECEIVED: RefCell<Vec<InputValue>> = const { RefCell::new(vec![]) };
+    }
+
+    fn source_run(_inputs: &Vec<&InputValue>, _ctx: &mut BlockRunContext) -> Result<Option<Vec<OutputResult>>, String> {
+        let values = SOURCE.with(|s| s.borrow().clone());
+        Ok(Some(vec![OutputResult::Iteration(values)]))
+    }
+
+    fn sink_run(inputs: &Vec<&InputValue>, _ctx: &mut BlockRunContext) -> Result<Option<Vec<OutputResult>>, String> {
+        RECEIVED.with(|r| r.borrow_mut().push(inputs[0].clone()));
+        Ok(None)
+    }
+
+    fn test_ctx() -> BlockRunContext<'static> {
+        BlockRunContext {
+            screen_w: 800.0,
+            screen_h: 600.0,
+            percentage: 0.0,
+            rng: ChaCha8Rng::seed_from_u64(0),
+            // zero sized, so leaking it doesn't allocate
+            draw: crate::sink::TransformedSink::new(Box::leak(Box::new(MacroquadSink))),
+            images: Box::leak(Box::new(crate::images::ImageCache::default())),
+            frame: 0,
+            audio: Default::default(),
+            midi: Box::leak(Box::new(crate::midi::MidiState::default())),
+        }
+    }
+
+    /// connects output `output_index` of block `from` to
+    /// input `input_index` of block `to`
+    fn connect(ctx: &mut BlockContext, from: usize, output_index: usize, to: usize, input_index: usize) {
+        let from = ctx.blocks[from].as_ref().unwrap();
+        let output = from.outputs[output_index].id;
+        let output_parent = from.id;
+        let to = ctx.blocks[to].as_ref().unwrap();
+        let input = to.inputs[input_index].id;
+        let input_parent = to.id;
+        ctx.connect(input, input_parent, output, output_parent, ((0.0, 0.0), (0.0, 0.0)));
+    }
+
+    /// runs source -> flatten -> sink, and returns what the sink received
+    fn run_flatten(values: Vec<InputValue>) -> Result<Vec<InputValue>, String> {
+        SOURCE.with(|s| *s.borrow_mut() = values);
+        RECEIVED.with(|r| r.borrow_mut().clear());
+
+        let source = DraggableBlock::for_test(vec![], vec![BlockConnectionNode::new("out", Outputs)], source_run);
+        let flatten = DraggableBlock {
+            flatten_inputs: true,
+            ..DraggableBlock::for_test(
+                vec![BlockConnectionNode::new("in", Inputs)],
+                vec![BlockConnectionNode::new("out", Outputs)],
+                FlattenPointsBlock::run,
+            )
+        };
+        let sink = DraggableBlock::for_test(vec![BlockConnectionNode::new("in", Inputs)], vec![], sink_run);
+
+        let mut ctx = BlockContext::new([sink, flatten, source]);
+        connect(&mut ctx, 2, 0, 1, 0);
+        connect(&mut ctx, 1, 0, 0, 0);
+        ctx.recalculate_graph().unwrap();
+        ctx.run(&mut test_ctx())?;
+        Ok(RECEIVED.with(|r| r.borrow().clone()))
+    }
+
+    fn single_list_points(received: Vec<InputValue>) -> Vec<(f32, f32)> {
+        assert_eq!(received.len(), 1);
+        match &received[0] {
+            InputValue::ListPoints(x) => x.clone(),
+            x => panic!("Expected ListPoints, found {:?}", x),
+        }
+    }
+
+    fn single_list_numbers(received: Vec<InputValue>) -> Vec<f64> {
+        assert_eq!(received.len(), 1);
+        match &received[0] {
+            InputValue::ListNumbers(x) => x.clone(),
+            x => panic!("Expected ListNumbers, found {:?}", x),
+        }
+    }
+
+    #[test]
+    fn flatten_numbers() {
+        let received = run_flatten(vec![1.0.into(), 2.0.into(), 3.0.into()]).unwrap();
+        assert_eq!(single_list_numbers(received), vec![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn flatten_points() {
+        let received = run_flatten(vec![(1.0, 2.0).into(), (3.0, 4.0).into()]).unwrap();
+        assert_eq!(single_list_points(received), vec![(1.0, 2.0), (3.0, 4.0)]);
+    }
+
+    #[test]
+    fn flatten_empty() {
+        let received = run_flatten(vec![]).unwrap();
+        assert_eq!(single_list_numbers(received), vec![]);
+    }
+
+    #[test]
+    fn flatten_list_numbers_concatenates() {
+        let received = run_flatten(vec![
+            InputValue::ListNumbers(vec![1.0, 2.0]),
+            InputValue::ListNumbers(vec![]),
+            InputValue::ListNumbers(vec![3.0]),
+        ]).unwrap();
+        assert_eq!(single_list_numbers(received), vec![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn flatten_list_points_concatenates() {
+        let received = run_flatten(vec![
+            InputValue::ListPoints(vec![(1.0, 1.0)]),
+            InputValue::ListPoints(vec![(2.0, 2.0), (3.0, 3.0)]),
+        ]).unwrap();
+        assert_eq!(single_list_points(received), vec![(1.0, 1.0), (2.0, 2.0), (3.0, 3.0)]);
+    }
+
+    #[test]
+    fn flatten_unflattenable_types_error() {
+        let err = run_flatten(vec![BLACK.into(), WHITE.into()]).unwrap_err();
+        assert!(err.contains("cannot flatten type Color"), "{}", err);
+        let err = run_flatten(vec![(&["a", "b"][..]).into()]).unwrap_err();
+        assert!(err.contains("cannot flatten type Selection"), "{}", err);
+        let err = run_flatten(vec![true.into()]).unwrap_err();
+        assert!(err.contains("cannot flatten type Boolean"), "{}", err);
+        let err = run_flatten(vec!["a".into()]).unwrap_err();
+        assert!(err.contains("cannot flatten type Text"), "{}", err);
+    }
+
+    #[test]
+    fn flatten_list_colors_errors() {
+        // unlike lists of numbers and points, lists of colors aren't concatenated
+        let err = run_flatten(vec![InputValue::ListColors(vec![BLACK])]).unwrap_err();
+        assert!(err.contains("cannot flatten type ListColors"), "{}", err);
+    }
+
+    #[test]
+    fn connections_update_graph_incrementally() {
+        let new_block = || DraggableBlock::for_test(
+            vec![BlockConnectionNode::new("in", Inputs)],
+            vec![BlockConnectionNode::new("out", Outputs)],
+            run_fn_noop,
+        );
+        let (a, b, c) = (new_block(), new_block(), new_block());
+        let ids = [a.id, b.id, c.id];
+        let mut ctx = BlockContext::new([a, b, c]);
+        // a <- b <- c
+        connect(&mut ctx, 2, 0, 1, 0);
+        connect(&mut ctx, 1, 0, 0, 0);
+        ctx.recalculate_order().unwrap();
+        let order: Vec<Id> = ctx.graph_order.iter().map(|i| ctx.graph.nodes[*i].value).collect();
+        assert_eq!(order, vec![ids[2], ids[1], ids[0]]);
+
+        // disconnecting a from b means a no longer has to wait for anything
+        let a_input = ctx.blocks[0].as_ref().unwrap().inputs[0].id;
+        ctx.remove_connection(a_input);
+        assert!(ctx.graph.nodes[0].depends_on.is_empty());
+        assert!(ctx.graph.nodes[1].is_dependent_of.is_empty());
+        ctx.recalculate_order().unwrap();
+        let order: Vec<Id> = ctx.graph_order.iter().map(|i| ctx.graph.nodes[*i].value).collect();
+        assert_eq!(order, vec![ids[0], ids[2], ids[1]]);
+    }
+
+    #[test]
+    fn id_counter_resets_past_the_loaded_ids() {
+        // ahead of the counter, so the other tests running at the same
+        // time don't get ids they already hold
+        let loaded = DraggableBlock {
+            id: Id(get_id().0 + 1000),
+            ..DraggableBlock::for_test(vec![BlockConnectionNode::new("in", Inputs)], vec![], run_fn_noop)
+        };
+        let loaded_id = loaded.id;
+        BlockContext::new([loaded]).reset_id_counter();
+        assert!(get_id().0 > loaded_id.0);
+    }
+
+    #[test]
+    fn cyclic_connections_are_detected() {
+        let new_block = || DraggableBlock::for_test(
+            vec![BlockConnectionNode::new("in", Inputs)],
+            vec![BlockConnectionNode::new("out", Outputs)],
+            run_fn_noop,
+        );
+        let (a, b, c) = (new_block(), new_block(), new_block());
+        let ids = [a.id, b.id, c.id];
+        let mut ctx = BlockContext::new([a, b, c]);
+        // a <- b <- c
+        connect(&mut ctx, 2, 0, 1, 0);
+        connect(&mut ctx, 1, 0, 0, 0);
+        assert!(ctx.would_create_cycle(ids[2], ids[0]));
+        assert!(ctx.would_create_cycle(ids[1], ids[1]));
+        assert!(!ctx.would_create_cycle(ids[0], ids[2]));
+    }
+
+    #[test]
+    fn invalid_graphs_keep_the_previous_graph_and_order() {
+        let new_block = || DraggableBlock::for_test(
+            vec![BlockConnectionNode::new("in", Inputs)],
+            vec![BlockConnectionNode::new("out", Outputs)],
+            run_fn_noop,
+        );
+        let (a, b) = (new_block(), new_block());
+        let (a_input, b_id) = (a.inputs[0].id, b.id);
+        let mut ctx = BlockContext::new([a, b]);
+        // a <- b
+        connect(&mut ctx, 1, 0, 0, 0);
+        ctx.recalculate_graph().unwrap();
+        let depends_on = |ctx: &BlockContext| -> Vec<Vec<usize>> {
+            ctx.graph.nodes.iter().map(|n| n.depends_on.clone()).collect()
+        };
+        let (graph, order) = (depends_on(&ctx), ctx.graph_order.clone());
+        // b <- a as well, without going through can_connect
+        let b_input = ctx.blocks[1].as_ref().unwrap().inputs[0].id;
+        let a_id = ctx.blocks[0].as_ref().unwrap().id;
+        ctx.inputs.insert(b_input, a_id);
+        assert!(ctx.recalculate_graph().unwrap_err().contains("cycle"));
+        assert_eq!(depends_on(&ctx), graph);
+        assert_eq!(ctx.graph_order, order);
+        assert_eq!(ctx.inputs.get(&a_input), Some(&b_id));
+    }
+
+    #[test]
+    fn auto_layout_rows_follow_depth() {
+        let block = |width: f32| DraggableBlock {
+            width,
+            x: 500.0,
+            y: 500.0,
+            ..DraggableBlock::for_test(
+                vec![BlockConnectionNode::new("a", Inputs)],
+                vec![BlockConnectionNode::new("out", Outputs)],
+                run_fn_noop,
+            )
+        };
+        let mut ctx = BlockContext::new([block(100.0), block(50.0), block(70.0)]);
+        // 0 -> 2, 1 has nothing connected
+        connect(&mut ctx, 0, 0, 2, 0);
+        ctx.recalculate_graph().unwrap();
+        ctx.auto_layout((10.0, 20.0));
+
+        let pos = |i: usize| {
+            let b = ctx.blocks[i].as_ref().unwrap();
+            (b.x, b.y)
+        };
+        let row_height = BLOCK_HEIGHT + LAYOUT_SPACING_Y;
+        assert_eq!(pos(2), (10.0, 20.0 + row_height));
+        // 0 and 1 share the first row, side by side
+        let mut first_row = [pos(0), pos(1)];
+        first_row.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        assert_eq!(first_row[0], (10.0, 20.0));
+        assert_eq!(first_row[1].1, 20.0);
+        assert!(first_row[1].0 > 10.0);
+        // both ends of the connection moved along with their blocks.
+        // the test connection started with both ends at (0, 0)
+        let (input_pt, output_pt) = *ctx.connections.values().next().unwrap();
+        assert_eq!(input_pt, (pos(2).0 - 500.0, pos(2).1 - 500.0));
+        assert_eq!(output_pt, (pos(0).0 - 500.0, pos(0).1 - 500.0));
+    }
+
+    #[test]
+    fn selected_blocks_move_together() {
+        let block = || DraggableBlock::for_test(
+            vec![BlockConnectionNode::new("a", Inputs)],
+            vec![BlockConnectionNode::new("out", Outputs)],
+            run_fn_noop,
+        );
+        let mut ctx = BlockContext::new([block(), block(), block()]);
+        let ids: Vec<Id> = ctx.blocks.iter().flatten().map(|b| b.id).collect();
+        // 0 -> 1, the connection starts with both ends at (0, 0)
+        connect(&mut ctx, 0, 0, 1, 0);
+
+        ctx.click_select(Some(ids[0]), false);
+        ctx.click_select(Some(ids[1]), true);
+        ctx.click_select(Some(ids[2]), false);
+        assert_eq!(ctx.selected, HashSet::from([ids[2]]));
+        ctx.click_select(Some(ids[0]), true);
+        ctx.click_select(Some(ids[1]), true);
+        ctx.click_select(Some(ids[2]), true);
+        assert_eq!(ctx.selected, HashSet::from([ids[0], ids[1]]));
+        // clicking an already selected block keeps the selection
+        ctx.click_select(Some(ids[0]), false);
+        assert_eq!(ctx.selected.len(), 2);
+
+        let selected: Vec<Id> = ctx.selected.iter().copied().collect();
+        ctx.move_blocks(&selected, 10.0, -1.0);
+        let pos = |ctx: &BlockContext, i: usize| {
+            let b = ctx.blocks[i].as_ref().unwrap();
+            (b.x, b.y)
+        };
+        assert_eq!(pos(&ctx, 0), (10.0, -1.0));
+        assert_eq!(pos(&ctx, 1), (10.0, -1.0));
+        assert_eq!(pos(&ctx, 2), (0.0, 0.0));
+        let (input_pt, output_pt) = *ctx.connections.values().next().unwrap();
+        assert_eq!(input_pt, (10.0, -1.0));
+        assert_eq!(output_pt, (10.0, -1.0));
+
+        ctx.click_select(None, false);
+        assert!(ctx.selected.is_empty());
+    }
+
+    #[test]
+    fn duplicate_copies_values_with_new_ids() {
+        let mut original = DraggableBlock {
+            x: 30.0,
+            ..DraggableBlock::for_test(
+                vec![BlockConnectionNode::new_with_input_type("radius", 12.0.into(), Inputs).integer()],
+                vec![BlockConnectionNode::new("out", Outputs)],
+                run_fn_noop,
+            )
+        };
+        original.name = format!("{} circle", original.id);
+        let mut ctx = BlockContext::new([original]);
+        let original = ctx.blocks[0].as_ref().unwrap();
+        let copy = original.duplicate();
+
+        assert_ne!(copy.id, original.id);
+        assert_eq!((copy.name.clone(), copy.x), (format!("{} circle", copy.id), 30.0));
+        assert_ne!(copy.inputs[0].id, original.inputs[0].id);
+        assert_ne!(copy.outputs[0].id, original.outputs[0].id);
+        assert_eq!(copy.inputs[0].parent_id, copy.id);
+        assert_eq!(copy.inputs[0].value.as_f32(), 12.0);
+        assert!(copy.inputs[0].is_integer);
+
+        ctx.add_block(copy);
+        assert_eq!(ctx.blocks.len(), 2);
+        assert!(ctx.connections.is_empty());
+    }
+
+    #[test]
+    fn solo_runs_only_the_block_and_its_dependencies() {
+        let mut ctx = BlockContext::new([range_block(10.0, 50.0, 5.0), circle_block(0.0), circle_block(1.0)]);
+        connect(&mut ctx, 0, 0, 1, 2);
+        ctx.recalculate_graph().unwrap();
+        assert_eq!(run_headless(&ctx, 0, (800.0, 600.0), 0.0).unwrap().len(), 6);
+
+        // the soloed circle still gets its radii from the range
+        ctx.soloed = Some(ctx.blocks[1].as_ref().unwrap().id);
+        assert_eq!(run_headless(&ctx, 0, (800.0, 600.0), 0.0).unwrap().len(), 5);
+        ctx.soloed = Some(ctx.blocks[2].as_ref().unwrap().id);
+        assert_eq!(run_headless(&ctx, 0, (800.0, 600.0), 0.0).unwrap().len(), 1);
+        // the range draws nothing on its own
+        ctx.soloed = Some(ctx.blocks[0].as_ref().unwrap().id);
+        assert_eq!(run_headless(&ctx, 0, (800.0, 600.0), 0.0).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn deleting_a_block_removes_dependent_connections() {
+        let mut ctx = BlockContext::new([range_block(10.0, 50.0, 5.0), circle_block(0.0), circle_block(1.0)]);
+        connect(&mut ctx, 0, 0, 1, 2);
+        connect(&mut ctx, 0, 0, 2, 2);
+        ctx.recalculate_graph().unwrap();
+        let range_id = ctx.blocks[0].as_ref().unwrap().id;
+        assert_eq!(ctx.dependent_connections(&[range_id]), 2);
+
+        // the first request only warns
+        ctx.request_delete(vec![range_id]).unwrap();
+        assert!(ctx.blocks[0].is_some());
+        assert_eq!(ctx.pending_delete, Some(vec![range_id]));
+        assert!(ctx.notices.pop().unwrap().contains("Press Delete again"));
+
+        ctx.request_delete(vec![range_id]).unwrap();
+        assert!(ctx.blocks[0].is_none());
+        assert_eq!(ctx.notices.pop().unwrap(), "Removed 2 connections that depended on this block");
+        assert!(ctx.connections.is_empty());
+        assert!(ctx.inputs.is_empty());
+        assert!(ctx.input_output.is_empty());
+        assert!(ctx.input_parents.is_empty());
+        // both circles draw with their own radius again, instead of failing
+        assert_eq!(run_headless(&ctx, 0, (800.0, 600.0), 0.0).unwrap().len(), 2);
+
+        // nothing depends on a circle, so it goes right away
+        let circle_id = ctx.blocks[1].as_ref().unwrap().id;
+        ctx.selected.insert(circle_id);
+        ctx.request_delete(vec![circle_id]).unwrap();
+        assert!(ctx.blocks[1].is_none());
+        assert!(ctx.selected.is_empty());
+        assert!(ctx.notices.is_empty());
+        assert_eq!(run_headless(&ctx, 0, (800.0, 600.0), 0.0).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn tall_blocks_stack_their_nodes_down_the_sides() {
+        let mut block = DraggableBlock {
+            x: 100.0,
+            y: 50.0,
+            width: 80.0,
+            ..DraggableBlock::for_test(
+                (0..3).map(|i| BlockConnectionNode::new(i.to_string(), Inputs)).collect(),
+                vec![BlockConnectionNode::new("out", Outputs)],
+                run_fn_noop,
+            )
+        };
+        let positions = |block: &DraggableBlock, connection_type| {
+            let mut out = vec![];
+            block.iter_connections(connection_type, |x, y, _| out.push((x, y)));
+            out
+        };
+        // a row above and below by default
+        assert_eq!(positions(&block, Inputs), vec![(100.0, 40.0), (138.0, 40.0), (176.0, 40.0)]);
+        assert_eq!(positions(&block, Outputs), vec![(100.0, 50.0 + BLOCK_HEIGHT)]);
+
+        block.tall = true;
+        let step = CONNECTION_SIZE + TALL_PORT_SPACING;
+        let top = 50.0 + BLOCK_HEIGHT;
+        assert_eq!(positions(&block, Inputs), vec![(90.0, top), (90.0, top + step), (90.0, top + 2.0 * step)]);
+        assert_eq!(positions(&block, Outputs), vec![(180.0, top)]);
+        assert_eq!(block.height(), BLOCK_HEIGHT + 3.0 * step);
+        assert_eq!(block.port_side(Inputs), PortSide::Left);
+        let mut opposite = vec![];
+        block.iter_connections_opposite(Outputs, |x, y, _| opposite.push((x, y)));
+        assert_eq!(opposite, positions(&block, Inputs));
+        let bounds = block.outer_bounds();
+        assert_eq!((bounds.x, bounds.w), (90.0, 100.0));
+
+        // auto layout leaves room under the tall block for the next row
+        let below = DraggableBlock::for_test(vec![BlockConnectionNode::new("a", Inputs)], vec![], run_fn_noop);
+        let mut ctx = BlockContext::new([block, below]);
+        connect(&mut ctx, 0, 0, 1, 0);
+        ctx.recalculate_graph().unwrap();
+        ctx.auto_layout((0.0, 0.0));
+        let below_y = ctx.blocks[1].as_ref().unwrap().y;
+        assert_eq!(below_y, BLOCK_HEIGHT + 3.0 * step + LAYOUT_SPACING_Y);
+    }
+
+    #[test]
+    fn port_labels_are_abbreviated() {
+        assert_eq!(BlockConnectionNode::new("thickness", Inputs).label(), "thic");
+        assert_eq!(BlockConnectionNode::new("x", Outputs).label(), "x");
+        // cut by characters, not bytes
+        assert_eq!(BlockConnectionNode::new("ümlaut", Inputs).label(), "ümla");
+    }
+
+    #[test]
+    fn notes_are_left_out_of_the_graph() {
+        fn fails(_inputs: &Vec<&InputValue>, _ctx: &mut BlockRunContext) -> Result<Option<Vec<OutputResult>>, String> {
+            Err("notes should never run".into())
+        }
+        let note = DraggableBlock { note: Some("first\nsecond".into()), run_fn: fails, ..Default::default() };
+        assert_eq!(note.height(), (2.0 * NOTE_LINE_HEIGHT + 2.0 * NOTE_PADDING).max(BLOCK_HEIGHT));
+        let note_id = note.id;
+        let mut ctx = BlockContext::new([note, circle_block(5.0)]);
+        ctx.add_block(DraggableBlock { note: Some("added later".into()), run_fn: fails, ..Default::default() });
+        ctx.recalculate_graph().unwrap();
+        assert_eq!(ctx.graph.nodes.len(), 1);
+        assert_eq!(ctx.graph_order.len(), 1);
+        assert_eq!(run_headless(&ctx, 0, (800.0, 600.0), 0.0).unwrap().len(), 1);
+
+        // still a block as far as dragging and deleting go
+        ctx.move_blocks(&[note_id], 10.0, 0.0);
+        assert_eq!(ctx.blocks[0].as_ref().unwrap().x, 10.0);
+        ctx.request_delete(vec![note_id]).unwrap();
+        assert!(ctx.blocks[0].is_none());
+        assert_eq!(ctx.graph.nodes.len(), 1);
+    }
+
+    #[test]
+    fn camera_zoom_and_fit() {
+        let mut camera = GraphCamera::default();
+        camera.zoom_around((100.0, 100.0), 2.0);
+        // the point under the mouse stays put
+        assert_eq!(camera.to_screen((100.0, 100.0)), (100.0, 100.0));
+        assert_eq!(camera.to_screen((110.0, 100.0)), (120.0, 100.0));
+        assert_eq!(camera.to_graph(camera.to_screen((-3.0, 7.0))), (-3.0, 7.0));
+        camera.zoom_around((0.0, 0.0), 1000.0);
+        assert_eq!(camera.zoom, MAX_ZOOM);
+
+        let a = DraggableBlock { x: -500.0, y: -200.0, width: 100.0, ..Default::default() };
+        let b = DraggableBlock { x: 1400.0, y: 900.0, width: 100.0, ..Default::default() };
+        let mut ctx = BlockContext::new([a, b]);
+        let bounds = ctx.blocks_bounds().unwrap();
+        assert_eq!((bounds.x, bounds.y), (-500.0, -200.0 - CONNECTION_SIZE));
+        assert_eq!(bounds.right(), 1500.0);
+        assert_eq!(bounds.bottom(), 900.0 + BLOCK_HEIGHT + CONNECTION_SIZE);
+
+        let area = Rect::new(0.0, 0.0, 800.0, 600.0);
+        ctx.zoom_to_fit(area);
+        // everything is on screen, within the margin, and the
+        // wider side touches the margin
+        let top_left = ctx.camera.to_screen((bounds.x, bounds.y));
+        let bottom_right = ctx.camera.to_screen((bounds.right(), bounds.bottom()));
+        assert!(top_left.0 >= ZOOM_FIT_MARGIN - 0.01 && top_left.1 >= ZOOM_FIT_MARGIN - 0.01);
+        assert!(bottom_right.0 <= 800.0 - ZOOM_FIT_MARGIN + 0.01 && bottom_right.1 <= 600.0 - ZOOM_FIT_MARGIN + 0.01);
+        assert!((top_left.0 - ZOOM_FIT_MARGIN).abs() < 0.01);
+
+        // nothing to fit, the camera stays where it is
+        let mut empty = BlockContext::new([]);
+        empty.zoom_to_fit(area);
+        assert_eq!(empty.camera, GraphCamera::default());
+    }
+
+    fn five_run(_inputs: &Vec<&InputValue>, _ctx: &mut BlockRunContext) -> Result<Option<Vec<OutputResult>>, String> {
+        Ok(Some(vec![OutputResult::SingleValue(5.0.into())]))
+    }
+
+    #[test]
+    fn inserting_between_feeds_the_number_into_pt_combine() {
+        let number = DraggableBlock::for_test(vec![], vec![BlockConnectionNode::new("value", Outputs)], five_run);
+        let point_sink = DraggableBlock::for_test(
+            vec![BlockConnectionNode::new_with_input_type("pt", (1.0, 1.0).into(), Inputs)],
+            vec![],
+            sink_run,
+        );
+        let mut ctx = BlockContext::new([number, point_sink]);
+        let (number, point_sink) = (ctx.blocks[0].as_ref().unwrap(), ctx.blocks[1].as_ref().unwrap());
+        let pending = PendingInsert {
+            output: number.outputs[0].id,
+            output_parent: number.id,
+            output_pos: (0.0, 0.0),
+            input: point_sink.inputs[0].id,
+            input_parent: point_sink.id,
+            input_pos: (0.0, 100.0),
+            menu_pos: (0.0, 100.0),
+        };
+        let combine = DraggableBlock::for_test(
+            vec![BlockConnectionNode::new("x", Inputs), BlockConnectionNode::new("y", Inputs)],
+            vec![BlockConnectionNode::new_with_input_type("pt", (0.0, 0.0).into(), Outputs)],
+            PtCombineBlock::run,
+        );
+        ctx.insert_between(pending, combine);
+        ctx.recalculate_order().unwrap();
+
+        assert_eq!(ctx.blocks.len(), 3);
+        assert_eq!(ctx.connections.len(), 2);
+        RECEIVED.with(|r| r.borrow_mut().clear());
+        ctx.run(&mut test_ctx()).unwrap();
+        let received = RECEIVED.with(|r| r.borrow().clone());
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].as_point(), (5.0, 0.0));
+    }
+
+    #[test]
+    fn duplicated_context_runs_the_same_with_new_ids() {
+        // added before the range, so only the graph order makes it run after
+        let mut ctx = BlockContext::new([circle_block(0.0), range_block(10.0, 50.0, 5.0)]);
+        connect(&mut ctx, 1, 0, 0, 2);
+        ctx.recalculate_graph().unwrap();
+
+        let copy = ctx.duplicate();
+        let old_ids: HashSet<Id> = ctx.blocks.iter().flatten().flat_map(|b| b.node_ids().into_iter().chain([b.id])).collect();
+        let new_ids: HashSet<Id> = copy.blocks.iter().flatten().flat_map(|b| b.node_ids().into_iter().chain([b.id])).collect();
+        assert!(old_ids.is_disjoint(&new_ids));
+        assert_eq!(copy.connections.len(), 1);
+        assert!(copy.connections.keys().all(|(i, o)| new_ids.contains(i) && new_ids.contains(o)));
+        assert_eq!(
+            run_headless(&copy, 0, (800.0, 600.0), 0.0).unwrap(),
+            run_headless(&ctx, 0, (800.0, 600.0), 0.0).unwrap(),
+        );
+    }
+
+    #[test]
+    fn segment_distance() {
+        assert_eq!(distance_to_segment((5.0, 3.0), (0.0, 0.0), (10.0, 0.0)), 3.0);
+        // past the ends, the distance is to the closest end point
+        assert_eq!(distance_to_segment((13.0, 4.0), (0.0, 0.0), (10.0, 0.0)), 5.0);
+        assert_eq!(distance_to_segment((-3.0, 0.0), (0.0, 0.0), (10.0, 0.0)), 3.0);
+        // a segment of length 0
+        assert_eq!(distance_to_segment((3.0, 4.0), (0.0, 0.0), (0.0, 0.0)), 5.0);
+    }
+
+    #[test]
+    fn connection_near_finds_the_line() {
+        let mut ctx = BlockContext::new([]);
+        let (input, output) = (get_id(), get_id());
+        ctx.connections.insert((input, output), ((0.0, 0.0), (100.0, 0.0)));
+        assert_eq!(ctx.connection_near((50.0, 2.0)), Some((input, output)));
+        assert_eq!(ctx.connection_near((50.0, 20.0)), None);
+    }
+
+    #[test]
+    fn snapping() {
+        assert_eq!(snap_to_grid(0.0, 20.0), 0.0);
+        assert_eq!(snap_to_grid(9.0, 20.0), 0.0);
+        assert_eq!(snap_to_grid(11.0, 20.0), 20.0);
+        assert_eq!(snap_to_grid(-11.0, 20.0), -20.0);
+        assert_eq!(snap_to_grid(95.0, 20.0), 100.0);
+    }
+
+    #[test]
+    fn iterations_append_per_block() {
+        RECEIVED.with(|r| r.borrow_mut().clear());
+        let grid = DraggableBlock::for_test(
+            vec![
+                BlockConnectionNode::new_with_input_type("rows", 3.0.into(), Inputs),
+                BlockConnectionNode::new_with_input_type("cols", 4.0.into(), Inputs),
+            ],
+            vec![
+                BlockConnectionNode::new("xi", Outputs),
+                BlockConnectionNode::new("yi", Outputs),
+                BlockConnectionNode::new("t", Outputs),
+            ],
+            GridBlock::run,
+        );
+        // pt combine outputs a single value per iteration, so its
+        // output should get appended into an iteration of 12 points
+        let combine = DraggableBlock::for_test(
+            vec![
+                BlockConnectionNode::new("x", Inputs),
+                BlockConnectionNode::new("y", Inputs),
+            ],
+            vec![BlockConnectionNode::new_with_input_type("pt", (0.0, 0.0).into(), Outputs)],
+            PtCombineBlock::run,
+        );
+        let sink = DraggableBlock::for_test(
+            vec![BlockConnectionNode::new_with_input_type("pt", (0.0, 0.0).into(), Inputs)],
+            vec![],
+            sink_run,
+        );
+
+        let mut ctx = BlockContext::new([sink, combine, grid]);
+        connect(&mut ctx, 2, 0, 1, 0);
+        connect(&mut ctx, 2, 1, 1, 1);
+        connect(&mut ctx, 1, 0, 0, 0);
+        ctx.recalculate_graph().unwrap();
+        ctx.run(&mut test_ctx()).unwrap();
+
+        let received = RECEIVED.with(|r| r.borrow().clone());
+        assert_eq!(received.len(), 12);
+        // 800x600 screen, 4 cols and 3 rows
+        assert_eq!(received[0].as_point(), (100.0, 100.0));
+        assert_eq!(received[1].as_point(), (300.0, 100.0));
+        assert_eq!(received[11].as_point(), (700.0, 500.0));
+    }
+
+    /// a circle block with the default inputs, without needing a window
+    fn circle_block(radius: f32) -> DraggableBlock {
+        DraggableBlock::for_test(
+            vec![
+                BlockConnectionNode::new("cx", Inputs),
+                BlockConnectionNode::new("cy", Inputs),
+                BlockConnectionNode::new_with_input_type("radius", radius.into(), Inputs),
+                BlockConnectionNode::new_with_input_type("color", BLACK.into(), Inputs),
+            ],
+            vec![],
+            CircleBlock::run,
+        )
+    }
+
+    /// a range block, same as circle_block
+    fn range_block(start: f32, end: f32, count: f32) -> DraggableBlock {
+        DraggableBlock::for_test(
+            vec![
+                BlockConnectionNode::new_with_input_type("start", start.into(), Inputs),
+                BlockConnectionNode::new_with_input_type("end", end.into(), Inputs),
+                BlockConnectionNode::new_with_input_type("count", count.into(), Inputs),
+            ],
+            vec![BlockConnectionNode::new("value", Outputs)],
+            RangeBlock::run,
+        )
+    }
+
+    fn numbers_run(_inputs: &Vec<&InputValue>, _ctx: &mut BlockRunContext) -> Result<Option<Vec<OutputResult>>, String> {
+        Ok(Some(vec![OutputResult::SingleValue(InputValue::ListNumbers(vec![4.0, 5.0, 6.0]))]))
+    }
+
+    #[test]
+    fn any_list_inputs_take_the_connected_list_type() {
+        // the Index block's nodes, its to_draggable_block measures text
+        let index = DraggableBlock::for_test(
+            vec![
+                BlockConnectionNode::new_with_input_type("list", InputValue::ListPoints(vec![]), Inputs).any_list(),
+                BlockConnectionNode::new_with_input_type("index", 1.0.into(), Inputs),
+            ],
+            vec![
+                BlockConnectionNode::new_with_input_type("value", (0.0, 0.0).into(), Outputs).follows_list(ListFollow::Element),
+            ],
+            crate::IndexBlock::run,
+        );
+        let numbers = DraggableBlock::for_test(
+            vec![],
+            vec![BlockConnectionNode::new_with_input_type("nums", InputValue::ListNumbers(vec![]), Outputs)],
+            numbers_run,
+        );
+
+        // what can_connect checks. only an any-list input takes another list type
+        let list = &index.inputs[0];
+        assert_eq!(link_kind(&list.value, list.is_any_list, &numbers.outputs[0].value), Some(LinkKind::Direct));
+        assert_eq!(link_kind(&list.value, false, &numbers.outputs[0].value), None);
+        assert_eq!(link_kind(&list.value, list.is_any_list, &InputValue::Number(0.0)), None);
+
+        let mut ctx = BlockContext::new([circle_block(0.0), index, numbers]);
+        connect(&mut ctx, 2, 0, 1, 0);
+        ctx.recalculate_graph().unwrap();
+        let index = ctx.blocks[1].as_ref().unwrap();
+        assert_eq!(index.inputs[0].value.type_name(), "ListNumbers");
+        assert_eq!(index.outputs[0].value.type_name(), "Number");
+        // so now its output fits a number input
+        let radius = &ctx.blocks[0].as_ref().unwrap().inputs[2];
+        assert_eq!(link_kind(&radius.value, false, &index.outputs[0].value), Some(LinkKind::Direct));
+        connect(&mut ctx, 1, 0, 0, 2);
+        ctx.recalculate_graph().unwrap();
+        match run_headless(&ctx, 0, (800.0, 600.0), 0.0).unwrap()[..] {
+            [Primitive::Circle { radius, .. }] => assert_eq!(radius, 5.0),
+            ref x => panic!("Expected one circle, found {:?}", x),
+        }
+    }
+
+    #[test]
+    fn reversed_lists_keep_their_type_down_the_chain() {
+        // two ReverseList blocks in a row, built by hand like the Index above
+        let reverse = || DraggableBlock::for_test(
+            vec![BlockConnectionNode::new_with_input_type("list", InputValue::ListPoints(vec![]), Inputs).any_list()],
+            vec![
+                BlockConnectionNode::new_with_input_type("list", InputValue::ListPoints(vec![]), Outputs).follows_list(ListFollow::List),
+            ],
+            crate::ReverseListBlock::run,
+        );
+        let numbers = DraggableBlock::for_test(
+            vec![],
+            vec![BlockConnectionNode::new_with_input_type("nums", InputValue::ListNumbers(vec![]), Outputs)],
+            numbers_run,
+        );
+        let sink = DraggableBlock::for_test(
+            vec![BlockConnectionNode::new_with_input_type("in", InputValue::ListNumbers(vec![]), Inputs)],
+            vec![],
+            sink_run,
+        );
+        let mut ctx = BlockContext::new([sink, reverse(), reverse(), numbers]);
+        connect(&mut ctx, 3, 0, 2, 0);
+        connect(&mut ctx, 2, 0, 1, 0);
+        ctx.recalculate_graph().unwrap();
+        for i in [1, 2] {
+            assert_eq!(ctx.blocks[i].as_ref().unwrap().outputs[0].value.type_name(), "ListNumbers");
+        }
+        let output = &ctx.blocks[1].as_ref().unwrap().outputs[0];
+        assert_eq!(link_kind(&ctx.blocks[0].as_ref().unwrap().inputs[0].value, false, &output.value), Some(LinkKind::Direct));
+        connect(&mut ctx, 1, 0, 0, 0);
+        ctx.recalculate_graph().unwrap();
+        RECEIVED.with(|r| r.borrow_mut().clear());
+        ctx.run(&mut test_ctx()).unwrap();
+        match RECEIVED.with(|r| r.borrow_mut().pop()) {
+            Some(InputValue::ListNumbers(l)) => assert_eq!(l, vec![4.0, 5.0, 6.0]),
+            x => panic!("Expected a list of numbers, found {:?}", x),
+        }
+    }
+
+    #[test]
+    fn retyped_outputs_drop_connections_that_no_longer_fit() {
+        let index = DraggableBlock::for_test(
+            vec![
+                BlockConnectionNode::new_with_input_type("list", InputValue::ListPoints(vec![]), Inputs).any_list(),
+                BlockConnectionNode::new("index", Inputs),
+            ],
+            vec![
+                BlockConnectionNode::new_with_input_type("value", (0.0, 0.0).into(), Outputs).follows_list(ListFollow::Element),
+            ],
+            run_fn_noop,
+        );
+        let point_input = DraggableBlock::for_test(
+            vec![BlockConnectionNode::new_with_input_type("pt", (0.0, 0.0).into(), Inputs)],
+            vec![],
+            run_fn_noop,
+        );
+        let numbers = DraggableBlock::for_test(
+            vec![],
+            vec![BlockConnectionNode::new_with_input_type("nums", InputValue::ListNumbers(vec![]), Outputs)],
+            run_fn_noop,
+        );
+        let mut ctx = BlockContext::new([point_input, index, numbers]);
+        connect(&mut ctx, 1, 0, 0, 0);
+        connect(&mut ctx, 2, 0, 1, 0);
+        ctx.recalculate_graph().unwrap();
+        // the point connection can't take a number
+        assert_eq!(ctx.connections.len(), 1);
+        assert_eq!(ctx.notices.pop().unwrap(), "Removed the connection into pt, it can't take a Number");
+    }
+
+    #[test]
+    fn headless_grid_to_circles() {
+        let grid = DraggableBlock::for_test(
+            vec![
+                BlockConnectionNode::new_with_input_type("rows", 3.0.into(), Inputs),
+                BlockConnectionNode::new_with_input_type("cols", 4.0.into(), Inputs),
+            ],
+            vec![
+                BlockConnectionNode::new("xi", Outputs),
+                BlockConnectionNode::new("yi", Outputs),
+                BlockConnectionNode::new("t", Outputs),
+            ],
+            GridBlock::run,
+        );
+        let mut ctx = BlockContext::new([circle_block(5.0), grid]);
+        connect(&mut ctx, 1, 0, 0, 0);
+        connect(&mut ctx, 1, 1, 0, 1);
+        ctx.recalculate_graph().unwrap();
+
+        let primitives = run_headless(&ctx, 0, (800.0, 600.0), 0.0).unwrap();
+        assert_eq!(primitives.len(), 3 * 4);
+        // 800x600 screen, 4 cols and 3 rows
+        assert_eq!(primitives[0], Primitive::Circle { x: 100.0, y: 100.0, radius: 5.0, color: BLACK });
+        assert_eq!(primitives[5], Primitive::Circle { x: 300.0, y: 300.0, radius: 5.0, color: BLACK });
+        assert_eq!(primitives[11], Primitive::Circle { x: 700.0, y: 500.0, radius: 5.0, color: BLACK });
+    }
+
+    #[test]
+    fn headless_range_to_circle_radius() {
+        let mut ctx = BlockContext::new([range_block(10.0, 50.0, 5.0), circle_block(0.0)]);
+        // the range drives the radius, the position stays the default
+        connect(&mut ctx, 0, 0, 1, 2);
+        ctx.recalculate_graph().unwrap();
+
+        let primitives = run_headless(&ctx, 0, (800.0, 600.0), 0.0).unwrap();
+        let radii: Vec<f32> = primitives.iter().map(|p| match p {
+            Primitive::Circle { x: 0.0, y: 0.0, radius, .. } => *radius,
+            x => panic!("Expected a circle at the origin, found {:?}", x),
+        }).collect();
+        assert_eq!(radii, vec![10.0, 20.0, 30.0, 40.0, 50.0]);
+    }
+
+    #[test]
+    fn run_keeps_outputs_only_when_showing_values() {
+        let range = range_block(0.0, 1.0, 3.0);
+        let output_id = range.outputs[0].id;
+        let mut ctx = BlockContext::new([range]);
+
+        run_headless(&ctx, 0, (800.0, 600.0), 0.0).unwrap();
+        assert!(ctx.last_outputs.borrow().is_empty());
+
+        ctx.show_values = true;
+        run_headless(&ctx, 0, (800.0, 600.0), 0.0).unwrap();
+        let outputs = ctx.last_outputs.borrow();
+        assert_eq!(outputs[&output_id].describe(), "3 items, first 0.00");
+    }
+
+    #[test]
+    fn expression_inputs_are_evaluated_each_run() {
+        RECEIVED.with(|r| r.borrow_mut().clear());
+        let sink = DraggableBlock {
+            name: "sink".into(),
+            ..DraggableBlock::for_test(
+                vec![BlockConnectionNode::new_with_input_type("in", InputValue::Expression("screen_w / 2 + frame".into()), Inputs)],
+                vec![],
+                sink_run,
+            )
+        };
+        let mut ctx = BlockContext::new([sink]);
+        ctx.recalculate_graph().unwrap();
+        let mut run_ctx = test_ctx();
+        ctx.run(&mut run_ctx).unwrap();
+        run_ctx.frame = 3;
+        ctx.run(&mut run_ctx).unwrap();
+        let received: Vec<f64> = RECEIVED.with(|r| r.borrow().iter().map(InputValue::as_f64).collect());
+        assert_eq!(received, vec![400.0, 403.0]);
+
+        ctx.blocks[0].as_mut().unwrap().inputs[0].value = InputValue::Expression("width / 2".into());
+        assert_eq!(ctx.run(&mut run_ctx).unwrap_err(), "Block sink input in: Unknown variable width");
+        for src in ["1/0", "sqrt(0-1)"] {
+            ctx.blocks[0].as_mut().unwrap().inputs[0].value = InputValue::Expression(src.into());
+            assert_eq!(ctx.run(&mut run_ctx).unwrap_err(), "Block sink input in: expression is not a finite number");
+        }
+    }
+
+    #[test]
+    fn outputs_are_described_in_run_order() {
+        let range = DraggableBlock { name: "range".into(), ..range_block(10.0, 50.0, 5.0) };
+        let mut ctx = BlockContext::new([circle_block(0.0), range]);
+        connect(&mut ctx, 1, 0, 0, 2);
+        ctx.recalculate_graph().unwrap();
+
+        let mut sink = crate::sink::RecordingSink::default();
+        let mut images = crate::images::ImageCache::default();
+        let midi = crate::midi::MidiState::default();
+        let mut run_ctx = BlockRunContext {
+            screen_w: 800.0,
+            screen_h: 600.0,
+            percentage: 0.5,
+            rng: ::rand::SeedableRng::seed_from_u64(0),
+            draw: crate::sink::TransformedSink::new(&mut sink),
+            images: &mut images,
+            frame: 0,
+            audio: Default::default(),
+            midi: &midi,
+        };
+        let outputs = ctx.run_collecting(&mut run_ctx).unwrap();
+        // the circle has no outputs, so only the range shows up
+        assert_eq!(ctx.describe_outputs(&outputs), vec!["range: value = 5 items, first 10.00".to_string()]);
+        // nothing is kept unless asked for
+        assert!(ctx.last_outputs.borrow().is_empty());
+    }
+}
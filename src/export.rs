@@ -0,0 +1,282 @@
+use std::fs::File;
+
+use macroquad::prelude::*;
+
+use crate::{LiveInputs, Timeline, TimelineItem};
+use crate::images::ImageCache;
+use crate::sink::{MacroquadSink, SvgSink};
+
+pub struct ExportSettings {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub path: String,
+    pub svg_path: String,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self { width: 640, height: 480, fps: 25, path: "out.gif".into(), svg_path: "out.svg".into() }
+    }
+}
+
+impl ExportSettings {
+    /// the timeline's canvas size if it has one, otherwise the size set here
+    pub fn size(&self, timeline: &Timeline) -> (u32, u32) {
+        match timeline.canvas_size {
+            Some((w, h)) => (w.round() as u32, h.round() as u32),
+            None => (self.width, self.height),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportKind {
+    Gif,
+    Svg,
+}
+
+/// maps 0, 0 - the target's size onto the whole render target. what's drawn
+/// through it reads back, and draws as a texture, the right way up
+pub fn target_camera(target: RenderTarget) -> Camera2D {
+    let (w, h) = (target.texture.width(), target.texture.height());
+    Camera2D {
+        // positive y zoom because render targets are upside down
+        // compared to the screen
+        zoom: vec2(2.0 / w, 2.0 / h),
+        target: vec2(w / 2.0, h / 2.0),
+        render_target: Some(target),
+        ..Default::default()
+    }
+}
+
+/// draws the items that are playing `secs` into the timeline into the
+/// render target, and reads back its pixels
+pub fn render_frame(
+    target: RenderTarget,
+    timeline: &Timeline,
+    secs: f32,
+    timeline_items: &[TimelineItem],
+    images: &mut ImageCache,
+) -> Result<Image, String> {
+    let (w, h) = (target.texture.width(), target.texture.height());
+    set_camera(&target_camera(target));
+    clear_background(timeline.background);
+    // there's no live input to react to while exporting
+    let result = timeline.run_items_at(secs, &LiveInputs::default(), timeline_items, (w, h), &mut MacroquadSink, images);
+    // switching cameras flushes the draw calls into the target
+    set_default_camera();
+    result?;
+    Ok(target.texture.get_texture_data())
+}
+
+/// viewers show gif frames shorter than 2 centiseconds for much longer,
+/// so frame rates above this can't be exported
+pub const MAX_GIF_FPS: u32 = 50;
+
+/// how many gif frames are rendered per frame of the app. the rest
+/// of the app (like the progress bar) only updates in between
+pub const GIF_FRAMES_PER_STEP: usize = 2;
+
+/// how far along an export is
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ExportProgress {
+    /// how many frames are done
+    pub frame: usize,
+    pub frame_count: usize,
+}
+
+impl ExportProgress {
+    /// from 0 to 1
+    pub fn fraction(&self) -> f32 {
+        if self.frame_count == 0 {
+            return 1.0;
+        }
+        (self.frame as f32 / self.frame_count as f32).min(1.0)
+    }
+}
+
+/// renders the whole timeline from left to right into a looping gif,
+/// a few frames per `step`, so that the app keeps drawing while it exports.
+/// items use their own seeds, so exporting twice gives the same gif.
+/// dropping it before it's done stops the export, keeping the frames
+/// written so far in the file
+pub struct GifExport {
+    encoder: gif::Encoder<File>,
+    target: RenderTarget,
+    size: (u16, u16),
+    /// see `frame_delay`
+    delay: u16,
+    /// the timeline's width and length when the export started. the frames
+    /// are spread over these, even if the timeline changes in the meantime
+    timeline_width: f32,
+    total_time_secs: f32,
+    pub progress: ExportProgress,
+}
+
+impl GifExport {
+    pub fn start(timeline: &Timeline, settings: &ExportSettings) -> Result<Self, String> {
+        let (w, h) = settings.size(timeline);
+        if w == 0 || h == 0 || w > u16::MAX as u32 || h > u16::MAX as u32 {
+            return Err(format!("Invalid gif size {}x{}", w, h));
+        }
+        let delay = Self::frame_delay(settings.fps);
+        let frame_count = Self::frame_count(timeline.total_time_secs, delay);
+        let (_, _, timeline_width, _) = timeline.dimensions();
+
+        let file = File::create(&settings.path)
+            .map_err(|e| format!("Failed to create {}: {}", settings.path, e))?;
+        let mut encoder = gif::Encoder::new(file, w as u16, h as u16, &[])
+            .map_err(|e| format!("Failed to encode gif: {}", e))?;
+        encoder.set_repeat(gif::Repeat::Infinite)
+            .map_err(|e| format!("Failed to encode gif: {}", e))?;
+        Ok(Self {
+            encoder,
+            target: render_target(w, h),
+            size: (w as u16, h as u16),
+            delay,
+            timeline_width,
+            total_time_secs: timeline.total_time_secs,
+            progress: ExportProgress { frame: 0, frame_count },
+        })
+    }
+    /// how long each frame shows for at `fps`. gif frame delays are in
+    /// centiseconds (hundredths of a second), so most frame rates can
+    /// only be matched roughly
+    pub fn frame_delay(fps: u32) -> u16 {
+        (100.0 / fps.clamp(1, MAX_GIF_FPS) as f32).round() as u16
+    }
+    /// how many frames of `delay` centiseconds fill the timeline. the
+    /// frames are spread by the rounded delay rather than the fps, so
+    /// the gif plays for as long as the timeline does
+    pub fn frame_count(total_time_secs: f32, delay: u16) -> usize {
+        ((total_time_secs * 100.0 / delay.max(1) as f32).round() as usize).max(1)
+    }
+    /// renders and writes up to `max_frames` more frames.
+    /// returns true once every frame is written
+    pub fn step(
+        &mut self,
+        timeline: &Timeline,
+        timeline_items: &[TimelineItem],
+        images: &mut ImageCache,
+        max_frames: usize,
+    ) -> Result<bool, String> {
+        let ExportProgress { frame, frame_count } = self.progress;
+        for i in frame..(frame + max_frames).min(frame_count) {
+            let bar_pos = self.timeline_width * i as f32 / frame_count as f32;
+            let secs = Timeline::secs_at(bar_pos, self.timeline_width, self.total_time_secs);
+            let mut image = render_frame(self.target, timeline, secs, timeline_items, images)?;
+            let mut gif_frame = gif::Frame::from_rgba_speed(self.size.0, self.size.1, &mut image.bytes, 10);
+            gif_frame.delay = self.delay;
+            self.encoder.write_frame(&gif_frame)
+                .map_err(|e| format!("Failed to encode gif: {}", e))?;
+            self.progress.frame = i + 1;
+        }
+        Ok(self.progress.frame >= frame_count)
+    }
+}
+
+impl Drop for GifExport {
+    fn drop(&mut self) {
+        // the encoder finishes the file when it's dropped
+        self.target.delete();
+    }
+}
+
+/// writes the frame under the timeline's current position as an svg.
+/// only blocks that draw through the draw sink end up in the svg
+pub fn export_svg(
+    timeline: &Timeline,
+    timeline_items: &[TimelineItem],
+    images: &mut ImageCache,
+    settings: &ExportSettings,
+) -> Result<(), String> {
+    let (w, h) = settings.size(timeline);
+    let (w, h) = (w as f32, h as f32);
+    let mut svg = SvgSink::new(w, h, timeline.background);
+    let (_, _, timeline_width, _) = timeline.dimensions();
+    let secs = Timeline::secs_at(timeline.bar_pos, timeline_width, timeline.total_time_secs);
+    timeline.run_items_at(secs, &LiveInputs::default(), timeline_items, (w, h), &mut svg, images)?;
+    std::fs::write(&settings.svg_path, svg.to_document())
+        .map_err(|e| format!("Failed to write {}: {}", settings.svg_path, e))
+}
+
+/// the points as the `d` of an svg path, one line through all of them
+pub fn points_svg_path(pts: &[(f32, f32)]) -> String {
+    let mut d = String::new();
+    for (i, (x, y)) in pts.iter().enumerate() {
+        if i > 0 {
+            d.push(' ');
+        }
+        d.push_str(&format!("{} {} {}", if i == 0 { "M" } else { "L" }, x, y));
+    }
+    d
+}
+
+/// an svg file of `width` x `height` with just the points' path in it
+pub fn points_svg_document(pts: &[(f32, f32)], width: f32, height: f32) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n\
+        <path d=\"{d}\" fill=\"none\" stroke=\"black\"/>\n</svg>\n",
+        w = width, h = height, d = points_svg_path(pts),
+    )
+}
+
+/// a header row, then an x,y row per point
+pub fn points_csv(pts: &[(f32, f32)]) -> String {
+    let mut csv = String::from("x,y\n");
+    for (x, y) in pts {
+        csv.push_str(&format!("{},{}\n", x, y));
+    }
+    csv
+}
+
+/// only touches the file when `contents` differ from what's in it,
+/// so a file that was changed or deleted elsewhere is written again.
+/// returns whether it wrote
+pub fn write_if_changed(path: &str, contents: String) -> Result<bool, String> {
+    if std::fs::read_to_string(path).ok().as_deref() == Some(contents.as_str()) {
+        return Ok(false);
+    }
+    std::fs::write(path, &contents)
+        .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_progress_fraction() {
+        assert_eq!(ExportProgress { frame: 0, frame_count: 4 }.fraction(), 0.0);
+        assert_eq!(ExportProgress { frame: 1, frame_count: 4 }.fraction(), 0.25);
+        assert_eq!(ExportProgress { frame: 9, frame_count: 4 }.fraction(), 1.0);
+        assert_eq!(ExportProgress { frame: 0, frame_count: 0 }.fraction(), 1.0);
+    }
+
+    #[test]
+    fn gif_frame_delays_round_to_centiseconds() {
+        assert_eq!(GifExport::frame_delay(25), 4);
+        assert_eq!(GifExport::frame_delay(30), 3);
+        assert_eq!(GifExport::frame_delay(50), 2);
+        assert_eq!(GifExport::frame_delay(1), 100);
+        // clamped to MAX_GIF_FPS
+        assert_eq!(GifExport::frame_delay(60), 2);
+        assert_eq!(GifExport::frame_delay(0), 100);
+    }
+
+    #[test]
+    fn gifs_last_as_long_as_the_timeline() {
+        for fps in 1..=MAX_GIF_FPS {
+            let delay = GifExport::frame_delay(fps);
+            let frame_count = GifExport::frame_count(30.0, delay);
+            // within a frame of 30 seconds, even when the delay isn't exact
+            let centisecs = frame_count as i64 * delay as i64;
+            assert!((centisecs - 3000).abs() <= delay as i64 / 2, "{} fps: {}cs", fps, centisecs);
+        }
+        // 30 fps rounds to 3cs frames, so there are 1000 of them rather than 900
+        assert_eq!(GifExport::frame_count(30.0, 3), 1000);
+        assert_eq!(GifExport::frame_count(0.0, 4), 1);
+    }
+}
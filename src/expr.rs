@@ -0,0 +1,260 @@
+use std::iter::Peekable;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Op(char),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Func {
+    Sin,
+    Cos,
+    Tan,
+    Sqrt,
+    Abs,
+}
+
+impl Func {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sin" => Some(Func::Sin),
+            "cos" => Some(Func::Cos),
+            "tan" => Some(Func::Tan),
+            "sqrt" => Some(Func::Sqrt),
+            "abs" => Some(Func::Abs),
+            _ => None,
+        }
+    }
+    fn apply(&self, x: f64) -> f64 {
+        match self {
+            Func::Sin => x.sin(),
+            Func::Cos => x.cos(),
+            Func::Tan => x.tan(),
+            Func::Sqrt => x.sqrt(),
+            Func::Abs => x.abs(),
+        }
+    }
+}
+
+/// a parsed math expression, see `parse`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Var(String),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(Func, Box<Expr>),
+}
+
+impl Expr {
+    /// `var` looks up the value of every name other than `pi`.
+    /// unknown names are errors
+    pub fn eval(&self, var: &dyn Fn(&str) -> Option<f64>) -> Result<f64, String> {
+        Ok(match self {
+            Expr::Number(n) => *n,
+            Expr::Var(name) if name == "pi" => std::f64::consts::PI,
+            Expr::Var(name) => var(name).ok_or_else(|| format!("Unknown variable {}", name))?,
+            Expr::Neg(e) => -e.eval(var)?,
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.eval(var)?, b.eval(var)?);
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    _ => a.powf(b),
+                }
+            }
+            Expr::Call(f, e) => f.apply(e.eval(var)?),
+        })
+    }
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = src.char_indices().peekable();
+    while let Some(&(i, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = i;
+            while let Some(&(j, c)) = chars.peek() {
+                if !(c.is_ascii_digit() || c == '.') {
+                    break;
+                }
+                end = j + 1;
+                chars.next();
+            }
+            let number = &src[i..end];
+            let value = number.parse::<f64>()
+                .map_err(|_| format!("Invalid number {:?} in expression", number))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = i;
+            while let Some(&(j, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = j + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Name(src[i..end].to_string()));
+        } else if "+-*/^()".contains(c) {
+            tokens.push(Token::Op(c));
+            chars.next();
+        } else {
+            return Err(format!("Unexpected {:?} in expression", c));
+        }
+    }
+    Ok(tokens)
+}
+
+type Tokens = Peekable<std::vec::IntoIter<Token>>;
+
+/// how deep parentheses, unary minuses and powers can nest. parsing
+/// recurses once per level, so without a cap a long enough run of `(`
+/// overflows the stack
+const MAX_DEPTH: usize = 256;
+
+fn next_op(tokens: &mut Tokens, ops: &str) -> Option<char> {
+    match tokens.peek() {
+        Some(Token::Op(c)) if ops.contains(*c) => {
+            let c = *c;
+            tokens.next();
+            Some(c)
+        }
+        _ => None,
+    }
+}
+
+/// sum = product (('+' | '-') product)*
+fn sum(tokens: &mut Tokens, depth: usize) -> Result<Expr, String> {
+    let mut e = product(tokens, depth)?;
+    while let Some(op) = next_op(tokens, "+-") {
+        e = Expr::Binary(op, Box::new(e), Box::new(product(tokens, depth)?));
+    }
+    Ok(e)
+}
+
+/// product = unary (('*' | '/') unary)*
+fn product(tokens: &mut Tokens, depth: usize) -> Result<Expr, String> {
+    let mut e = unary(tokens, depth)?;
+    while let Some(op) = next_op(tokens, "*/") {
+        e = Expr::Binary(op, Box::new(e), Box::new(unary(tokens, depth)?));
+    }
+    Ok(e)
+}
+
+/// unary = '-' unary | atom ('^' unary)?
+/// so -2^2 is -4 and 2^3^2 is 2^9, like in maths
+fn unary(tokens: &mut Tokens, depth: usize) -> Result<Expr, String> {
+    if depth > MAX_DEPTH {
+        return Err("Expression is nested too deeply".into());
+    }
+    if next_op(tokens, "-").is_some() {
+        return Ok(Expr::Neg(Box::new(unary(tokens, depth + 1)?)));
+    }
+    let base = atom(tokens, depth)?;
+    if next_op(tokens, "^").is_some() {
+        return Ok(Expr::Binary('^', Box::new(base), Box::new(unary(tokens, depth + 1)?)));
+    }
+    Ok(base)
+}
+
+fn parenthesized(tokens: &mut Tokens, depth: usize) -> Result<Expr, String> {
+    if next_op(tokens, "(").is_none() {
+        return Err("Expected ( in expression".into());
+    }
+    let e = sum(tokens, depth + 1)?;
+    if next_op(tokens, ")").is_none() {
+        return Err("Expected ) in expression".into());
+    }
+    Ok(e)
+}
+
+/// atom = number | name | function '(' sum ')' | '(' sum ')'
+fn atom(tokens: &mut Tokens, depth: usize) -> Result<Expr, String> {
+    match tokens.peek().cloned() {
+        Some(Token::Number(n)) => {
+            tokens.next();
+            Ok(Expr::Number(n))
+        }
+        Some(Token::Name(name)) => {
+            tokens.next();
+            match Func::from_name(&name) {
+                Some(f) => Ok(Expr::Call(f, Box::new(parenthesized(tokens, depth)?))),
+                None => Ok(Expr::Var(name)),
+            }
+        }
+        Some(Token::Op('(')) => parenthesized(tokens, depth),
+        Some(Token::Op(c)) => Err(format!("Unexpected {} in expression", c)),
+        None => Err("Unexpected end of expression".into()),
+    }
+}
+
+/// parses `+ - * / ^`, parentheses, numbers, names and the functions
+/// `sin cos tan sqrt abs`. names other than `pi` are looked up by `Expr::eval`.
+/// nesting deeper than MAX_DEPTH is an error
+pub fn parse(src: &str) -> Result<Expr, String> {
+    let mut tokens = tokenize(src)?.into_iter().peekable();
+    let e = sum(&mut tokens, 0)?;
+    match tokens.next() {
+        None => Ok(e),
+        Some(Token::Op(c)) => Err(format!("Unexpected {} in expression", c)),
+        Some(_) => Err("Expected an operator between values in expression".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(src: &str, t: f64) -> Result<f64, String> {
+        parse(src)?.eval(&|name| (name == "t").then_some(t))
+    }
+
+    #[test]
+    fn follows_precedence() {
+        assert_eq!(eval("1 + 2 * 3", 0.0), Ok(7.0));
+        assert_eq!(eval("(1 + 2) * 3", 0.0), Ok(9.0));
+        assert_eq!(eval("10 - 4 - 3", 0.0), Ok(3.0));
+        assert_eq!(eval("-2^2", 0.0), Ok(-4.0));
+        assert_eq!(eval("2^3^2", 0.0), Ok(512.0));
+        assert_eq!(eval("8 / -2", 0.0), Ok(-4.0));
+    }
+
+    #[test]
+    fn evaluates_names_and_functions() {
+        assert_eq!(eval("t * 2", 0.25), Ok(0.5));
+        assert_eq!(eval("cos(t * pi)", 1.0), Ok(-1.0));
+        assert_eq!(eval("sqrt(abs(-16))", 0.0), Ok(4.0));
+        assert_eq!(eval("u", 0.0), Err("Unknown variable u".into()));
+    }
+
+    #[test]
+    fn malformed_expressions_are_errors() {
+        assert!(eval("", 0.0).is_err());
+        assert!(eval("1 +", 0.0).is_err());
+        assert!(eval("(1 + 2", 0.0).is_err());
+        assert!(eval("sin t", 0.0).is_err());
+        assert!(eval("1 2", 0.0).is_err());
+        assert!(eval("1 % 2", 0.0).is_err());
+        assert!(eval("1..2", 0.0).is_err());
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let nested = |open: &str, n: usize, close: &str| format!("{}1{}", open.repeat(n), close.repeat(n));
+        assert_eq!(eval(&nested("(", MAX_DEPTH, ")"), 0.0), Ok(1.0));
+        assert_eq!(eval(&nested("-", MAX_DEPTH, ""), 0.0), Ok(1.0));
+        assert_eq!(eval(&nested("sqrt(", 10, ")"), 0.0), Ok(1.0));
+        // these would overflow the stack without the cap
+        let too_deep = Err("Expression is nested too deeply".into());
+        assert_eq!(eval(&nested("(", 100_000, ")"), 0.0), too_deep);
+        assert_eq!(eval(&nested("-", 100_000, ""), 0.0), too_deep);
+        assert_eq!(eval(&"2^".repeat(100_000), 0.0), too_deep);
+        assert_eq!(eval(&nested("(", MAX_DEPTH + 1, ")"), 0.0), too_deep);
+    }
+}
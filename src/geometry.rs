@@ -0,0 +1,310 @@
+/// below this, a triangle's (doubled) area counts as 0
+const EPSILON: f32 = 1e-6;
+
+/// twice the signed area of the triangle a, b, c.
+/// positive when a -> b -> c turns counter clockwise (with y going up)
+pub fn cross(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> f32 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+/// twice the signed area of the polygon, same sign convention as `cross`
+pub fn polygon_area2(pts: &[(f32, f32)]) -> f32 {
+    let mut area = 0.0;
+    for i in 0..pts.len() {
+        let (a, b) = (pts[i], pts[(i + 1) % pts.len()]);
+        area += a.0 * b.1 - b.0 * a.1;
+    }
+    area
+}
+
+/// p mirrored across the line through a and b.
+/// None if a and b are the same point, so there's no line
+pub fn reflect(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> Option<(f32, f32)> {
+    let d = (b.0 - a.0, b.1 - a.1);
+    let length2 = d.0 * d.0 + d.1 * d.1;
+    if length2 < EPSILON {
+        return None;
+    }
+    // the closest point to p on the line is halfway to the reflection
+    let along = ((p.0 - a.0) * d.0 + (p.1 - a.1) * d.1) / length2;
+    let closest = (a.0 + d.0 * along, a.1 + d.1 * along);
+    Some((2.0 * closest.0 - p.0, 2.0 * closest.1 - p.1))
+}
+
+/// true if p is inside the counter clockwise triangle a, b, c, or on its edge
+fn in_triangle(p: (f32, f32), a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> bool {
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+/// splits a simple polygon (convex or not, either winding) into triangles
+/// by ear clipping. returns indices into `pts`. collinear points don't
+/// produce triangles. self intersecting polygons can't be fully
+/// triangulated, in which case only the ears found so far are returned
+pub fn triangulate(pts: &[(f32, f32)]) -> Vec<[usize; 3]> {
+    let mut triangles = vec![];
+    if pts.len() < 3 {
+        return triangles;
+    }
+    let area = polygon_area2(pts);
+    if area.abs() < EPSILON {
+        return triangles;
+    }
+    // work in counter clockwise order so that convex corners have cross > 0
+    let mut remaining: Vec<usize> = (0..pts.len()).collect();
+    if area < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut i = 0;
+    // how many corners in a row were checked without clipping one.
+    // once every remaining corner was checked, there are no ears left
+    let mut checked = 0;
+    while remaining.len() > 3 && checked < remaining.len() {
+        let n = remaining.len();
+        let prev = remaining[(i + n - 1) % n];
+        let curr = remaining[i % n];
+        let next = remaining[(i + 1) % n];
+        let (a, b, c) = (pts[prev], pts[curr], pts[next]);
+        let turn = cross(a, b, c);
+        if turn.abs() < EPSILON {
+            // a straight (or doubled back) corner adds nothing, drop it
+            remaining.remove(i % n);
+            checked = 0;
+            continue;
+        }
+        let is_ear = turn > 0.0 && !remaining.iter().any(|&other| {
+            other != prev && other != curr && other != next
+                && pts[other] != a && pts[other] != b && pts[other] != c
+                && in_triangle(pts[other], a, b, c)
+        });
+        if is_ear {
+            triangles.push([prev, curr, next]);
+            remaining.remove(i % n);
+            checked = 0;
+        } else {
+            i += 1;
+            checked += 1;
+        }
+        i %= remaining.len();
+    }
+    if remaining.len() == 3 {
+        let [a, b, c] = [remaining[0], remaining[1], remaining[2]];
+        if cross(pts[a], pts[b], pts[c]).abs() >= EPSILON {
+            triangles.push([a, b, c]);
+        }
+    }
+    triangles
+}
+
+/// the center and squared radius of the circle through a, b and c.
+/// collinear points have no such circle, in which case the radius is
+/// infinite so that every point counts as inside it
+fn circumcircle(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> ((f64, f64), f64) {
+    let d = 2.0 * (a.0 * (b.1 - c.1) + b.0 * (c.1 - a.1) + c.0 * (a.1 - b.1));
+    if d.abs() < 1e-12 {
+        return ((f64::INFINITY, f64::INFINITY), f64::INFINITY);
+    }
+    let (a2, b2, c2) = (a.0 * a.0 + a.1 * a.1, b.0 * b.0 + b.1 * b.1, c.0 * c.0 + c.1 * c.1);
+    let x = (a2 * (b.1 - c.1) + b2 * (c.1 - a.1) + c2 * (a.1 - b.1)) / d;
+    let y = (a2 * (c.0 - b.0) + b2 * (a.0 - c.0) + c2 * (b.0 - a.0)) / d;
+    ((x, y), (a.0 - x).powi(2) + (a.1 - y).powi(2))
+}
+
+/// the delaunay triangulation of the points (Bowyer-Watson), as indices
+/// into `pts`. duplicate points are only used once. fewer than 3
+/// distinct points, or points that are all on one line, give no triangles
+pub fn delaunay(pts: &[(f32, f32)]) -> Vec<[usize; 3]> {
+    if pts.len() < 3 {
+        return vec![];
+    }
+    // f64 because the super triangle is much bigger than the points
+    let mut vertices: Vec<(f64, f64)> = pts.iter().map(|p| (p.0 as f64, p.1 as f64)).collect();
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for (x, y) in vertices.iter() {
+        min_x = min_x.min(*x);
+        min_y = min_y.min(*y);
+        max_x = max_x.max(*x);
+        max_y = max_y.max(*y);
+    }
+    let size = (max_x - min_x).max(max_y - min_y);
+    if !size.is_finite() || size <= 0.0 {
+        return vec![];
+    }
+    // a triangle that contains every point, removed again at the end
+    let (mid_x, mid_y) = ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0);
+    let n = pts.len();
+    vertices.push((mid_x - 20.0 * size, mid_y - size));
+    vertices.push((mid_x, mid_y + 20.0 * size));
+    vertices.push((mid_x + 20.0 * size, mid_y - size));
+
+    let make_triangle = |vertices: &Vec<(f64, f64)>, t: [usize; 3]| {
+        let (center, r2) = circumcircle(vertices[t[0]], vertices[t[1]], vertices[t[2]]);
+        (t, center, r2)
+    };
+    let mut triangles = vec![make_triangle(&vertices, [n, n + 1, n + 2])];
+    let mut seen = std::collections::HashSet::new();
+    for i in 0..n {
+        if !seen.insert((pts[i].0.to_bits(), pts[i].1.to_bits())) {
+            continue;
+        }
+        let p = vertices[i];
+        let mut boundary: Vec<(usize, usize)> = vec![];
+        triangles.retain(|(t, center, r2)| {
+            let inside = r2.is_infinite()
+                || (p.0 - center.0).powi(2) + (p.1 - center.1).powi(2) < *r2 * (1.0 - 1e-9);
+            if inside {
+                for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+                    // an edge shared by 2 removed triangles is inside the hole
+                    match boundary.iter().position(|e| *e == (b, a) || *e == (a, b)) {
+                        Some(shared) => { boundary.swap_remove(shared); }
+                        None => boundary.push((a, b)),
+                    }
+                }
+            }
+            !inside
+        });
+        for (a, b) in boundary {
+            triangles.push(make_triangle(&vertices, [a, b, i]));
+        }
+    }
+    triangles.into_iter()
+        .map(|(t, _, _)| t)
+        .filter(|t| t.iter().all(|v| *v < n))
+        .filter(|t| cross(pts[t[0]], pts[t[1]], pts[t[2]]).abs() >= EPSILON)
+        .collect()
+}
+
+/// every edge of the triangles once, lower index first, sorted
+pub fn triangle_edges(triangles: &[[usize; 3]]) -> Vec<(usize, usize)> {
+    let mut edges = std::collections::BTreeSet::new();
+    for t in triangles {
+        for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+            edges.insert((a.min(b), a.max(b)));
+        }
+    }
+    edges.into_iter().collect()
+}
+
+/// the boundaries between the voronoi cells of the points, built from
+/// their delaunay triangulation. the outer cells are open, their edges
+/// are rays cut off after `ray_length`
+pub fn voronoi_edges(pts: &[(f32, f32)], ray_length: f32) -> Vec<((f32, f32), (f32, f32))> {
+    let triangles = delaunay(pts);
+    let centers: Vec<(f32, f32)> = triangles.iter().map(|t| {
+        let p = |i: usize| (pts[t[i]].0 as f64, pts[t[i]].1 as f64);
+        let ((x, y), _) = circumcircle(p(0), p(1), p(2));
+        (x as f32, y as f32)
+    }).collect();
+    // which triangles (up to 2) share each edge, and the corner opposite it
+    let mut edge_triangles: std::collections::BTreeMap<(usize, usize), Vec<(usize, usize)>> = Default::default();
+    for (ti, t) in triangles.iter().enumerate() {
+        for (a, b, opposite) in [(t[0], t[1], t[2]), (t[1], t[2], t[0]), (t[2], t[0], t[1])] {
+            edge_triangles.entry((a.min(b), a.max(b))).or_default().push((ti, opposite));
+        }
+    }
+    let mut edges = vec![];
+    for ((a, b), sharing) in edge_triangles {
+        match sharing[..] {
+            [(t1, _), (t2, _)] => {
+                // triangles on the same circle (like the two halves of a
+                // square) share a center, so there's no edge between them
+                let (c1, c2) = (centers[t1], centers[t2]);
+                if (c1.0 - c2.0).hypot(c1.1 - c2.1) > EPSILON {
+                    edges.push((c1, c2));
+                }
+            }
+            [(t, opposite)] => {
+                // an edge on the hull: the ray goes along the edge's
+                // normal, away from the rest of the triangle
+                let (pa, pb, po) = (pts[a], pts[b], pts[opposite]);
+                let (mut nx, mut ny) = (pb.1 - pa.1, pa.0 - pb.0);
+                let (mx, my) = ((pa.0 + pb.0) / 2.0, (pa.1 + pb.1) / 2.0);
+                if nx * (po.0 - mx) + ny * (po.1 - my) > 0.0 {
+                    nx = -nx;
+                    ny = -ny;
+                }
+                let len = nx.hypot(ny);
+                let start = centers[t];
+                edges.push((start, (start.0 + nx / len * ray_length, start.1 + ny / len * ray_length)));
+            }
+            _ => {}
+        }
+    }
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total_area(pts: &[(f32, f32)], triangles: &[[usize; 3]]) -> f32 {
+        triangles.iter().map(|[a, b, c]| cross(pts[*a], pts[*b], pts[*c]).abs() / 2.0).sum()
+    }
+
+    #[test]
+    fn reflects_across_any_line() {
+        // the diagonal y = x swaps the coordinates
+        let p = reflect((3.0, 1.0), (0.0, 0.0), (2.0, 2.0)).unwrap();
+        assert!((p.0 - 1.0).abs() < 1e-5 && (p.1 - 3.0).abs() < 1e-5, "{:?}", p);
+        // points on the line stay put
+        assert_eq!(reflect((5.0, 5.0), (0.0, 0.0), (1.0, 1.0)), Some((5.0, 5.0)));
+        assert_eq!(reflect((1.0, 2.0), (4.0, 4.0), (4.0, 4.0)), None);
+    }
+
+    #[test]
+    fn triangulates_concave_polygon() {
+        // an L shape, 3 squares of area 1
+        let l_shape = [(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0), (1.0, 2.0), (0.0, 2.0)];
+        let triangles = triangulate(&l_shape);
+        assert_eq!(triangles.len(), 4);
+        assert!((total_area(&l_shape, &triangles) - 3.0).abs() < 1e-5);
+
+        // same shape, clockwise
+        let mut reversed = l_shape;
+        reversed.reverse();
+        let triangles = triangulate(&reversed);
+        assert_eq!(triangles.len(), 4);
+        assert!((total_area(&reversed, &triangles) - 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn skips_degenerate_polygons() {
+        assert!(triangulate(&[(0.0, 0.0), (1.0, 1.0)]).is_empty());
+        assert!(triangulate(&[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (3.0, 3.0)]).is_empty());
+        // the collinear middle point on the bottom edge doesn't add a sliver
+        let square = [(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+        let triangles = triangulate(&square);
+        assert!((total_area(&square, &triangles) - 4.0).abs() < 1e-5);
+        assert!(triangles.iter().all(|[a, b, c]| cross(square[*a], square[*b], square[*c]).abs() > 0.0));
+    }
+
+    #[test]
+    fn delaunay_of_a_square() {
+        let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let triangles = delaunay(&square);
+        assert_eq!(triangles.len(), 2);
+        // 4 sides and one diagonal
+        assert_eq!(triangle_edges(&triangles).len(), 5);
+        assert!((total_area(&square, &triangles) - 100.0).abs() < 1e-3);
+
+        // the center point splits it into 4
+        let with_center = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0), (5.0, 5.0)];
+        let triangles = delaunay(&with_center);
+        assert_eq!(triangles.len(), 4);
+        assert_eq!(triangle_edges(&triangles).len(), 8);
+        // the 4 cells meet in the center of each triangle's circumcircle,
+        // and each of the 4 hull edges has a ray going out
+        assert_eq!(voronoi_edges(&with_center, 100.0).len(), 8);
+    }
+
+    #[test]
+    fn delaunay_skips_degenerate_points() {
+        assert!(delaunay(&[(0.0, 0.0), (1.0, 1.0)]).is_empty());
+        assert!(delaunay(&[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (3.0, 3.0)]).is_empty());
+        assert!(delaunay(&[(1.0, 1.0), (1.0, 1.0), (1.0, 1.0)]).is_empty());
+        assert!(voronoi_edges(&[(0.0, 0.0), (1.0, 1.0)], 100.0).is_empty());
+        // a duplicate point doesn't add triangles
+        let triangles = delaunay(&[(0.0, 0.0), (10.0, 0.0), (0.0, 10.0), (10.0, 0.0)]);
+        assert_eq!(triangles.len(), 1);
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use macroquad::prelude::*;
+
+/// images that blocks sample from, keyed by path. failed loads aren't
+/// kept, so a missing file (or a path that's still being typed) is
+/// tried again on the next run instead of staying an error
+#[derive(Default)]
+pub struct ImageCache {
+    images: HashMap<String, Image>,
+}
+
+impl ImageCache {
+    pub fn get(&mut self, path: &str) -> Result<&Image, String> {
+        if !self.images.contains_key(path) {
+            let image = load_image_file(path)?;
+            self.images.insert(path.to_string(), image);
+        }
+        Ok(&self.images[path])
+    }
+    #[cfg(test)]
+    pub fn insert(&mut self, path: &str, image: Image) {
+        self.images.insert(path.to_string(), image);
+    }
+}
+
+/// only png is supported, same as macroquad's own image loading.
+/// unlike macroquad's `Image::from_file_with_format`, this doesn't panic
+/// on bad files
+fn load_image_file(path: &str) -> Result<Image, String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read image {}: {}", path, e))?;
+    let decoded = image::load_from_memory(&bytes)
+        .map_err(|e| format!("Failed to decode image {}: {}", path, e))?
+        .to_rgba8();
+    let (width, height) = decoded.dimensions();
+    if width == 0 || height == 0 || width > u16::MAX as u32 || height > u16::MAX as u32 {
+        return Err(format!("Image {} has an unsupported size {}x{}", path, width, height));
+    }
+    Ok(Image { width: width as u16, height: height as u16, bytes: decoded.into_raw() })
+}
+
+/// the color of the pixel closest to the normalized coordinate (u, v).
+/// (0, 0) is the top left, (1, 1) the bottom right. out of range
+/// coordinates are clamped to the edges
+pub fn sample(image: &Image, u: f32, v: f32) -> Color {
+    if image.width == 0 || image.height == 0 {
+        return BLANK;
+    }
+    let x = (u.clamp(0.0, 1.0) * (image.width - 1) as f32).round() as u32;
+    let y = (v.clamp(0.0, 1.0) * (image.height - 1) as f32).round() as u32;
+    image.get_pixel(x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_picks_the_closest_pixel() {
+        let mut image = Image::gen_image_color(2, 2, BLACK);
+        image.set_pixel(1, 0, WHITE);
+        assert_eq!(sample(&image, 0.0, 0.0), BLACK);
+        assert_eq!(sample(&image, 0.9, 0.1), WHITE);
+        // clamped to the top right corner
+        assert_eq!(sample(&image, 5.0, -5.0), WHITE);
+    }
+
+    #[test]
+    fn missing_files_are_errors() {
+        let mut cache = ImageCache::default();
+        let err = cache.get("does/not/exist.png").unwrap_err();
+        assert!(err.contains("does/not/exist.png"), "{}", err);
+        // so that it's read again once it exists
+        assert!(cache.images.is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+use macroquad::prelude::*;
+
+use crate::{EditorWindow, Timeline};
+
+/// where the layout is saved whenever a panel is resized
+pub const LAYOUT_PATH: &str = "layout.cfg";
+/// how far outside of a panel's edge it can be grabbed to resize it
+pub const HANDLE_SIZE: f32 = 6.0;
+pub const MIN_EDITOR_WIDTH: f32 = 200.0;
+/// the editor can't be dragged any wider than leaves this much of the canvas
+pub const MIN_CANVAS_WIDTH: f32 = 100.0;
+
+/// the sizes of the panels that the user can change by dragging their edges
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    pub editor_width: f32,
+    pub timeline_percentage_height: f32,
+}
+
+impl Layout {
+    pub fn from_ui(window: &EditorWindow, timeline: &Timeline) -> Self {
+        Self {
+            editor_width: window.width,
+            timeline_percentage_height: timeline.percentage_height,
+        }
+    }
+    pub fn apply(&self, window: &mut EditorWindow, timeline: &mut Timeline) {
+        window.width = self.editor_width;
+        timeline.percentage_height = self.timeline_percentage_height;
+    }
+    /// the same layout, kept within what dragging the edges allows on a
+    /// screen of this size. the saved layout may be from a bigger screen
+    pub fn clamped(self, screen_width: f32, screen_height: f32, min_height: f32, max_height: f32) -> Self {
+        let max_width = (screen_width - MIN_CANVAS_WIDTH).max(MIN_EDITOR_WIDTH);
+        let height = self.timeline_percentage_height * screen_height;
+        Self {
+            editor_width: self.editor_width.clamp(MIN_EDITOR_WIDTH, max_width),
+            timeline_percentage_height: timeline_percentage_for(screen_height - height, screen_height, min_height, max_height),
+        }
+    }
+    /// one `key=value` per line
+    pub fn to_config(self) -> String {
+        format!(
+            "editor_width={}\ntimeline_percentage_height={}\n",
+            self.editor_width, self.timeline_percentage_height
+        )
+    }
+    /// keys missing from the config keep their value from `defaults`.
+    /// unknown keys are ignored
+    pub fn parse(config: &str, defaults: Layout) -> Result<Self, String> {
+        let mut layout = defaults;
+        for line in config.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| format!("Expected key=value in layout, found {:?}", line))?;
+            let value: f32 = value.trim().parse()
+                .map_err(|_| format!("Invalid number for {} in layout: {:?}", key.trim(), value.trim()))?;
+            if !value.is_finite() || value <= 0.0 {
+                return Err(format!("{} in layout must be more than 0, found {}", key.trim(), value));
+            }
+            match key.trim() {
+                "editor_width" => layout.editor_width = value,
+                "timeline_percentage_height" => layout.timeline_percentage_height = value,
+                _ => {}
+            }
+        }
+        Ok(layout)
+    }
+    /// Ok(None) if there is no saved layout yet
+    pub fn load(path: &str, defaults: Layout) -> Result<Option<Self>, String> {
+        match std::fs::read_to_string(path) {
+            Ok(config) => Self::parse(&config, defaults).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to read {}: {}", path, e)),
+        }
+    }
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_config())
+            .map_err(|e| format!("Failed to write {}: {}", path, e))
+    }
+}
+
+/// the editor width that puts the editor's left edge at `mouse_x`
+pub fn editor_width_for(mouse_x: f32, screen_width: f32) -> f32 {
+    let max = (screen_width - MIN_CANVAS_WIDTH).max(MIN_EDITOR_WIDTH);
+    (screen_width - mouse_x).clamp(MIN_EDITOR_WIDTH, max)
+}
+
+/// the timeline percentage height that puts the timeline's top edge at
+/// `mouse_y`, kept within the timeline's min/max height
+pub fn timeline_percentage_for(mouse_y: f32, screen_height: f32, min_height: f32, max_height: f32) -> f32 {
+    if screen_height <= 0.0 {
+        return 0.0;
+    }
+    let height = (screen_height - mouse_y).clamp(min_height, max_height.max(min_height));
+    height / screen_height
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutEdge {
+    /// the left edge of the editor window
+    Editor,
+    /// the top edge of the timeline
+    Timeline,
+}
+
+/// resizing the panels by dragging their edges
+#[derive(Default)]
+pub struct LayoutHandles {
+    pub hovered: Option<LayoutEdge>,
+    pub dragging: Option<LayoutEdge>,
+}
+
+impl LayoutHandles {
+    /// the edge whose handle is under pt. the handles are just outside
+    /// of the panels, so they don't overlap anything inside of them
+    pub fn edge_at(pt: (f32, f32), window: &EditorWindow, timeline: &Timeline) -> Option<LayoutEdge> {
+        let (editor_x, editor_y, _, editor_h) = window.dimensions(timeline);
+        let (_, timeline_y, _, _) = timeline.dimensions();
+        if pt.0 >= editor_x - HANDLE_SIZE && pt.0 < editor_x && pt.1 >= editor_y && pt.1 < editor_y + editor_h {
+            Some(LayoutEdge::Editor)
+        } else if pt.1 >= timeline_y - HANDLE_SIZE && pt.1 < timeline_y {
+            Some(LayoutEdge::Timeline)
+        } else {
+            None
+        }
+    }
+    /// true while the mouse is on a handle or dragging one. the mouse
+    /// shouldn't do anything else in that case
+    pub fn is_active(&self) -> bool {
+        self.hovered.is_some() || self.dragging.is_some()
+    }
+    /// returns true when a resize just finished, so that it can be saved
+    pub fn update(&mut self, window: &mut EditorWindow, timeline: &mut Timeline, pointer_over_egui: bool) -> bool {
+        let (mx, my) = mouse_position();
+        let (s_width, s_height) = (screen_width(), screen_height());
+        match self.dragging {
+            Some(LayoutEdge::Editor) => window.width = editor_width_for(mx, s_width),
+            Some(LayoutEdge::Timeline) => {
+                timeline.percentage_height = timeline_percentage_for(my, s_height, timeline.min_height, timeline.max_height);
+            }
+            None => {}
+        }
+        if self.dragging.is_some() && is_mouse_button_released(MouseButton::Left) {
+            self.dragging = None;
+            return true;
+        }
+        self.hovered = if pointer_over_egui || self.dragging.is_some() {
+            None
+        } else {
+            Self::edge_at((mx, my), window, timeline)
+        };
+        if self.hovered.is_some() && is_mouse_button_pressed(MouseButton::Left) {
+            self.dragging = self.hovered;
+        }
+        false
+    }
+    pub fn draw(&self, window: &EditorWindow, timeline: &Timeline) {
+        let color = Color::new(0.3, 0.5, 1.0, 0.8);
+        match self.dragging.or(self.hovered) {
+            Some(LayoutEdge::Editor) => {
+                let (x, y, _, h) = window.dimensions(timeline);
+                draw_rectangle(x - HANDLE_SIZE, y, HANDLE_SIZE, h, color);
+            }
+            Some(LayoutEdge::Timeline) => {
+                let (x, y, w, _) = timeline.dimensions();
+                draw_rectangle(x, y - HANDLE_SIZE, w, HANDLE_SIZE, color);
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULTS: Layout = Layout { editor_width: 350.0, timeline_percentage_height: 0.25 };
+
+    #[test]
+    fn config_round_trips() {
+        let layout = Layout { editor_width: 420.5, timeline_percentage_height: 0.3 };
+        assert_eq!(Layout::parse(&layout.to_config(), DEFAULTS), Ok(layout));
+        // missing keys keep the defaults, unknown ones are skipped
+        let parsed = Layout::parse("editor_width = 500\nsomething_new=1\n\n", DEFAULTS).unwrap();
+        assert_eq!(parsed, Layout { editor_width: 500.0, ..DEFAULTS });
+
+        assert!(Layout::parse("editor_width", DEFAULTS).is_err());
+        assert!(Layout::parse("editor_width=wide", DEFAULTS).is_err());
+        assert!(Layout::parse("editor_width=-5", DEFAULTS).is_err());
+    }
+
+    #[test]
+    fn resizing_is_clamped() {
+        assert_eq!(editor_width_for(600.0, 1000.0), 400.0);
+        assert_eq!(editor_width_for(990.0, 1000.0), MIN_EDITOR_WIDTH);
+        assert_eq!(editor_width_for(0.0, 1000.0), 1000.0 - MIN_CANVAS_WIDTH);
+
+        assert_eq!(timeline_percentage_for(800.0, 1000.0, 80.0, 300.0), 0.2);
+        // within the timeline's min/max height
+        assert_eq!(timeline_percentage_for(990.0, 1000.0, 80.0, 300.0), 0.08);
+        assert_eq!(timeline_percentage_for(0.0, 1000.0, 80.0, 300.0), 0.3);
+    }
+
+    #[test]
+    fn loaded_layouts_are_clamped_to_the_screen() {
+        let fits = Layout { editor_width: 400.0, timeline_percentage_height: 0.2 };
+        assert_eq!(fits.clamped(1000.0, 1000.0, 80.0, 300.0), fits);
+
+        // saved on a bigger screen
+        let big = Layout { editor_width: 1800.0, timeline_percentage_height: 0.9 };
+        assert_eq!(big.clamped(1000.0, 1000.0, 80.0, 300.0), Layout {
+            editor_width: 1000.0 - MIN_CANVAS_WIDTH,
+            timeline_percentage_height: 0.3,
+        });
+        let small = Layout { editor_width: 50.0, timeline_percentage_height: 0.01 };
+        assert_eq!(small.clamped(1000.0, 1000.0, 80.0, 300.0), Layout {
+            editor_width: MIN_EDITOR_WIDTH,
+            timeline_percentage_height: 0.08,
+        });
+    }
+}
@@ -1,26 +1,74 @@
-use color::Hsl;
+use audio::{AudioInput, AudioLevels};
+use color::{Hsl, Hsv, lerp_color, parse_hex, to_hex};
+use export::{ExportKind, ExportProgress, ExportSettings, GifExport, GIF_FRAMES_PER_STEP, MAX_GIF_FPS};
+use images::ImageCache;
+use midi::{MidiListener, MidiState};
+use layout::{Layout, LayoutHandles, LAYOUT_PATH};
+use sink::{BlendMode, DrawSink, MacroquadSink, RecordingSink, TransformedSink};
 use macroquad::prelude::*;
 use egui_macroquad::egui::{self, Ui};
 use ::rand::prelude::*;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use rand_chacha::ChaCha8Rng;
 
+mod audio;
 mod dependency_resolution;
 mod draw;
 mod color;
+mod export;
+mod expr;
+mod geometry;
+mod images;
+mod layout;
+mod midi;
+mod sink;
+mod svg_path;
 
-use draw::{BlockContext, DraggableBlock, BlockConnectionNode, OutputResult, FONT_SIZE, FONT_SIZE_F32};
+use draw::{BlockContext, DraggableBlock, BlockConnectionNode, Id, ListFollow, OutputResult, get_id, FONT_SIZE, FONT_SIZE_F32};
 use draw::ConnectionType::*;
 
 pub const BLOCK_WIDTH_PER_INPUT: f32 = 50.0;
 pub const BLOCK_HEIGHT: f32 = 40.0;
 pub const TIMELINE_ITEM_HEIGHT: f32 = 30.0;
+/// every lane is a row of the timeline one item tall, with a gap below it
+pub const LANE_HEIGHT: f32 = TIMELINE_ITEM_HEIGHT + 6.0;
+/// the space between the top of the timeline and the first lane
+pub const LANE_TOP_MARGIN: f32 = 4.0;
 pub const ERR_FONT_SIZE: u16 = 20;
 pub const ERR_FONT_SIZE_F32: f32 = ERR_FONT_SIZE as f32;
+/// the most values a block is allowed to generate in one run.
+/// prevents a typo like 1e9 from allocating until the app dies
+pub const MAX_GENERATED_VALUES: usize = 100_000;
+/// the preview moves the playhead this many times per second of the
+/// timeline. the Frame block counts frames at this rate
+pub const FRAMES_PER_SECOND: f32 = 60.0;
 
 pub fn screen_size() -> (f32, f32) {
     (screen_width(), screen_height())
 }
 
+/// true if the mouse is over one of the egui windows (or egui is in the
+/// middle of using it, eg: dragging a value). uses the state of the
+/// previous egui frame
+pub fn egui_wants_pointer() -> bool {
+    let mut wants_pointer = false;
+    egui_macroquad::cfg(|egui_ctx| {
+        wants_pointer = egui_ctx.wants_pointer_input() || egui_ctx.is_pointer_over_area();
+    });
+    wants_pointer
+}
+
+/// true if an egui text field has focus, so key presses are meant for it
+pub fn egui_wants_keyboard() -> bool {
+    let mut wants_keyboard = false;
+    egui_macroquad::cfg(|egui_ctx| {
+        wants_keyboard = egui_ctx.wants_keyboard_input();
+    });
+    wants_keyboard
+}
+
 #[derive(Debug, Clone)]
 pub enum InputValue {
     Number(f64),
@@ -29,6 +77,41 @@ pub enum InputValue {
     Selection((usize, Vec<String>)),
     ListNumbers(Vec<f64>),
     ListPoints(Vec<(f32, f32)>),
+    ListColors(Vec<Color>),
+    Boolean(bool),
+    Text(String),
+    /// a number worked out from a formula each run, see
+    /// BlockRunContext::eval_expression. only ever an input's own value
+    Expression(String),
+}
+
+/// floats are hashed by their bits, so that BlockContext::fingerprint can
+/// tell when any value changes without formatting it
+impl Hash for InputValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let hash_color = |c: &Color, state: &mut H| [c.r, c.g, c.b, c.a].map(f32::to_bits).hash(state);
+        std::mem::discriminant(self).hash(state);
+        match self {
+            InputValue::Number(x) => x.to_bits().hash(state),
+            InputValue::Point((x, y)) => (x.to_bits(), y.to_bits()).hash(state),
+            InputValue::Color(c) => hash_color(c, state),
+            InputValue::Selection(selection) => selection.hash(state),
+            InputValue::ListNumbers(list) => {
+                list.len().hash(state);
+                list.iter().for_each(|x| x.to_bits().hash(state));
+            }
+            InputValue::ListPoints(list) => {
+                list.len().hash(state);
+                list.iter().for_each(|(x, y)| (x.to_bits(), y.to_bits()).hash(state));
+            }
+            InputValue::ListColors(list) => {
+                list.len().hash(state);
+                list.iter().for_each(|c| hash_color(c, state));
+            }
+            InputValue::Boolean(b) => b.hash(state),
+            InputValue::Text(text) | InputValue::Expression(text) => text.hash(state),
+        }
+    }
 }
 
 impl From<(f32, f32)> for InputValue {
@@ -55,6 +138,24 @@ impl From<Color> for InputValue {
     }
 }
 
+impl From<bool> for InputValue {
+    fn from(x: bool) -> Self {
+        InputValue::Boolean(x)
+    }
+}
+
+impl From<String> for InputValue {
+    fn from(x: String) -> Self {
+        InputValue::Text(x)
+    }
+}
+
+impl From<&str> for InputValue {
+    fn from(x: &str) -> Self {
+        InputValue::Text(x.to_string())
+    }
+}
+
 impl From<&[&str]> for InputValue {
     fn from(x: &[&str]) -> Self {
         InputValue::Selection((0, x.iter().map(|s| s.to_string()).collect()))
@@ -62,6 +163,51 @@ impl From<&[&str]> for InputValue {
 }
 
 impl InputValue {
+    /// user facing name of this value's type, for error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            InputValue::Number(_) => "Number",
+            InputValue::Point(_) => "Point",
+            InputValue::Color(_) => "Color",
+            InputValue::Selection(_) => "Selection",
+            InputValue::ListNumbers(_) => "ListNumbers",
+            InputValue::ListPoints(_) => "ListPoints",
+            InputValue::ListColors(_) => "ListColors",
+            InputValue::Boolean(_) => "Boolean",
+            InputValue::Text(_) => "Text",
+            InputValue::Expression(_) => "Expression",
+        }
+    }
+    /// true for lists of numbers, points and colors
+    pub fn is_list(&self) -> bool {
+        matches!(self, InputValue::ListNumbers(_) | InputValue::ListPoints(_) | InputValue::ListColors(_))
+    }
+    /// an empty list of the same type, and the default value of one
+    /// of its elements. None if this isn't a list
+    pub fn empty_list(&self) -> Option<(InputValue, InputValue)> {
+        match self {
+            InputValue::ListNumbers(_) => Some((InputValue::ListNumbers(vec![]), InputValue::Number(0.0))),
+            InputValue::ListPoints(_) => Some((InputValue::ListPoints(vec![]), InputValue::Point((0.0, 0.0)))),
+            InputValue::ListColors(_) => Some((InputValue::ListColors(vec![]), InputValue::Color(BLACK))),
+            _ => None,
+        }
+    }
+    /// a short, human readable version of the value. used by the
+    /// live values overlay
+    pub fn describe(&self) -> String {
+        match self {
+            InputValue::Number(x) => format!("{:.2}", x),
+            InputValue::Point((x, y)) => format!("({:.1}, {:.1})", x, y),
+            InputValue::Color(c) => format!("rgba({:.2}, {:.2}, {:.2}, {:.2})", c.r, c.g, c.b, c.a),
+            InputValue::Selection((i, options)) => options.get(*i).cloned().unwrap_or_default(),
+            InputValue::ListNumbers(l) => format!("{} numbers", l.len()),
+            InputValue::ListPoints(l) => format!("{} points", l.len()),
+            InputValue::ListColors(l) => format!("{} colors", l.len()),
+            InputValue::Boolean(b) => b.to_string(),
+            InputValue::Text(t) => format!("{:?}", t),
+            InputValue::Expression(e) => format!("= {}", e),
+        }
+    }
     pub fn as_f32(&self) -> f32 {
         match self {
             InputValue::Number(x) => *x as _,
@@ -99,6 +245,16 @@ impl InputValue {
             }
         }
     }
+    pub fn as_list_colors(&self) -> &Vec<Color> {
+        match self {
+            InputValue::ListColors(x) => x,
+            x => {
+                macroquad::logging::error!("Expected ListColors, found {:?}", x);
+                static X: Vec<Color> = vec![];
+                &X
+            }
+        }
+    }
     pub fn as_color(&self) -> Color {
         match self {
             InputValue::Color(x) => *x,
@@ -108,6 +264,15 @@ impl InputValue {
             }
         }
     }
+    pub fn as_bool(&self) -> bool {
+        match self {
+            InputValue::Boolean(x) => *x,
+            x => {
+                macroquad::logging::error!("Expected Boolean, found {:?}", x);
+                false
+            }
+        }
+    }
     pub fn as_str(&self) -> &str {
         match self {
             InputValue::Selection((i, options)) => {
@@ -119,19 +284,121 @@ impl InputValue {
             }
         }
     }
+    pub fn as_text(&self) -> &str {
+        match self {
+            InputValue::Text(x) => x.as_str(),
+            x => {
+                macroquad::logging::error!("Expected Text, found {:?}", x);
+                ""
+            }
+        }
+    }
 }
 
-pub struct BlockRunContext {
+pub struct BlockRunContext<'a> {
     pub screen_w: f32,
     pub screen_h: f32,
     pub percentage: f32,
     pub rng: ChaCha8Rng,
+    /// shape blocks draw through this instead of calling macroquad directly
+    pub draw: TransformedSink<'a>,
+    /// images loaded by blocks. shared between runs so that
+    /// files are only loaded once
+    pub images: &'a mut ImageCache,
+    /// frames since the start of the timeline, see Timeline::frame_at
+    pub frame: u64,
+    /// the live audio input, sampled once per frame
+    pub audio: AudioLevels,
+    /// the MIDI controllers, polled once per frame
+    pub midi: &'a MidiState,
+}
+
+/// what's coming into the editor from outside while it runs. exports
+/// use the default (silence, every controller at 0), so that they
+/// don't depend on it
+#[derive(Default)]
+pub struct LiveInputs {
+    pub audio: AudioLevels,
+    pub midi: MidiState,
 }
 
-impl BlockRunContext {
+impl BlockRunContext<'_> {
     fn get_screen_space(&self) -> (f32, f32) {
         (self.screen_w, self.screen_h)
     }
+    /// the value of an expression input. it can use t (how far
+    /// through the item, 0 - 1), frame, screen_w and screen_h
+    pub fn eval_expression(&self, src: &str) -> Result<f64, String> {
+        expr::parse(src)?.eval(&|name| match name {
+            "t" => Some(self.percentage as f64),
+            "frame" => Some(self.frame as f64),
+            "screen_w" => Some(self.screen_w as f64),
+            "screen_h" => Some(self.screen_h as f64),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ItemDragKind {
+    Move,
+    Resize,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ItemDrag {
+    pub index: usize,
+    pub kind: ItemDragKind,
+    pub start_mouse_x: f32,
+    pub start_secs: f32,
+    pub start_duration_secs: f32,
+    /// false until the mouse moved more than ItemDrag::CLICK_DISTANCE.
+    /// until then, the drag might still just be a click
+    pub moved: bool,
+}
+impl ItemDrag {
+    /// grabbing this close to an item's right edge resizes it
+    pub const RESIZE_HANDLE_WIDTH: f32 = 6.0;
+    pub const CLICK_DISTANCE: f32 = 3.0;
+    pub const MIN_ITEM_DURATION_SECS: f32 = 0.25;
+
+    /// the handle and click distance are in pixels, `width_per_second`
+    /// turns the mouse movement into seconds
+    pub fn start(index: usize, item: &TimelineItem, mouse_x: f32, width_per_second: f32) -> Self {
+        let (x, length) = item.span_px(width_per_second);
+        let kind = if mouse_x >= x + length - Self::RESIZE_HANDLE_WIDTH {
+            ItemDragKind::Resize
+        } else {
+            ItemDragKind::Move
+        };
+        Self {
+            index,
+            kind,
+            start_mouse_x: mouse_x,
+            start_secs: item.start_secs,
+            start_duration_secs: item.duration_secs,
+            moved: false,
+        }
+    }
+    /// `lane` is the lane under the mouse, which moved items go to
+    pub fn apply(&mut self, item: &mut TimelineItem, mouse_x: f32, width_per_second: f32, lane: usize) {
+        let diff = mouse_x - self.start_mouse_x;
+        let changes_lane = self.kind == ItemDragKind::Move && lane != item.lane;
+        if !self.moved && diff.abs() <= Self::CLICK_DISTANCE && !changes_lane {
+            return;
+        }
+        self.moved = true;
+        let diff_secs = diff / width_per_second;
+        match self.kind {
+            ItemDragKind::Move => {
+                item.start_secs = (self.start_secs + diff_secs).max(0.0);
+                item.lane = lane;
+            }
+            ItemDragKind::Resize => {
+                item.duration_secs = (self.start_duration_secs + diff_secs).max(Self::MIN_ITEM_DURATION_SECS);
+            }
+        }
+    }
 }
 
 pub struct Timeline {
@@ -141,13 +408,95 @@ pub struct Timeline {
     /// a percentage (0 - 1) of how much vertical
     /// screen space to take up
     pub percentage_height: f32,
-    /// must be at least 5s
+    /// at least MIN_TOTAL_TIME_SECS, see set_total_time
     pub total_time_secs: f32,
     pub running: bool,
+    /// what the art is drawn on top of, both in the editor and in exports
+    pub background: Color,
+    /// when set, the art is rendered at this size, letterboxed into the
+    /// space left of the editor, instead of using the window's size.
+    /// exports use it too
+    pub canvas_size: Option<(f32, f32)>,
+    /// the item currently being moved/resized with the mouse
+    pub item_drag: Option<ItemDrag>,
+    /// the rows that items sit in, from the top. there's always at least one
+    pub lanes: Vec<Lane>,
+}
+
+/// a row of the timeline. items in lanes further up are drawn
+/// on top of the ones below them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lane {
+    pub name: String,
+    /// items in hidden lanes don't run, in the preview or in exports
+    pub visible: bool,
+}
+impl Lane {
+    pub fn new(name: String) -> Self {
+        Self { name, visible: true }
+    }
 }
 impl Timeline {
     pub fn new(percentage_height: f32) -> Self {
-        Self { bar_pos: 0.0, max_height: 300.0, min_height: 80.0, percentage_height, total_time_secs: 30.0, running: false }
+        Self {
+            bar_pos: 0.0,
+            max_height: 300.0,
+            min_height: 80.0,
+            percentage_height,
+            total_time_secs: 30.0,
+            running: false,
+            background: WHITE,
+            canvas_size: None,
+            item_drag: None,
+            lanes: (1..=3).map(|i| Lane::new(format!("Lane {}", i))).collect(),
+        }
+    }
+    /// the timeline's marks are every 5s, so it's at least that long
+    pub const MIN_TOTAL_TIME_SECS: f32 = 5.0;
+
+    /// changes how long the timeline is, clamped to MIN_TOTAL_TIME_SECS.
+    /// items keep their times. the bar is in pixels, and a second takes up
+    /// fewer of them on a longer timeline, so it's moved to stay on the
+    /// same time
+    pub fn set_total_time(&mut self, secs: f32) {
+        let secs = if secs.is_finite() { secs.max(Self::MIN_TOTAL_TIME_SECS) } else { self.total_time_secs };
+        self.bar_pos *= self.total_time_secs / secs;
+        self.total_time_secs = secs;
+    }
+    /// how far below the top of the timeline `lane` is
+    pub fn lane_offset(lane: usize) -> f32 {
+        LANE_TOP_MARGIN + lane as f32 * LANE_HEIGHT
+    }
+    /// the lane `offset` pixels below the top of the timeline. above the
+    /// first lane or below the last one is the closest lane
+    pub fn lane_at_offset(&self, offset: f32) -> usize {
+        let lane = ((offset - LANE_TOP_MARGIN) / LANE_HEIGHT).floor().max(0.0) as usize;
+        lane.min(self.lanes.len().saturating_sub(1))
+    }
+    pub fn is_lane_visible(&self, lane: usize) -> bool {
+        self.lanes.get(lane).is_none_or(|l| l.visible)
+    }
+    pub fn add_lane(&mut self) {
+        let name = format!("Lane {}", self.lanes.len() + 1);
+        self.lanes.push(Lane::new(name));
+    }
+    /// the last lane can't be removed. items on the removed lane move
+    /// to the one above it, or the new first lane
+    pub fn remove_lane(&mut self, lane: usize, timeline_items: &mut [TimelineItem]) {
+        if self.lanes.len() <= 1 || lane >= self.lanes.len() {
+            return;
+        }
+        self.lanes.remove(lane);
+        for item in timeline_items.iter_mut() {
+            if item.lane > lane || (item.lane == lane && lane > 0) {
+                item.lane -= 1;
+            }
+        }
+    }
+    /// how many pixels a second of the timeline takes up
+    pub fn width_per_second(&self) -> f32 {
+        let (_, _, width, _) = self.dimensions();
+        width / self.total_time_secs
     }
     pub fn max_height(mut self, max_height: f32) -> Self {
         self.max_height = max_height;
@@ -169,79 +518,174 @@ impl Timeline {
         let y = s_height - height;
         (0.0, y, s_width, height)
     }
-    pub fn handle_input(&mut self, open_item: &mut Option<usize>, timeline_items: &[TimelineItem]) {
+    /// `pointer_blocked` is true when the mouse is over some other ui, in
+    /// which case clicks don't open items or move the playhead
+    pub fn handle_input(&mut self, open_item: &mut Option<usize>, timeline_items: &mut [TimelineItem], pointer_blocked: bool) {
         if is_key_pressed(KeyCode::Space) {
             self.running = !self.running;
         }
 
         let (mx, my) = mouse_position();
-        if !is_mouse_button_pressed(MouseButton::Left) { return }
-
-        for (i, item) in timeline_items.iter().enumerate().rev() {
-            if mx >= item.x && mx < item.x + item.length && my >= item.y && my < item.y + TIMELINE_ITEM_HEIGHT {
-                // if item is open, and it was clicked again, we set it to be closed.
-                if let Some(index) = open_item {
-                    if *index == i {
-                        *open_item = None;
-                        return;
-                    }
+        let width_per_second = self.width_per_second();
+        let (_, y, _, _) = self.dimensions();
+        let lane = self.lane_at_offset(my - y);
+        if let Some(drag) = &mut self.item_drag {
+            if let Some(item) = timeline_items.get_mut(drag.index) {
+                drag.apply(item, mx, width_per_second, lane);
+            }
+            if is_mouse_button_released(MouseButton::Left) {
+                // a quick click that didn't move the item opens/closes it
+                if !drag.moved {
+                    *open_item = if *open_item == Some(drag.index) { None } else { Some(drag.index) };
+                }
+                self.item_drag = None;
+            }
+            return;
+        }
+        if pointer_blocked || !is_mouse_button_pressed(MouseButton::Left) { return }
+
+        for (i, item) in timeline_items.iter_mut().enumerate().rev() {
+            let (x, length) = item.span_px(width_per_second);
+            let item_y = y + Self::lane_offset(item.lane);
+            if mx >= x && mx < x + length && my >= item_y && my < item_y + TIMELINE_ITEM_HEIGHT {
+                match ItemToggle::at((mx - x, my - item_y)) {
+                    Some(toggle) => toggle.toggle(item),
+                    None => self.item_drag = Some(ItemDrag::start(i, item, mx, width_per_second)),
                 }
-                // otherwise, open it:
-                *open_item = Some(i);
                 return;
             }
         }
         // if no timeline items were clicked, then check if we clicked inside the timeline window
-        let (_, y, _, _) = self.dimensions();
         if my > y {
             self.bar_pos = mx;
         }
     }
-    pub fn run(&mut self, timeline_items: &[TimelineItem], screen_space: (f32, f32), error_queue: &mut ErrorQueue, seed: &mut u64) {
+    pub fn run(
+        &mut self,
+        timeline_items: &[TimelineItem],
+        screen_space: (f32, f32),
+        images: &mut ImageCache,
+        live: &LiveInputs,
+        error_queue: &mut ErrorQueue,
+    ) {
         let (_, _, width, _) = self.dimensions();
         let step_per_1s = width / self.total_time_secs;
-        let step_per_frame = step_per_1s / 60.0; // TODO: is this right?...
+        let step_per_frame = step_per_1s / FRAMES_PER_SECOND;
+
+        if !error_queue.has_errors() {
+            let secs = Self::secs_at(self.bar_pos, width, self.total_time_secs);
+            if let Err(e) = self.run_items_at(secs, live, timeline_items, screen_space, &mut MacroquadSink, images) {
+                self.running = false;
+                // if this is the first error message,
+                // add an extra error message that explains how
+                // to clear errors.
+                if error_queue.errors.len() == 0 {
+                    let e2 = format!("Error during evaluation. Pausing preview. Close all error messages (Esc) to resume");
+                    error_queue.push(ErrorMessage::new(e2));
+                }
+                error_queue.push(ErrorMessage::new(e));
+            }
+        }
 
-        // TODO: calculate which timeline items it's touching, and render them
+        if self.running {
+            self.bar_pos += step_per_frame;
+            if self.bar_pos > width {
+                self.bar_pos = 0.0;
+            }
+        }
+    }
+    /// the frame number at the timeline position `bar_pos`, counting
+    /// FRAMES_PER_SECOND from the start of a timeline `width` wide. it only
+    /// depends on the position, so the preview and exports agree on it
+    pub fn frame_at(bar_pos: f32, width: f32, total_time_secs: f32) -> u64 {
+        if width <= 0.0 || bar_pos <= 0.0 {
+            return 0;
+        }
+        Self::frame_at_secs(Self::secs_at(bar_pos, width, total_time_secs))
+    }
+    /// the frame number `secs` into the timeline
+    pub fn frame_at_secs(secs: f32) -> u64 {
+        if secs <= 0.0 {
+            return 0;
+        }
+        // a little over, so that rounding errors don't land on the previous frame
+        (secs * FRAMES_PER_SECOND + 1e-3).floor() as u64
+    }
+    /// the time at the timeline position `bar_pos`, on a timeline `width` wide
+    pub fn secs_at(bar_pos: f32, width: f32, total_time_secs: f32) -> f32 {
+        if width <= 0.0 {
+            return 0.0;
+        }
+        bar_pos / width * total_time_secs
+    }
+    /// runs every item that is playing `secs` into the timeline, skipping
+    /// hidden lanes. stops at the first item that errors
+    pub fn run_items_at(
+        &self,
+        secs: f32,
+        live: &LiveInputs,
+        timeline_items: &[TimelineItem],
+        screen_space: (f32, f32),
+        draw: &mut dyn DrawSink,
+        images: &mut ImageCache,
+    ) -> Result<(), String> {
+        let frame = Self::frame_at_secs(secs);
+        let any_soloed = timeline_items.iter().any(|item| item.soloed);
         let mut should_run_items = vec![];
-        for item in timeline_items {
-            if self.bar_pos >= item.x && self.bar_pos < item.x + item.length {
-                let percentage = (self.bar_pos - item.x) / item.length;
-                should_run_items.push((item.y, percentage, item));
+        for item in timeline_items.iter() {
+            let playing = secs >= item.start_secs && secs < item.end_secs();
+            if playing && item.plays(any_soloed) && self.is_lane_visible(item.lane) {
+                let linear = (secs - item.start_secs) / item.duration_secs;
+                should_run_items.push((item.lane, item.playback_percentage(linear), item.pass(linear), item));
             }
         }
-        // sort the items by their height. things higher up in the timeline
+        // sort the items by their lane. things higher up in the timeline
         // get rendered last (ie: above)
-        should_run_items.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        should_run_items.sort_by_key(|a| std::cmp::Reverse(a.0));
         // now they are sorted in order where the first items are the lowest in the timeline:
-        for (_, percentage, item) in should_run_items {
+        let mut run_item = |item: &TimelineItem, percentage: f32, pass: u32, sink: &mut dyn DrawSink| {
             let mut ctx = BlockRunContext {
                 screen_w: screen_space.0,
                 screen_h: screen_space.1,
                 percentage,
-                rng: ChaCha8Rng::seed_from_u64(*seed),
+                rng: item.rng(pass),
+                // a new one for every item, so transforms don't leak between items
+                draw: TransformedSink::new(sink),
+                images: &mut *images,
+                frame,
+                audio: live.audio,
+                midi: &live.midi,
             };
-            if !error_queue.has_errors() {
-                if let Err(e) = item.blocks.run(&mut ctx) {
-                    self.running = false;
-                    // if this is the first error message,
-                    // add an extra error message that explains how
-                    // to clear errors.
-                    if error_queue.errors.len() == 0 {
-                        let e2 = format!("Error during evaluation. Pausing preview. Close all error messages to resume");
-                        error_queue.errors.push(ErrorMessage { e: e2 });
-                    }
-                    error_queue.errors.push(ErrorMessage { e });
-                }
+            let result = item.blocks.run(&mut ctx);
+            // blend modes don't carry over to other items, or the ui
+            ctx.draw.set_blend_mode(BlendMode::Normal);
+            result
+        };
+        for (_, percentage, pass, item) in should_run_items {
+            // sinks that can't draw textures, like svg, get the shapes every time
+            if !item.frozen || !draw.draws_textures() {
+                run_item(item, percentage, pass, draw)?;
+                continue;
             }
-        }
-
-        if self.running {
-            self.bar_pos += step_per_frame;
-            if self.bar_pos > width {
-                self.bar_pos = 0.0;
+            let key = item.freeze_key(screen_space);
+            if item.frozen_draw.borrow().as_ref().map(|f| f.key) != Some(key) {
+                let target = render_target(screen_space.0.max(1.0) as u32, screen_space.1.max(1.0) as u32);
+                // made before the run, so the target is deleted if it fails
+                let frozen = FrozenDraw { key, target };
+                push_camera_state();
+                set_camera(&export::target_camera(target));
+                clear_background(Color::new(0.0, 0.0, 0.0, 0.0));
+                let result = run_item(item, percentage, pass, &mut MacroquadSink);
+                // this also flushes the draw calls into the target
+                pop_camera_state();
+                result?;
+                *item.frozen_draw.borrow_mut() = Some(frozen);
+            }
+            if let Some(frozen) = item.frozen_draw.borrow().as_ref() {
+                draw.texture(frozen.target.texture, screen_space.0, screen_space.1);
             }
         }
+        Ok(())
     }
     pub fn draw(&self, timeline_items: &[TimelineItem]) {
         let (x, y, w, h) = self.dimensions();
@@ -252,6 +696,14 @@ impl Timeline {
         let mut current_mark = 0.0;
         let mut current_time = 0;
         let s_height = screen_height();
+        for (i, lane) in self.lanes.iter().enumerate() {
+            let lane_y = y + Self::lane_offset(i);
+            if !lane.visible {
+                draw_rectangle(x, lane_y, w, TIMELINE_ITEM_HEIGHT, Color::new(0.0, 0.0, 0.0, 0.15));
+            }
+            draw_line(x, lane_y + LANE_HEIGHT - 3.0, x + w, lane_y + LANE_HEIGHT - 3.0, 1.0, Color::new(0.0, 0.0, 0.0, 0.2));
+            draw_text(&lane.name, x + 4.0, lane_y + TIMELINE_ITEM_HEIGHT - 4.0, 14.0, DARKGRAY);
+        }
         while current_mark < w {
             draw_line(current_mark, y, current_mark, s_height, 1.0, BLACK);
             draw_text(&format!("{current_time}s"), current_mark + 2.0, s_height - 2.0, 16.0, BLACK);
@@ -261,31 +713,250 @@ impl Timeline {
                 draw_line(current_mark, y, current_mark, s_height, 1.0, GRAY);
             }
         }
+        let any_soloed = timeline_items.iter().any(|item| item.soloed);
         for item in timeline_items {
-            draw_rectangle(item.x, item.y, item.length, TIMELINE_ITEM_HEIGHT, item.color);
+            let (item_x, length) = item.span_px(width_per_1s);
+            let item_y = y + Self::lane_offset(item.lane);
+            let mut color = item.color;
+            if !item.plays(any_soloed) || !self.is_lane_visible(item.lane) {
+                color.a *= 0.3;
+            }
+            draw_rectangle(item_x, item_y, length, TIMELINE_ITEM_HEIGHT, color);
+            // the part that can be grabbed to resize the item
+            let handle_x = item_x + length - ItemDrag::RESIZE_HANDLE_WIDTH;
+            draw_rectangle(handle_x, item_y, ItemDrag::RESIZE_HANDLE_WIDTH, TIMELINE_ITEM_HEIGHT, Color::new(0.0, 0.0, 0.0, 0.2));
+            for toggle in ItemToggle::ALL {
+                let (tx, ty) = toggle.offset();
+                let (tx, ty) = (item_x + tx, item_y + ty);
+                let fill = if toggle.is_on(item) { toggle.color() } else { Color::new(1.0, 1.0, 1.0, 0.5) };
+                draw_rectangle(tx, ty, ItemToggle::SIZE, ItemToggle::SIZE, fill);
+                draw_rectangle_lines(tx, ty, ItemToggle::SIZE, ItemToggle::SIZE, 1.0, BLACK);
+                draw_text(toggle.label(), tx + 2.0, ty + ItemToggle::SIZE - 2.0, 14.0, BLACK);
+            }
+            // so that a good seed can be noted down. only if it fits
+            let lock = if item.seed_locked { " (locked)" } else { "" };
+            let seed = format!("seed {}{}", item.seed, lock);
+            let seed_x = ItemToggle::ALL.len() as f32 * (ItemToggle::SIZE + ItemToggle::MARGIN) + ItemToggle::MARGIN;
+            if seed_x + measure_text(&seed, None, 14, 1.0).width < length - ItemDrag::RESIZE_HANDLE_WIDTH {
+                draw_text(&seed, item_x + seed_x, item_y + ItemToggle::SIZE, 14.0, BLACK);
+            }
         }
         draw_line(self.bar_pos, y, self.bar_pos, s_height, 1.0, RED);
     }
 }
 
+/// the buttons in the top left corner of every timeline item
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ItemToggle {
+    Mute,
+    Solo,
+}
+impl ItemToggle {
+    pub const ALL: [ItemToggle; 2] = [ItemToggle::Mute, ItemToggle::Solo];
+    pub const SIZE: f32 = 12.0;
+    pub const MARGIN: f32 = 2.0;
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ItemToggle::Mute => "M",
+            ItemToggle::Solo => "S",
+        }
+    }
+    /// what the button is filled with while it's on
+    pub fn color(&self) -> Color {
+        match self {
+            ItemToggle::Mute => ORANGE,
+            ItemToggle::Solo => YELLOW,
+        }
+    }
+    /// the button's top left, from the item's top left
+    pub fn offset(&self) -> (f32, f32) {
+        let i = Self::ALL.iter().position(|t| t == self).unwrap_or(0);
+        (Self::MARGIN + i as f32 * (Self::SIZE + Self::MARGIN), Self::MARGIN)
+    }
+    /// the button at `offset` from an item's top left
+    pub fn at(offset: (f32, f32)) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| {
+            let (x, y) = t.offset();
+            offset.0 >= x && offset.0 < x + Self::SIZE && offset.1 >= y && offset.1 < y + Self::SIZE
+        })
+    }
+    pub fn is_on(&self, item: &TimelineItem) -> bool {
+        match self {
+            ItemToggle::Mute => item.muted,
+            ItemToggle::Solo => item.soloed,
+        }
+    }
+    pub fn toggle(&self, item: &mut TimelineItem) {
+        match self {
+            ItemToggle::Mute => item.muted = !item.muted,
+            ItemToggle::Solo => item.soloed = !item.soloed,
+        }
+    }
+}
+
 pub struct TimelineItem {
-    pub x: f32,
-    pub y: f32,
-    pub length: f32,
+    /// stays the same when items are moved around, unlike the item's index
+    pub id: Id,
+    /// when the item starts playing, in seconds from the start of the timeline
+    pub start_secs: f32,
+    /// index into Timeline::lanes
+    pub lane: usize,
+    /// how long the item plays for, at least ItemDrag::MIN_ITEM_DURATION_SECS
+    pub duration_secs: f32,
     pub blocks: BlockContext,
     pub color: Color,
+    pub seed: u64,
+    /// how many times the item plays while the bar crosses it.
+    /// above 1 the item loops, below 1 it only plays part of the way
+    pub speed: f32,
+    /// index into EaseBlock::PRESETS, applied after the speed
+    pub playback_curve: usize,
+    /// the item's span is split into this many loops, each of
+    /// which runs the graph from 0 to 1. 0 is treated as 1
+    pub loop_count: u32,
+    /// muted items don't run
+    pub muted: bool,
+    /// while any item is soloed, only soloed items run
+    pub soloed: bool,
+    /// locked seeds aren't changed by re-seeding, see reseed
+    pub seed_locked: bool,
+    /// frozen items only run once, into a texture, and then draw that
+    /// texture until their blocks or the screen size change. for items
+    /// that look the same on every frame, like a background. their blend
+    /// modes only mix with what the item itself drew
+    pub frozen: bool,
+    /// what a frozen item drew when it last ran
+    pub frozen_draw: RefCell<Option<FrozenDraw>>,
+}
+
+pub struct FrozenDraw {
+    /// see TimelineItem::freeze_key
+    pub key: u64,
+    pub target: RenderTarget,
+}
+
+impl Drop for FrozenDraw {
+    fn drop(&mut self) {
+        self.target.delete();
+    }
+}
+impl TimelineItem {
+    /// speed is kept above this so the item doesn't freeze or run backwards
+    pub const MIN_SPEED: f32 = 0.01;
+
+    /// whether the item runs when the bar is over it, given
+    /// whether any item in the timeline is soloed
+    pub fn plays(&self, any_soloed: bool) -> bool {
+        !self.muted && (self.soloed || !any_soloed)
+    }
+    /// the texture of a frozen item is only reused while this stays the
+    /// same. it covers everything the item's drawing depends on, other than
+    /// the time and live inputs, which frozen items ignore
+    pub fn freeze_key(&self, screen_space: (f32, f32)) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.blocks.fingerprint(), self.seed, self.id).hash(&mut hasher);
+        (screen_space.0.to_bits(), screen_space.1.to_bits()).hash(&mut hasher);
+        hasher.finish()
+    }
+    pub fn end_secs(&self) -> f32 {
+        self.start_secs + self.duration_secs
+    }
+    /// the item's left edge and width in pixels, on a timeline
+    /// where a second is `width_per_second` wide
+    pub fn span_px(&self, width_per_second: f32) -> (f32, f32) {
+        (self.start_secs * width_per_second, self.duration_secs * width_per_second)
+    }
+    /// `linear` is how far the bar is through the item, from 0 to 1.
+    /// returns the percentage that this item's blocks see
+    pub fn playback_percentage(&self, linear: f32) -> f32 {
+        let speed = self.speed.max(Self::MIN_SPEED);
+        let loops = self.loop_count.max(1) as f32;
+        let t = ((linear * loops).fract() * speed) % 1.0;
+        let curve = EaseBlock::PRESETS.get(self.playback_curve).unwrap_or(&"linear");
+        EaseBlock::ease(curve, t)
+    }
+    /// `linear` is how far the bar is through the item, from 0 to 1.
+    /// returns which of the item's loops is playing, counting from 0
+    pub fn pass(&self, linear: f32) -> u32 {
+        let loops = self.loop_count.max(1);
+        ((linear * loops as f32).floor().max(0.0) as u32).min(loops - 1)
+    }
+    /// a copy placed right after this item on the timeline, with its own
+    /// copies of the blocks. the seed is different so that its random
+    /// values are too
+    pub fn duplicate(&self) -> TimelineItem {
+        TimelineItem {
+            id: get_id(),
+            start_secs: self.end_secs(),
+            lane: self.lane,
+            duration_secs: self.duration_secs,
+            blocks: self.blocks.duplicate(),
+            color: self.color,
+            seed: self.seed.wrapping_add(1),
+            speed: self.speed,
+            playback_curve: self.playback_curve,
+            loop_count: self.loop_count,
+            muted: self.muted,
+            soloed: self.soloed,
+            seed_locked: self.seed_locked,
+            frozen: self.frozen,
+            frozen_draw: RefCell::new(None),
+        }
+    }
+    /// picks a new seed, unless it's locked. the item's rng is recreated from
+    /// the seed on every run, so the next frame already uses it, even while paused
+    pub fn reseed(&mut self, rng: &mut impl Rng) {
+        if !self.seed_locked {
+            self.seed = rng.gen();
+        }
+    }
+    /// steps to a neighboring seed, wrapping around. works while locked,
+    /// the lock only stops random re-seeding
+    pub fn bump_seed(&mut self, by: i64) {
+        self.seed = self.seed.wrapping_add_signed(by);
+    }
+    /// the rng used for one run of this item's blocks, during loop `pass`
+    /// (see `pass`). it is recreated from scratch every run, so for a given
+    /// seed and pass, the random values are the same every frame
+    /// (ie: reproducible). the item's id and the pass select a separate
+    /// ChaCha stream, so two items that happen to share a seed, or two
+    /// loops of the same item, still don't produce the same random values.
+    /// moving items around the timeline doesn't change them. the stream is
+    /// the id and the pass side by side rather than a hash of them, so it
+    /// stays the same across builds
+    pub fn rng(&self, pass: u32) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_stream((u64::from(self.id) << 32) | pass as u64);
+        rng
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum SubWindowShown {
     BlockSelection,
     ValueEditing,
+    Export,
 }
 
 pub struct EditorWindow {
     pub width: f32,
     pub bottom_margin: f32,
     pub window_shown: SubWindowShown,
+    pub export_settings: ExportSettings,
+    /// set when the user clicks export. the main loop does the
+    /// export because it needs every timeline item
+    pub export_requested: Option<ExportKind>,
+    /// set when the user clicks duplicate on the open item. the main
+    /// loop adds the copy because it owns the timeline items
+    pub duplicate_requested: bool,
+    /// set when the user removes a lane. the main loop removes
+    /// it, because the items on it have to move
+    pub lane_removal_requested: Option<usize>,
+    /// set by the main loop while a gif is being exported
+    pub export_progress: Option<ExportProgress>,
+    /// set when the user aborts the running export
+    pub export_cancel_requested: bool,
 }
 impl EditorWindow {
     pub fn new() -> Self {
@@ -293,6 +964,12 @@ impl EditorWindow {
             window_shown: SubWindowShown::BlockSelection,
             width: 350.0,
             bottom_margin: 12.0,
+            export_settings: ExportSettings::default(),
+            export_requested: None,
+            duplicate_requested: false,
+            lane_removal_requested: None,
+            export_progress: None,
+            export_cancel_requested: false,
         }
     }
     pub fn dimensions(&self, timeline: &Timeline) -> (f32, f32, f32, f32) {
@@ -302,9 +979,8 @@ impl EditorWindow {
     }
     pub fn draw(
         &mut self,
-        timeline: &Timeline,
+        timeline: &mut Timeline,
         item: Option<&mut TimelineItem>,
-        seed: &mut u64,
         global_rng: &mut ChaCha8Rng,
         available_blocks: &[(fn () -> DraggableBlock, &str)],
     ) {
@@ -328,12 +1004,17 @@ impl EditorWindow {
                             ui.horizontal(|ui| {
                                 ui.selectable_value(&mut self.window_shown, SubWindowShown::BlockSelection, "Blocks");
                                 ui.selectable_value(&mut self.window_shown, SubWindowShown::ValueEditing, "Edit Values");
+                                ui.selectable_value(&mut self.window_shown, SubWindowShown::Export, "Export");
                             });
                             ui.separator();
 
                             match &self.window_shown {
                                 SubWindowShown::BlockSelection => {
                                     if let Some(item) = item {
+                                        if ui.button("Auto layout").clicked() {
+                                            item.blocks.auto_layout((20.0, 40.0));
+                                        }
+                                        ui.separator();
                                         ui.label("Click on a block to add it to the canvas");
                                         ui.separator();
                                         for (block_add_fn, block_name) in available_blocks {
@@ -341,8 +1022,8 @@ impl EditorWindow {
                                                 let mut b = block_add_fn();
                                                 let random_x = global_rng.gen_range(0.0..w);
                                                 let random_y = global_rng.gen_range(0.0..h);
-                                                b.x = random_x;
-                                                b.y = random_y;
+                                                // somewhere on screen, wherever the graph is panned to
+                                                (b.x, b.y) = item.blocks.camera.to_graph((random_x, random_y));
                                                 item.blocks.add_block(b);
                                             }
                                         }
@@ -351,31 +1032,132 @@ impl EditorWindow {
                                     }
                                 }
                                 SubWindowShown::ValueEditing => {
+                                    ui.horizontal(|ui| {
+                                        ui.label("background");
+                                        let c = &mut timeline.background;
+                                        let mut rgb = [c.r, c.g, c.b];
+                                        if ui.color_edit_button_rgb(&mut rgb).changed() {
+                                            c.r = rgb[0];
+                                            c.g = rgb[1];
+                                            c.b = rgb[2];
+                                        }
+                                    });
+                                    ui.horizontal(|ui| {
+                                        ui.label("total time (s)");
+                                        let mut total = timeline.total_time_secs;
+                                        let field = egui::DragValue::new(&mut total)
+                                            .speed(0.5)
+                                            .clamp_range(Timeline::MIN_TOTAL_TIME_SECS..=f32::MAX);
+                                        if ui.add(field).changed() {
+                                            timeline.set_total_time(total);
+                                        }
+                                    });
+                                    ui.horizontal(|ui| {
+                                        let mut fixed = timeline.canvas_size.is_some();
+                                        if ui.checkbox(&mut fixed, "fixed canvas size").changed() {
+                                            timeline.canvas_size = if fixed { Some((640.0, 480.0)) } else { None };
+                                        }
+                                        if let Some((w, h)) = &mut timeline.canvas_size {
+                                            ui.add(egui::DragValue::new(w).speed(1.0).clamp_range(1.0..=4096.0));
+                                            ui.label("x");
+                                            ui.add(egui::DragValue::new(h).speed(1.0).clamp_range(1.0..=4096.0));
+                                        }
+                                    });
+                                    ui.separator();
+                                    ui.label("lanes");
+                                    let lane_count = timeline.lanes.len();
+                                    for (i, lane) in timeline.lanes.iter_mut().enumerate() {
+                                        ui.horizontal(|ui| {
+                                            ui.checkbox(&mut lane.visible, "")
+                                                .on_hover_text("run the items in this lane");
+                                            ui.add(egui::TextEdit::singleline(&mut lane.name).desired_width(120.0));
+                                            if ui.add_enabled(lane_count > 1, egui::Button::new("Remove")).clicked() {
+                                                self.lane_removal_requested = Some(i);
+                                            }
+                                        });
+                                    }
+                                    if ui.button("Add lane").clicked() {
+                                        timeline.add_lane();
+                                    }
+                                    ui.separator();
                                     if let Some(item) = item {
-                                        let (_, _, width, _) = timeline.dimensions();
-                                        let width_per_second = width / timeline.total_time_secs;
-                                        self.draw_block_set(ui, width_per_second, item, seed);
+                                        if ui.button("Duplicate item").clicked() {
+                                            self.duplicate_requested = true;
+                                        }
+                                        self.draw_block_set(ui, &timeline.lanes, global_rng, item);
                                     }
                                 }
+                                SubWindowShown::Export => {
+                                    self.draw_export_settings(ui);
+                                }
                             }
                         });
                 });
         });
     }
-    pub fn draw_block_set(&self, ui: &mut Ui, width_per_second: f32, timeline_item: &mut TimelineItem, seed: &mut u64) {
-        let mut duration = timeline_item.length / width_per_second;
+    pub fn draw_export_settings(&mut self, ui: &mut Ui) {
+        let settings = &mut self.export_settings;
+        egui::Grid::new("export_grid")
+            .num_columns(2)
+            .spacing([40.0, 4.0])
+            .show(ui, |ui| {
+                ui.label("width");
+                ui.add(egui::DragValue::new(&mut settings.width).speed(1.0).clamp_range(1..=4096));
+                ui.end_row();
+                ui.label("height");
+                ui.add(egui::DragValue::new(&mut settings.height).speed(1.0).clamp_range(1..=4096));
+                ui.end_row();
+                ui.label("fps");
+                ui.add(egui::DragValue::new(&mut settings.fps).speed(1.0).clamp_range(1..=MAX_GIF_FPS));
+                ui.end_row();
+                ui.label("gif path");
+                ui.text_edit_singleline(&mut settings.path);
+                ui.end_row();
+                ui.label("svg path");
+                ui.text_edit_singleline(&mut settings.svg_path);
+                ui.end_row();
+            });
+        if let Some(progress) = self.export_progress {
+            ui.horizontal(|ui| {
+                let text = format!("frame {} of {}", progress.frame, progress.frame_count);
+                ui.add(egui::ProgressBar::new(progress.fraction()).text(text).desired_width(180.0));
+                if ui.button("Abort").clicked() {
+                    self.export_cancel_requested = true;
+                }
+            });
+        } else if ui.button("Export GIF").clicked() {
+            self.export_requested = Some(ExportKind::Gif);
+        }
+        if ui.button("Export SVG of current frame").clicked() {
+            self.export_requested = Some(ExportKind::Svg);
+        }
+    }
+    pub fn draw_block_set(&self, ui: &mut Ui, lanes: &[Lane], global_rng: &mut ChaCha8Rng, timeline_item: &mut TimelineItem) {
         egui::Grid::new("my_grid")
             .num_columns(2)
             .spacing([40.0, 4.0])
             .show(ui, |ui| {
-                ui.label("x");
-                ui.add(egui::DragValue::new(&mut timeline_item.x).speed(0.2));
+                ui.label("start (s)");
+                ui.add(
+                    egui::DragValue::new(&mut timeline_item.start_secs)
+                        .speed(0.05)
+                        .clamp_range(0.0..=f32::MAX)
+                );
                 ui.end_row();
-                ui.label("y");
-                ui.add(egui::DragValue::new(&mut timeline_item.y).speed(0.2));
+                ui.label("lane");
+                egui::ComboBox::from_id_source("item_lane").show_index(
+                    ui,
+                    &mut timeline_item.lane,
+                    lanes.len(),
+                    |i| lanes.get(i).map_or(String::new(), |l| l.name.clone())
+                );
                 ui.end_row();
                 ui.label("duration (s)");
-                ui.add(egui::DragValue::new(&mut duration).speed(0.2));
+                ui.add(
+                    egui::DragValue::new(&mut timeline_item.duration_secs)
+                        .speed(0.05)
+                        .clamp_range(ItemDrag::MIN_ITEM_DURATION_SECS..=f32::MAX)
+                );
                 ui.end_row();
                 ui.label("color");
                 let c = &mut timeline_item.color;
@@ -387,17 +1169,65 @@ impl EditorWindow {
                 }
                 ui.end_row();
                 ui.label("random seed");
-                ui.add(egui::DragValue::new(seed).speed(1.0));
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut timeline_item.seed).speed(1.0));
+                    if ui.small_button("-").clicked() {
+                        timeline_item.bump_seed(-1);
+                    }
+                    if ui.small_button("+").clicked() {
+                        timeline_item.bump_seed(1);
+                    }
+                    ui.checkbox(&mut timeline_item.seed_locked, "lock")
+                        .on_hover_text("R and re-seeding leave the seed alone");
+                    let reseed = egui::Button::new("Apply and re-seed");
+                    if ui.add_enabled(!timeline_item.seed_locked, reseed).clicked() {
+                        timeline_item.reseed(global_rng);
+                    }
+                });
+                ui.end_row();
+                ui.label("speed");
+                ui.add(
+                    egui::DragValue::new(&mut timeline_item.speed)
+                        .speed(0.05)
+                        .clamp_range(TimelineItem::MIN_SPEED..=f32::MAX)
+                );
+                ui.end_row();
+                ui.label("playback curve");
+                egui::ComboBox::from_id_source("playback_curve").show_index(
+                    ui,
+                    &mut timeline_item.playback_curve,
+                    EaseBlock::PRESETS.len(),
+                    |i| EaseBlock::PRESETS[i].to_owned()
+                );
+                ui.end_row();
+                ui.label("loops");
+                ui.add(egui::DragValue::new(&mut timeline_item.loop_count).speed(1.0).clamp_range(1..=u32::MAX));
+                ui.end_row();
+                ui.label("frozen");
+                ui.checkbox(&mut timeline_item.frozen, "")
+                    .on_hover_text("run once and keep drawing the result, until a block changes");
             });
         ui.separator();
-        timeline_item.length = duration * width_per_second;
 
         let block_set = &mut timeline_item.blocks;
+        let last_outputs = block_set.last_outputs.borrow();
         for (i, block) in block_set.blocks.iter_mut().enumerate() {
             let block = match block {
                 Some(b) => b,
                 None => continue,
             };
+            if block.note.is_some() {
+                // notes have no inputs. their text is only shown
+                // here while they're selected on the canvas
+                if block_set.selected.contains(&block.id) {
+                    ui.heading(&block.name);
+                    let changed = block.note.as_mut().is_some_and(|note| ui.text_edit_multiline(note).changed());
+                    if changed {
+                        block.calculate_width();
+                    }
+                }
+                continue;
+            }
             ui.heading(&block.name);
             egui::Grid::new(&format!("{i}_{}", block.name))
                 .num_columns(2)
@@ -405,17 +1235,45 @@ impl EditorWindow {
                 .show(ui, |ui| {
                     for input in block.inputs.iter_mut() {
                         ui.label(&input.name);
+                        // switching between a number and an expression
+                        // replaces the value, so it happens after the match
+                        let mut switch_to = None;
                         match &mut input.value {
                             InputValue::Number(x) => {
-                                ui.add(egui::DragValue::new(x).speed(1.0));
+                                ui.horizontal(|ui| {
+                                    if input.is_integer {
+                                        let mut whole = x.round() as i64;
+                                        ui.add(egui::DragValue::new(&mut whole).speed(1.0));
+                                        *x = whole as f64;
+                                    } else {
+                                        ui.add(egui::DragValue::new(x).speed(1.0));
+                                    }
+                                    if ui.small_button("f(x)").on_hover_text("Use an expression").clicked() {
+                                        switch_to = Some(InputValue::Expression(x.to_string()));
+                                    }
+                                });
+                            }
+                            InputValue::Expression(src) => {
+                                ui.horizontal(|ui| {
+                                    ui.text_edit_singleline(src)
+                                        .on_hover_text("Can use t, frame, screen_w, screen_h, pi, + - * / ^ and sin cos tan sqrt abs");
+                                    if ui.small_button("#").on_hover_text("Use a number").clicked() {
+                                        // keeps the value if it doesn't depend on the run
+                                        let x = expr::parse(src).and_then(|e| e.eval(&|_| None)).unwrap_or(0.0);
+                                        switch_to = Some(InputValue::Number(x));
+                                    }
+                                });
                             }
                             InputValue::Color(c) => {
-                                let mut rgb = [c.r, c.g, c.b];
-                                if ui.color_edit_button_rgb(&mut rgb).changed() {
-                                    c.r = rgb[0];
-                                    c.g = rgb[1];
-                                    c.b = rgb[2];
-                                }
+                                ui.horizontal(|ui| {
+                                    let mut rgb = [c.r, c.g, c.b];
+                                    if ui.color_edit_button_rgb(&mut rgb).changed() {
+                                        c.r = rgb[0];
+                                        c.g = rgb[1];
+                                        c.b = rgb[2];
+                                    }
+                                    hex_edit(ui, input.id, c);
+                                });
                             }
                             InputValue::Selection((selected, alternatives)) => {
                                 egui::ComboBox::from_id_source(format!("{}{}", block.name, i)).show_index(
@@ -431,6 +1289,80 @@ impl EditorWindow {
                                 ui.label(&format!("{}_y", input.name));
                                 ui.add(egui::DragValue::new(y).speed(1.0));
                             }
+                            // the panel is drawn after the run, so the click
+                            // is seen by the next run, then reset by the next draw
+                            InputValue::Boolean(b) if input.is_button => {
+                                *b = ui.button(&input.name).clicked();
+                            }
+                            InputValue::Boolean(b) => {
+                                ui.checkbox(b, "");
+                            }
+                            InputValue::Text(text) => {
+                                ui.text_edit_singleline(text);
+                            }
+                            InputValue::ListNumbers(list) if input.is_list_editable => {
+                                ui.vertical(|ui| {
+                                    let mut remove = None;
+                                    for (j, x) in list.iter_mut().enumerate() {
+                                        ui.horizontal(|ui| {
+                                            ui.add(egui::DragValue::new(x).speed(1.0));
+                                            if ui.button("remove").clicked() {
+                                                remove = Some(j);
+                                            }
+                                        });
+                                    }
+                                    if let Some(j) = remove {
+                                        list.remove(j);
+                                    }
+                                    if ui.button("add").clicked() {
+                                        list.push(0.0);
+                                    }
+                                });
+                            }
+                            InputValue::ListPoints(list) if input.is_list_editable => {
+                                ui.vertical(|ui| {
+                                    let mut remove = None;
+                                    for (j, (x, y)) in list.iter_mut().enumerate() {
+                                        ui.horizontal(|ui| {
+                                            ui.add(egui::DragValue::new(x).speed(1.0));
+                                            ui.add(egui::DragValue::new(y).speed(1.0));
+                                            if ui.button("remove").clicked() {
+                                                remove = Some(j);
+                                            }
+                                        });
+                                    }
+                                    if let Some(j) = remove {
+                                        list.remove(j);
+                                    }
+                                    if ui.button("add").clicked() {
+                                        list.push((0.0, 0.0));
+                                    }
+                                });
+                            }
+                            InputValue::ListColors(list) if input.is_list_editable => {
+                                ui.vertical(|ui| {
+                                    let mut remove = None;
+                                    for (j, c) in list.iter_mut().enumerate() {
+                                        ui.horizontal(|ui| {
+                                            let mut rgb = [c.r, c.g, c.b];
+                                            if ui.color_edit_button_rgb(&mut rgb).changed() {
+                                                c.r = rgb[0];
+                                                c.g = rgb[1];
+                                                c.b = rgb[2];
+                                            }
+                                            if ui.button("remove").clicked() {
+                                                remove = Some(j);
+                                            }
+                                        });
+                                    }
+                                    if let Some(j) = remove {
+                                        list.remove(j);
+                                    }
+                                    if ui.button("add").clicked() {
+                                        list.push(BLACK);
+                                    }
+                                });
+                            }
                             // the rest are all only editable dynamically, so
                             // no need to show them in the manual editor
                             _ => {
@@ -439,36 +1371,285 @@ impl EditorWindow {
                                 ui.add_enabled(false, val);
                             }
                         }
+                        if let Some(value) = switch_to {
+                            input.value = value;
+                        }
                         ui.end_row();
                     }
+                    for output in block.outputs.iter() {
+                        if let Some(preview) = last_outputs.get(&output.id).and_then(OutputPreview::of) {
+                            ui.label(&output.name);
+                            preview.show(ui);
+                            ui.end_row();
+                        }
+                    }
                 });
             ui.separator();
         }
     }
 }
 
-fn sigmoid(x: f32) -> f32 {
-    1.0 / (1.0 + std::f32::consts::E.powf(-x))
+/// a small picture of a block's last output in the value panel
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputPreview {
+    Swatches(Vec<Color>),
+    /// the points to draw, and how many the output had
+    Plot(Vec<(f32, f32)>, usize),
 }
 
-#[derive(Default, Debug)]
-pub struct ErrorQueue {
-    pub errors: Vec<ErrorMessage>,
-}
-impl ErrorQueue {
-    pub fn has_errors(&self) -> bool {
-        self.errors.len() > 0
+impl OutputPreview {
+    pub const PLOT_SIZE: (f32, f32) = (120.0, 80.0);
+    pub const SWATCH_SIZE: f32 = 16.0;
+    /// long lists of colors are cut off after this many
+    pub const MAX_SWATCHES: usize = 16;
+    /// long lists of points are thinned out to about this many
+    pub const MAX_PLOT_POINTS: usize = 1000;
+
+    /// only colors and points have a preview for now
+    pub fn of(output: &OutputResult) -> Option<Self> {
+        match output {
+            OutputResult::SingleValue(InputValue::Color(c)) => Some(Self::Swatches(vec![*c])),
+            OutputResult::SingleValue(InputValue::ListColors(colors)) => Some(Self::Swatches(colors.clone())),
+            OutputResult::SingleValue(InputValue::ListPoints(pts)) => Some(Self::plot(pts)),
+            OutputResult::Iteration(values) if !values.is_empty() => {
+                let colors: Option<Vec<Color>> = values.iter()
+                    .map(|v| if let InputValue::Color(c) = v { Some(*c) } else { None })
+                    .collect();
+                let pts: Option<Vec<(f32, f32)>> = values.iter()
+                    .map(|v| if let InputValue::Point(p) = v { Some(*p) } else { None })
+                    .collect();
+                match (colors, pts) {
+                    (Some(colors), _) => Some(Self::Swatches(colors)),
+                    (_, Some(pts)) => Some(Self::plot(&pts)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
     }
-    pub fn draw(&mut self) {
-        let mut remove = None;
-        let mut y = 0.0;
-        for (i, err) in self.errors.iter().enumerate() {
-            let measured = measure_text(&err.e, None, ERR_FONT_SIZE, 1.0);
-            draw_rectangle(0.0, y, measured.width + 30.0, measured.height, RED);
-            draw_text(&err.e, 0.0, y + measured.offset_y, ERR_FONT_SIZE_F32, WHITE);
-            draw_text("X", measured.width + 10.0, y + measured.offset_y, ERR_FONT_SIZE_F32, WHITE);
-            if is_mouse_button_pressed(MouseButton::Left) {
-                let (mx, my) = mouse_position();
+    /// every nth point, so a long list keeps its shape but stays cheap to draw
+    fn plot(pts: &[(f32, f32)]) -> Self {
+        let step = pts.len().div_ceil(Self::MAX_PLOT_POINTS).max(1);
+        Self::Plot(pts.iter().step_by(step).copied().collect(), pts.len())
+    }
+    /// `pts` scaled and centered to fit in a box `size` big, keeping
+    /// their aspect ratio. y is down, like on the canvas
+    pub fn fit(pts: &[(f32, f32)], size: (f32, f32)) -> Vec<(f32, f32)> {
+        let finite = || pts.iter().filter(|(x, y)| x.is_finite() && y.is_finite());
+        let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+        let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+        for (x, y) in finite() {
+            (min_x, min_y) = (min_x.min(*x), min_y.min(*y));
+            (max_x, max_y) = (max_x.max(*x), max_y.max(*y));
+        }
+        // a single point, or points along a line, would divide by 0
+        let (span_x, span_y) = ((max_x - min_x).max(f32::EPSILON), (max_y - min_y).max(f32::EPSILON));
+        let scale = (size.0 / span_x).min(size.1 / span_y);
+        let offset = ((size.0 - (max_x - min_x) * scale) / 2.0, (size.1 - (max_y - min_y) * scale) / 2.0);
+        finite().map(|(x, y)| (offset.0 + (x - min_x) * scale, offset.1 + (y - min_y) * scale)).collect()
+    }
+    pub fn show(&self, ui: &mut Ui) {
+        let to_egui = |c: &Color| {
+            let [r, g, b, a]: [u8; 4] = (*c).into();
+            egui::Color32::from_rgba_unmultiplied(r, g, b, a)
+        };
+        match self {
+            OutputPreview::Swatches(colors) => {
+                ui.horizontal_wrapped(|ui| {
+                    for c in colors.iter().take(Self::MAX_SWATCHES) {
+                        let size = egui::vec2(Self::SWATCH_SIZE, Self::SWATCH_SIZE);
+                        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+                        ui.painter().rect_filled(rect, 2.0, to_egui(c));
+                        response.on_hover_text(to_hex(*c));
+                    }
+                    if colors.len() > Self::MAX_SWATCHES {
+                        ui.label(format!("+{}", colors.len() - Self::MAX_SWATCHES));
+                    }
+                });
+            }
+            OutputPreview::Plot(pts, total) => {
+                ui.horizontal(|ui| {
+                    let size = egui::vec2(Self::PLOT_SIZE.0, Self::PLOT_SIZE.1);
+                    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+                    let painter = ui.painter_at(rect);
+                    painter.rect_filled(rect, 2.0, egui::Color32::from_gray(20));
+                    // a little margin, so points on the edge aren't cut in half
+                    let inner = rect.shrink(3.0);
+                    for (x, y) in Self::fit(pts, (inner.width(), inner.height())) {
+                        painter.circle_filled(inner.min + egui::vec2(x, y), 1.5, egui::Color32::WHITE);
+                    }
+                    response.on_hover_text(format!("{} points", total));
+                    if *total > pts.len() {
+                        ui.label(format!("+{} more", total - pts.len()));
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// a text field with the color's hex code. the color only changes once
+/// the text is a valid hex color. while the field is focused, the text
+/// being typed is kept in egui's memory, otherwise it would be replaced
+/// by the current color's hex every frame
+fn hex_edit(ui: &mut Ui, id_source: impl std::hash::Hash + std::fmt::Debug, color: &mut Color) {
+    let id = ui.make_persistent_id(id_source);
+    let mut text = ui.data().get_temp::<String>(id).unwrap_or_else(|| to_hex(*color));
+    let response = ui.add(egui::TextEdit::singleline(&mut text).desired_width(80.0));
+    if response.changed() {
+        if let Ok(parsed) = parse_hex(&text) {
+            *color = parsed;
+        }
+    }
+    if response.has_focus() {
+        ui.data().insert_temp(id, text);
+    } else {
+        ui.data().remove::<String>(id);
+    }
+}
+
+/// passing this on the command line lets F4 log the open item's
+/// outputs, see log_item_outputs
+pub const DEBUG_OUTPUTS_FLAG: &str = "--debug-outputs";
+pub const DEBUG_OUTPUTS_SEED: u64 = 0;
+
+/// runs the item's graph once, halfway through and with a fixed seed, and
+/// logs every block's outputs. nothing it draws ends up on the screen
+fn log_item_outputs(item: &TimelineItem, screen_space: (f32, f32), images: &mut ImageCache) {
+    let mut sink = RecordingSink::default();
+    let midi = MidiState::default();
+    let mut ctx = BlockRunContext {
+        screen_w: screen_space.0,
+        screen_h: screen_space.1,
+        percentage: 0.5,
+        rng: ChaCha8Rng::seed_from_u64(DEBUG_OUTPUTS_SEED),
+        draw: TransformedSink::new(&mut sink),
+        images,
+        frame: 0,
+        audio: AudioLevels::default(),
+        midi: &midi,
+    };
+    match item.blocks.run_collecting(&mut ctx) {
+        Ok(outputs) => {
+            let lines = item.blocks.describe_outputs(&outputs);
+            info!("{} outputs at 50% with seed {}:", lines.len(), DEBUG_OUTPUTS_SEED);
+            for line in lines {
+                info!("  {}", line);
+            }
+        }
+        Err(e) => info!("Running the item failed: {}", e),
+    }
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + std::f32::consts::E.powf(-x))
+}
+
+/// fps, frame time, graph size and how long running the timeline took.
+/// toggled with F3
+#[derive(Default, Debug)]
+pub struct PerfHud {
+    pub shown: bool,
+    /// seconds that the last Timeline::run took
+    pub last_run_secs: f64,
+}
+impl PerfHud {
+    /// measured with get_time instead of std::time::Instant,
+    /// because Instant panics on wasm
+    pub fn time_run<T>(&mut self, run: impl FnOnce() -> T) -> T {
+        let start = get_time();
+        let out = run();
+        self.last_run_secs = get_time() - start;
+        out
+    }
+    /// the open item's blocks and connections, or the totals of every
+    /// item if none is open
+    pub fn lines(&self, timeline_items: &[TimelineItem], open_item: Option<usize>) -> Vec<String> {
+        let items: Vec<&TimelineItem> = match open_item.and_then(|i| timeline_items.get(i)) {
+            Some(item) => vec![item],
+            None => timeline_items.iter().collect(),
+        };
+        let blocks: usize = items.iter().map(|i| i.blocks.blocks.iter().flatten().count()).sum();
+        let connections: usize = items.iter().map(|i| i.blocks.connections.len()).sum();
+        vec![
+            format!("fps: {}", get_fps()),
+            format!("frame: {:.2}ms", get_frame_time() * 1000.0),
+            format!("run: {:.2}ms", self.last_run_secs * 1000.0),
+            format!("blocks: {}", blocks),
+            format!("connections: {}", connections),
+        ]
+    }
+    pub fn draw(&mut self, timeline_items: &[TimelineItem], open_item: Option<usize>) {
+        if is_key_pressed(KeyCode::F3) {
+            self.shown = !self.shown;
+        }
+        if !self.shown {
+            return;
+        }
+        let lines = self.lines(timeline_items, open_item);
+        let x = screen_width() / 2.0;
+        let mut y = 0.0;
+        for line in lines {
+            let measured = measure_text(&line, None, ERR_FONT_SIZE, 1.0);
+            draw_rectangle(x, y, measured.width + 10.0, measured.height + 4.0, Color::new(0.0, 0.0, 0.0, 0.7));
+            draw_text(&line, x + 5.0, y + 2.0 + measured.offset_y, ERR_FONT_SIZE_F32, GREEN);
+            y += measured.height + 4.0;
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct ErrorQueue {
+    pub errors: Vec<ErrorMessage>,
+    /// informational messages, like a delete confirmation. unlike
+    /// errors they don't pause the preview
+    pub notices: Vec<ErrorMessage>,
+}
+impl ErrorQueue {
+    pub fn has_errors(&self) -> bool {
+        self.errors.len() > 0
+    }
+    pub fn clear(&mut self) {
+        self.errors.clear();
+        self.notices.clear();
+    }
+    /// shows the message below the errors, without counting as one
+    pub fn notify(&mut self, notice: ErrorMessage) {
+        if let Some(existing) = self.notices.iter_mut().find(|n| n.e == notice.e) {
+            existing.created_at = notice.created_at;
+            return;
+        }
+        self.notices.push(notice);
+    }
+    /// adds the message, unless an identical one is already
+    /// showing, in which case that one's repeat count goes up
+    pub fn push(&mut self, err: ErrorMessage) {
+        if let Some(existing) = self.errors.iter_mut().find(|e| e.e == err.e) {
+            existing.repeats += 1;
+            // restart the timer of transient messages that keep happening
+            existing.created_at = err.created_at;
+            return;
+        }
+        self.errors.push(err);
+    }
+    pub fn draw(&mut self) {
+        if is_key_pressed(KeyCode::Escape) {
+            self.clear();
+        }
+        let now = get_time();
+        self.errors.retain(|err| !err.is_expired(now));
+        self.notices.retain(|notice| !notice.is_expired(now));
+
+        let mut remove = None;
+        let mut y = 0.0;
+        for (i, err) in self.errors.iter().enumerate() {
+            let txt = err.text();
+            let measured = measure_text(&txt, None, ERR_FONT_SIZE, 1.0);
+            draw_rectangle(0.0, y, measured.width + 30.0, measured.height, RED);
+            draw_text(&txt, 0.0, y + measured.offset_y, ERR_FONT_SIZE_F32, WHITE);
+            draw_text("X", measured.width + 10.0, y + measured.offset_y, ERR_FONT_SIZE_F32, WHITE);
+            if is_mouse_button_pressed(MouseButton::Left) {
+                let (mx, my) = mouse_position();
                 if mx >= measured.width + 10.0 && mx < measured.width + 30.0
                     && my >= y && my < y + measured.height
                 {
@@ -477,6 +1658,26 @@ impl ErrorQueue {
             }
             y += measured.height;
         }
+        if self.errors.len() > 1 {
+            let txt = "Clear all (Esc)";
+            let measured = measure_text(txt, None, ERR_FONT_SIZE, 1.0);
+            draw_rectangle(0.0, y, measured.width + 10.0, measured.height, DARKGRAY);
+            draw_text(txt, 5.0, y + measured.offset_y, ERR_FONT_SIZE_F32, WHITE);
+            if is_mouse_button_pressed(MouseButton::Left) {
+                let (mx, my) = mouse_position();
+                if mx < measured.width + 10.0 && my >= y && my < y + measured.height {
+                    self.clear();
+                    return;
+                }
+            }
+            y += measured.height;
+        }
+        for notice in self.notices.iter() {
+            let measured = measure_text(&notice.e, None, ERR_FONT_SIZE, 1.0);
+            draw_rectangle(0.0, y, measured.width + 10.0, measured.height, Color::new(0.1, 0.3, 0.6, 1.0));
+            draw_text(&notice.e, 5.0, y + measured.offset_y, ERR_FONT_SIZE_F32, WHITE);
+            y += measured.height;
+        }
         if let Some(remove_index) = remove {
             self.errors.remove(remove_index);
         }
@@ -486,6 +1687,36 @@ impl ErrorQueue {
 #[derive(Debug, Default)]
 pub struct ErrorMessage {
     pub e: String,
+    /// value of get_time() when this message was made
+    pub created_at: f64,
+    /// if set, the message goes away on its own after this many seconds
+    pub ttl: Option<f64>,
+    /// how many more times this same message was pushed
+    pub repeats: usize,
+}
+
+impl ErrorMessage {
+    pub fn new(e: String) -> Self {
+        Self { e, ..Default::default() }
+    }
+    /// a message that gets dismissed automatically after `ttl` seconds
+    pub fn transient(e: String, ttl: f64) -> Self {
+        Self { e, created_at: get_time(), ttl: Some(ttl), repeats: 0 }
+    }
+    /// the text to show for this message
+    pub fn text(&self) -> String {
+        if self.repeats == 0 {
+            self.e.clone()
+        } else {
+            format!("{} (x{})", self.e, self.repeats + 1)
+        }
+    }
+    pub fn is_expired(&self, now: f64) -> bool {
+        match self.ttl {
+            Some(ttl) => now - self.created_at > ttl,
+            None => false,
+        }
+    }
 }
 
 pub struct CircleBlock;
@@ -494,14 +1725,14 @@ impl CircleBlock {
 
     pub fn run(
         inputs: &Vec<&InputValue>,
-        _ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
         let x = &inputs[0].as_f32();
         let y = &inputs[1].as_f32();
         let radius = &inputs[2].as_f32();
         let color = &inputs[3].as_color();
-        draw_circle(*x, *y, *radius, *color);
-        None
+        ctx.draw.circle(*x, *y, *radius, *color);
+        Ok(None)
     }
 
     pub fn to_draggable_block() -> DraggableBlock {
@@ -519,20 +1750,174 @@ impl CircleBlock {
     }
 }
 
+pub struct ArcBlock;
+impl ArcBlock {
+    const NAME: &'static str = "Arc";
+
+    /// samples `segments + 1` points along the arc. angles are in degrees,
+    /// going clockwise on screen from the positive x axis.
+    pub fn arc_points(cx: f32, cy: f32, radius: f32, start_angle: f32, end_angle: f32, segments: usize) -> Vec<(f32, f32)> {
+        let segments = segments.max(1);
+        let start = start_angle.to_radians();
+        let step = (end_angle.to_radians() - start) / segments as f32;
+        (0..=segments).map(|i| {
+            let angle = start + step * i as f32;
+            (cx + radius * angle.cos(), cy + radius * angle.sin())
+        }).collect()
+    }
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let cx = inputs[0].as_f32();
+        let cy = inputs[1].as_f32();
+        let radius = inputs[2].as_f32();
+        let start_angle = inputs[3].as_f32();
+        let end_angle = inputs[4].as_f32();
+        let segments = inputs[5].as_f32().max(1.0) as usize;
+        if segments > MAX_GENERATED_VALUES {
+            return Err(format!("segments {} is more than the max of {}", segments, MAX_GENERATED_VALUES));
+        }
+        let color = inputs[6].as_color();
+        let thickness = inputs[7].as_f32();
+        let pts = Self::arc_points(cx, cy, radius, start_angle, end_angle, segments);
+        for pair in pts.windows(2) {
+            ctx.draw.line(pair[0].0, pair[0].1, pair[1].0, pair[1].1, thickness, color);
+        }
+        Ok(None)
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new("cx", Inputs),
+            BlockConnectionNode::new("cy", Inputs),
+            BlockConnectionNode::new_with_input_type("radius", 50.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("start_deg", 0.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("end_deg", 90.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("segments", 32.0.into(), Inputs).integer(),
+            BlockConnectionNode::new_with_input_type("color", BLACK.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("thickness", 2.0.into(), Inputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.tall = true;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct SpiralBlock;
+impl SpiralBlock {
+    const NAME: &'static str = "Spiral";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let cx = inputs[0].as_f32();
+        let cy = inputs[1].as_f32();
+        let start_radius = inputs[2].as_f32();
+        let end_radius = inputs[3].as_f32();
+        let turns = inputs[4].as_f32();
+        // need at least 2 points to make a line
+        let points = inputs[5].as_f32().max(2.0) as usize;
+        if !turns.is_finite() || turns <= 0.0 {
+            return Err(format!("turns must be a finite number more than 0, found {}", turns));
+        }
+        if points > MAX_GENERATED_VALUES {
+            return Err(format!("points {} is more than the max of {}", points, MAX_GENERATED_VALUES));
+        }
+        // archimedean: the radius grows linearly with the angle
+        let total_angle = turns * std::f32::consts::TAU;
+        let pts = (0..points).map(|i| {
+            let t = i as f32 / (points - 1) as f32;
+            let angle = total_angle * t;
+            let radius = start_radius + (end_radius - start_radius) * t;
+            (cx + radius * angle.cos(), cy + radius * angle.sin())
+        }).collect();
+        Ok(Some(vec![OutputResult::SingleValue(InputValue::ListPoints(pts))]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new("cx", Inputs),
+            BlockConnectionNode::new("cy", Inputs),
+            BlockConnectionNode::new_with_input_type("start_radius", 0.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("end_radius", 100.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("turns", 3.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("points", 200.0.into(), Inputs).integer(),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.tall = true;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct DistributePointsOnCircleBlock;
+impl DistributePointsOnCircleBlock {
+    const NAME: &'static str = "PointsOnCircle";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let cx = inputs[0].as_f32();
+        let cy = inputs[1].as_f32();
+        let radius = inputs[2].as_f32();
+        let count = inputs[3].as_f32().max(1.0) as usize;
+        if count > MAX_GENERATED_VALUES {
+            return Err(format!("count {} is more than the max of {}", count, MAX_GENERATED_VALUES));
+        }
+        // the first point is at angle 0, ie: directly right of the center
+        let step = std::f32::consts::TAU / count as f32;
+        let pts = (0..count).map(|i| {
+            let angle = step * i as f32;
+            (cx + radius * angle.cos(), cy + radius * angle.sin())
+        }).collect();
+        Ok(Some(vec![OutputResult::SingleValue(InputValue::ListPoints(pts))]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new("cx", Inputs),
+            BlockConnectionNode::new("cy", Inputs),
+            BlockConnectionNode::new_with_input_type("radius", 100.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("count", 6.0.into(), Inputs).integer(),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
 pub struct SquareBlock;
 impl SquareBlock {
     const NAME: &'static str = "Square";
 
     pub fn run(
         inputs: &Vec<&InputValue>,
-        _ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
         let x = &inputs[0].as_f32();
         let y = &inputs[1].as_f32();
         let size = &inputs[2].as_f32();
         let color = &inputs[3].as_color();
-        draw_rectangle_lines(*x, *y, *size, *size, 2.0, *color);
-        None
+        let thickness = inputs[4].as_f32();
+        ctx.draw.rect_lines(*x, *y, *size, *size, thickness, *color);
+        Ok(None)
     }
 
     pub fn to_draggable_block() -> DraggableBlock {
@@ -542,6 +1927,7 @@ impl SquareBlock {
             BlockConnectionNode::new("y0", Inputs),
             BlockConnectionNode::new("size", Inputs),
             BlockConnectionNode::new_with_input_type("color", BLACK.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("thickness", 2.0.into(), Inputs),
         ];
         draggable_block2.name = format!("{} {}", draggable_block2.id, Self::NAME);
         draggable_block2.run_fn = Self::run;
@@ -550,6 +1936,75 @@ impl SquareBlock {
     }
 }
 
+pub struct TextBlock;
+impl TextBlock {
+    const NAME: &'static str = "Text";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let text = inputs[0].as_text();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        let x = inputs[1].as_f32();
+        let y = inputs[2].as_f32();
+        let size = inputs[3].as_f32().max(1.0);
+        let color = inputs[4].as_color();
+        ctx.draw.text(text, x, y, size, color);
+        Ok(None)
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("text", "text".into(), Inputs),
+            BlockConnectionNode::new("x", Inputs),
+            BlockConnectionNode::new("y", Inputs),
+            BlockConnectionNode::new_with_input_type("size", 32.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("color", BLACK.into(), Inputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct RectFillBlock;
+impl RectFillBlock {
+    const NAME: &'static str = "RectFill";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let x = inputs[0].as_f32();
+        let y = inputs[1].as_f32();
+        let w = inputs[2].as_f32();
+        let h = inputs[3].as_f32();
+        let color = inputs[4].as_color();
+        ctx.draw.rect(x, y, w, h, color);
+        Ok(None)
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new("x", Inputs),
+            BlockConnectionNode::new("y", Inputs),
+            BlockConnectionNode::new_with_input_type("width", 50.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("height", 50.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("color", BLACK.into(), Inputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
 
 pub struct FlattenPointsBlock;
 impl FlattenPointsBlock {
@@ -558,14 +2013,19 @@ impl FlattenPointsBlock {
     pub fn run(
         inputs: &Vec<&InputValue>,
         _ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
-        match inputs[0] {
-            InputValue::ListPoints(_) => {
-                // TODO: do something smarter than clone.
-                Some(vec![OutputResult::SingleValue(inputs[0].clone())])
-            }
-            _ => None,
-        }
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        // the flattening itself is done by the block context before
+        // this runs. run only borrows its inputs, so the one copy here
+        // is needed to hand the list off as this block's output.
+        let out = match inputs[0] {
+            InputValue::ListPoints(_) | InputValue::ListNumbers(_) => inputs[0].clone(),
+            // a single unconnected/non-iteration point becomes a list of 1
+            InputValue::Point(pt) => InputValue::ListPoints(vec![*pt]),
+            // always output something, otherwise blocks that depend
+            // on this output will fail to find a value.
+            _ => InputValue::ListPoints(vec![]),
+        };
+        Ok(Some(vec![OutputResult::SingleValue(out)]))
     }
 
     pub fn to_draggable_block() -> DraggableBlock {
@@ -592,26 +2052,30 @@ impl PointConnectionBlock {
 
     pub fn run(
         inputs: &Vec<&InputValue>,
-        _ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
         let pts = inputs[0].as_list_points();
+        let color = inputs[1].as_color();
+        let thickness = inputs[2].as_f32();
         // macroquad::logging::info!("{:?}", pts);
         let mut previous_pt: Option<&(f32, f32)> = None;
         for pt in pts.iter() {
             if let Some((prev_x, prev_y)) = previous_pt {
-                draw_line(*prev_x, *prev_y, pt.0, pt.1, 2.0, RED);
+                ctx.draw.line(*prev_x, *prev_y, pt.0, pt.1, thickness, color);
                 previous_pt = Some(pt);
             } else {
                 previous_pt = Some(pt);
             }
         }
-        None
+        Ok(None)
     }
 
     pub fn to_draggable_block() -> DraggableBlock {
         let mut draggable_block2 = DraggableBlock::default();
         draggable_block2.inputs = vec![
-            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Inputs),
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Inputs).list_editable(),
+            BlockConnectionNode::new_with_input_type("color", RED.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("thickness", 2.0.into(), Inputs),
         ];
         // draggable_block2.outputs = vec![
         //     BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Outputs),
@@ -623,74 +2087,300 @@ impl PointConnectionBlock {
     }
 }
 
-
-pub struct RandomPointBlock;
-impl RandomPointBlock {
-    const NAME: &'static str = "RandomPoint";
+/// everything drawn after this block runs is rotated by `angle` degrees
+/// around the pivot, then moved by (dx, dy). until a PopTransform runs,
+/// or the item finishes running.
+/// to make sure a block runs after this one, connect `pass` (a
+/// pass-through of the `pass` input) to it. blocks that don't depend on
+/// each other run in the order they were added
+pub struct PushTransformBlock;
+impl PushTransformBlock {
+    const NAME: &'static str = "PushTransform";
 
     pub fn run(
         inputs: &Vec<&InputValue>,
         ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
-        let pt0 = *&inputs[0].as_point();
-        let pt1 = *&inputs[1].as_point();
-        let pt2 = *&inputs[2].as_point();
-        let pt3 = *&inputs[3].as_point();
-        if ctx.rng.gen_bool(0.5) {
-            Some(vec![OutputResult::SingleValue(pt0.into()), OutputResult::SingleValue(pt2.into())])
-        } else {
-            Some(vec![OutputResult::SingleValue(pt1.into()), OutputResult::SingleValue(pt3.into())])
-        }
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let pass = inputs[0].clone();
+        let angle = inputs[1].as_f32().to_radians();
+        let pivot = vec2(inputs[2].as_f32(), inputs[3].as_f32());
+        let offset = vec2(inputs[4].as_f32(), inputs[5].as_f32());
+        let transform = Affine2::from_translation(pivot + offset)
+            * Affine2::from_angle(angle)
+            * Affine2::from_translation(-pivot);
+        ctx.draw.push_transform(transform);
+        Ok(Some(vec![OutputResult::SingleValue(pass)]))
     }
 
     pub fn to_draggable_block() -> DraggableBlock {
-        let mut draggable_block2 = DraggableBlock::default();
-        draggable_block2.inputs = vec![
-            BlockConnectionNode::new_with_input_type("pt0", InputValue::Point((0.0, 0.0)), Inputs),
-            BlockConnectionNode::new_with_input_type("pt1", InputValue::Point((0.0, 0.0)), Inputs),
-            BlockConnectionNode::new_with_input_type("pt2", InputValue::Point((0.0, 0.0)), Inputs),
-            BlockConnectionNode::new_with_input_type("pt3", InputValue::Point((0.0, 0.0)), Inputs),
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new("pass", Inputs),
+            BlockConnectionNode::new("angle", Inputs),
+            BlockConnectionNode::new("pivot_x", Inputs),
+            BlockConnectionNode::new("pivot_y", Inputs),
+            BlockConnectionNode::new("dx", Inputs),
+            BlockConnectionNode::new("dy", Inputs),
         ];
-        draggable_block2.outputs = vec![
-            BlockConnectionNode::new_with_input_type("ptA", InputValue::Point((0.0, 0.0)), Outputs),
-            BlockConnectionNode::new_with_input_type("ptB", InputValue::Point((0.0, 0.0)), Outputs),
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("pass", Outputs),
         ];
-        draggable_block2.name = format!("{} {}", draggable_block2.id, Self::NAME);
-        draggable_block2.run_fn = Self::run;
-        draggable_block2.calculate_width();
-        draggable_block2
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.tall = true;
+        draggable_block.calculate_width();
+        draggable_block
     }
 }
 
-
-pub struct LineBlock;
-impl LineBlock {
-    const NAME: &'static str = "Line";
+/// undoes the most recent PushTransform
+pub struct PopTransformBlock;
+impl PopTransformBlock {
+    const NAME: &'static str = "PopTransform";
 
     pub fn run(
         inputs: &Vec<&InputValue>,
-        _ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
-        let x1 = &inputs[0].as_f32();
-        let y1 = &inputs[1].as_f32();
-        let x2 = &inputs[2].as_f32();
-        let y2 = &inputs[3].as_f32();
-        let color = &inputs[4].as_color();
-        draw_line(*x1, *y1, *x2, *y2, 2.0, *color);
-        None
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        if !ctx.draw.pop_transform() {
+            return Err("there is no transform to pop. add a PushTransform that runs before this".into());
+        }
+        Ok(Some(vec![OutputResult::SingleValue(inputs[0].clone())]))
     }
 
     pub fn to_draggable_block() -> DraggableBlock {
-        let mut draggable_block2 = DraggableBlock::default();
-        draggable_block2.inputs = vec![
-            BlockConnectionNode::new("x1", Inputs),
-            BlockConnectionNode::new("y1", Inputs),
-            BlockConnectionNode::new("x2", Inputs),
-            BlockConnectionNode::new("y2", Inputs),
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new("pass", Inputs),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("pass", Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+/// everything drawn after this block runs uses the blend mode, until
+/// another BlendMode block runs or the item finishes running. like
+/// PushTransform, connect `pass` to the blocks that should run after it
+pub struct BlendModeBlock;
+impl BlendModeBlock {
+    const NAME: &'static str = "BlendMode";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        ctx.draw.set_blend_mode(BlendMode::from_name(inputs[1].as_str()));
+        Ok(Some(vec![OutputResult::SingleValue(inputs[0].clone())]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new("pass", Inputs),
+            BlockConnectionNode::new_with_input_type("mode", BlendMode::NAMES[..].into(), Inputs),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("pass", Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct ColorFromHexBlock;
+impl ColorFromHexBlock {
+    const NAME: &'static str = "ColorFromHex";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let color = parse_hex(inputs[0].as_text())?;
+        Ok(Some(vec![OutputResult::SingleValue(color.into())]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("hex", "#ff8800".into(), Inputs),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("color", BLACK.into(), Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct FillPolygonBlock;
+impl FillPolygonBlock {
+    const NAME: &'static str = "FillPolygon";
+    /// ear clipping is O(n^2) or worse, so bigger polygons would freeze the editor
+    const MAX_POINTS: usize = 5_000;
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let pts = inputs[0].as_list_points();
+        if pts.len() > Self::MAX_POINTS {
+            return Err(format!("{} points is more than the max of {}", pts.len(), Self::MAX_POINTS));
+        }
+        let color = inputs[1].as_color();
+        for [a, b, c] in geometry::triangulate(pts) {
+            ctx.draw.triangle(pts[a], pts[b], pts[c], color);
+        }
+        Ok(None)
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Inputs).list_editable(),
+            BlockConnectionNode::new_with_input_type("color", BLACK.into(), Inputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct NearestNeighborsBlock;
+impl NearestNeighborsBlock {
+    const NAME: &'static str = "NearestNeighbors";
+    /// finding neighbors is O(n^2), so keep it from freezing the editor
+    const MAX_POINTS: usize = 5_000;
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let pts = inputs[0].as_list_points();
+        let k = inputs[1].as_f32().max(0.0) as usize;
+        let color = inputs[2].as_color();
+        let thickness = inputs[3].as_f32();
+        if pts.len() > Self::MAX_POINTS {
+            return Err(format!("{} points is more than the max of {}", pts.len(), Self::MAX_POINTS));
+        }
+        for (a, b) in Self::neighbor_edges(pts, k) {
+            let (pa, pb) = (pts[a], pts[b]);
+            ctx.draw.line(pa.0, pa.1, pb.0, pb.1, thickness, color);
+        }
+        Ok(None)
+    }
+    /// the index pairs (lower index first, sorted) connecting every point
+    /// to its k closest other points. an edge that is in both points'
+    /// k closest only shows up once. k >= n connects everything
+    pub fn neighbor_edges(pts: &[(f32, f32)], k: usize) -> Vec<(usize, usize)> {
+        let mut edges = std::collections::BTreeSet::new();
+        for (i, (x, y)) in pts.iter().enumerate() {
+            let mut others: Vec<(f32, usize)> = pts.iter().enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(j, (ox, oy))| ((ox - x).powi(2) + (oy - y).powi(2), j))
+                .collect();
+            others.sort_by(|a, b| a.0.total_cmp(&b.0));
+            for (_, j) in others.into_iter().take(k) {
+                edges.insert((i.min(j), i.max(j)));
+            }
+        }
+        edges.into_iter().collect()
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Inputs).list_editable(),
+            BlockConnectionNode::new_with_input_type("k", 2.0.into(), Inputs).integer(),
+            BlockConnectionNode::new_with_input_type("color", BLACK.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("thickness", 1.0.into(), Inputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct RandomPointBlock;
+impl RandomPointBlock {
+    const NAME: &'static str = "RandomPoint";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let pt0 = *&inputs[0].as_point();
+        let pt1 = *&inputs[1].as_point();
+        let pt2 = *&inputs[2].as_point();
+        let pt3 = *&inputs[3].as_point();
+        if ctx.rng.gen_bool(0.5) {
+            Ok(Some(vec![OutputResult::SingleValue(pt0.into()), OutputResult::SingleValue(pt2.into())]))
+        } else {
+            Ok(Some(vec![OutputResult::SingleValue(pt1.into()), OutputResult::SingleValue(pt3.into())]))
+        }
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block2 = DraggableBlock::default();
+        draggable_block2.inputs = vec![
+            BlockConnectionNode::new_with_input_type("pt0", InputValue::Point((0.0, 0.0)), Inputs),
+            BlockConnectionNode::new_with_input_type("pt1", InputValue::Point((0.0, 0.0)), Inputs),
+            BlockConnectionNode::new_with_input_type("pt2", InputValue::Point((0.0, 0.0)), Inputs),
+            BlockConnectionNode::new_with_input_type("pt3", InputValue::Point((0.0, 0.0)), Inputs),
+        ];
+        draggable_block2.outputs = vec![
+            BlockConnectionNode::new_with_input_type("ptA", InputValue::Point((0.0, 0.0)), Outputs),
+            BlockConnectionNode::new_with_input_type("ptB", InputValue::Point((0.0, 0.0)), Outputs),
+        ];
+        draggable_block2.name = format!("{} {}", draggable_block2.id, Self::NAME);
+        draggable_block2.run_fn = Self::run;
+        draggable_block2.calculate_width();
+        draggable_block2
+    }
+}
+
+
+pub struct LineBlock;
+impl LineBlock {
+    const NAME: &'static str = "Line";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let x1 = &inputs[0].as_f32();
+        let y1 = &inputs[1].as_f32();
+        let x2 = &inputs[2].as_f32();
+        let y2 = &inputs[3].as_f32();
+        let color = &inputs[4].as_color();
+        let thickness = inputs[5].as_f32();
+        ctx.draw.line(*x1, *y1, *x2, *y2, thickness, *color);
+        Ok(None)
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block2 = DraggableBlock::default();
+        draggable_block2.inputs = vec![
+            BlockConnectionNode::new("x1", Inputs),
+            BlockConnectionNode::new("y1", Inputs),
+            BlockConnectionNode::new("x2", Inputs),
+            BlockConnectionNode::new("y2", Inputs),
             BlockConnectionNode::new_with_input_type("color", BLACK.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("thickness", 2.0.into(), Inputs),
         ];
         draggable_block2.name = format!("{} {}", draggable_block2.id, Self::NAME);
         draggable_block2.run_fn = Self::run;
+        draggable_block2.tall = true;
         draggable_block2.calculate_width();
         draggable_block2
     }
@@ -704,9 +2394,9 @@ impl PtExtractBlock {
     pub fn run(
         inputs: &Vec<&InputValue>,
         _ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
+    ) -> Result<Option<Vec<OutputResult>>, String> {
         let (x, y) = *&inputs[0].as_point();
-        Some(vec![OutputResult::SingleValue(x.into()), OutputResult::SingleValue(y.into())])
+        Ok(Some(vec![OutputResult::SingleValue(x.into()), OutputResult::SingleValue(y.into())]))
     }
 
     pub fn to_draggable_block() -> DraggableBlock {
@@ -733,10 +2423,10 @@ impl PtCombineBlock {
     pub fn run(
         inputs: &Vec<&InputValue>,
         _ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
+    ) -> Result<Option<Vec<OutputResult>>, String> {
         let x = *&inputs[0].as_f32();
         let y = *&inputs[1].as_f32();
-        Some(vec![OutputResult::SingleValue((x, y).into())])
+        Ok(Some(vec![OutputResult::SingleValue((x, y).into())]))
     }
 
     pub fn to_draggable_block() -> DraggableBlock {
@@ -763,14 +2453,14 @@ impl HslColorBlock {
     pub fn run(
         inputs: &Vec<&InputValue>,
         _ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
+    ) -> Result<Option<Vec<OutputResult>>, String> {
         let h = &inputs[0].as_f32();
         let s = &inputs[1].as_f32();
         let l = &inputs[1].as_f32();
         let hsl = Hsl::new(*h, *s, *l);
         let (r, g, b) = hsl.hsl_to_rgb();
         let c = Color::from_rgba(r, g, b, 255);
-        Some(vec![OutputResult::SingleValue(c.into())])
+        Ok(Some(vec![OutputResult::SingleValue(c.into())]))
     }
     pub fn to_draggable_block() -> DraggableBlock {
         let mut draggable_block2 = DraggableBlock::default();
@@ -789,6 +2479,122 @@ impl HslColorBlock {
     }
 }
 
+pub struct GradientFillBlock;
+impl GradientFillBlock {
+    const NAME: &'static str = "GradientFill";
+    /// how many pixels wide each interpolated band is
+    const PIXELS_PER_BAND: f32 = 2.0;
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let from = inputs[0].as_color();
+        let to = inputs[1].as_color();
+        let vertical = inputs[2].as_str() != "horizontal";
+        let (s_width, s_height) = ctx.get_screen_space();
+        let dimension = if vertical { s_height } else { s_width };
+        let num_bands = (dimension / Self::PIXELS_PER_BAND).ceil().max(1.0);
+        let band_size = dimension / num_bands;
+        let num_bands = num_bands as u32;
+        for i in 0..num_bands {
+            let t = if num_bands > 1 { i as f32 / (num_bands - 1) as f32 } else { 0.0 };
+            let c = lerp_color(from, to, t);
+            let offset = i as f32 * band_size;
+            if vertical {
+                ctx.draw.rect(0.0, offset, s_width, band_size, c);
+            } else {
+                ctx.draw.rect(offset, 0.0, band_size, s_height, c);
+            }
+        }
+        Ok(None)
+    }
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block2 = DraggableBlock::default();
+        draggable_block2.inputs = vec![
+            BlockConnectionNode::new_with_input_type("from", WHITE.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("to", BLACK.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("direction",
+                [
+                    "vertical",
+                    "horizontal",
+                ][..].into(),
+                Inputs
+            ),
+        ];
+        draggable_block2.name = format!("{} {}", draggable_block2.id, Self::NAME);
+        draggable_block2.run_fn = Self::run;
+        draggable_block2.calculate_width();
+        draggable_block2
+    }
+}
+
+pub struct HsvColorBlock;
+impl HsvColorBlock {
+    const NAME: &'static str = "HsvColor";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let h = &inputs[0].as_f32();
+        let s = &inputs[1].as_f32();
+        let v = &inputs[2].as_f32();
+        let hsv = Hsv::new(*h, *s, *v);
+        let (r, g, b) = hsv.hsv_to_rgb();
+        let c = Color::from_rgba(r, g, b, 255);
+        Ok(Some(vec![OutputResult::SingleValue(c.into())]))
+    }
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block2 = DraggableBlock::default();
+        draggable_block2.inputs = vec![
+            BlockConnectionNode::new_with_input_type("hue", 0.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("saturation", 1.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("value", 1.0.into(), Inputs),
+        ];
+        draggable_block2.outputs = vec![
+            BlockConnectionNode::new_with_input_type("color", WHITE.into(), Outputs)
+        ];
+        draggable_block2.name = format!("{} {}", draggable_block2.id, Self::NAME);
+        draggable_block2.run_fn = Self::run;
+        draggable_block2.calculate_width();
+        draggable_block2
+    }
+}
+
+pub struct HueRotateBlock;
+impl HueRotateBlock {
+    const NAME: &'static str = "HueRotate";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let c = inputs[0].as_color();
+        let degrees = inputs[1].as_f64();
+        let [r, g, b, a] = c.into();
+        let mut hsl = Hsl::from_rgb(r, g, b);
+        hsl.h = (hsl.h + degrees).rem_euclid(360.0);
+        let (r, g, b) = hsl.hsl_to_rgb();
+        let c = Color::from_rgba(r, g, b, a);
+        Ok(Some(vec![OutputResult::SingleValue(c.into())]))
+    }
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block2 = DraggableBlock::default();
+        draggable_block2.inputs = vec![
+            BlockConnectionNode::new_with_input_type("color", RED.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("degrees", 0.0.into(), Inputs),
+        ];
+        draggable_block2.outputs = vec![
+            BlockConnectionNode::new_with_input_type("color", WHITE.into(), Outputs)
+        ];
+        draggable_block2.name = format!("{} {}", draggable_block2.id, Self::NAME);
+        draggable_block2.run_fn = Self::run;
+        draggable_block2.calculate_width();
+        draggable_block2
+    }
+}
+
 pub struct RandOffSetBlock;
 impl RandOffSetBlock {
     const NAME: &'static str = "RandomOffset";
@@ -796,7 +2602,7 @@ impl RandOffSetBlock {
     pub fn run(
         inputs: &Vec<&InputValue>,
         ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
+    ) -> Result<Option<Vec<OutputResult>>, String> {
         let s = &inputs[0].as_f32();
         let low = *&inputs[1].as_f32();
         let high = *&inputs[2].as_f32();
@@ -806,8 +2612,19 @@ impl RandOffSetBlock {
         } else {
             ctx.rng.gen_range(low..high)
         };
-        let c = *s + val;
-        Some(vec![OutputResult::SingleValue(c.into())])
+        let mut c = *s + val;
+        if inputs[3].as_bool() {
+            c = Self::wrap(c, inputs[4].as_f32(), inputs[5].as_f32());
+        }
+        Ok(Some(vec![OutputResult::SingleValue(c.into())]))
+    }
+    /// wraps v into min..max, eg: 370 in 0..360 is 10.
+    /// an empty range leaves v as it is
+    pub fn wrap(v: f32, min: f32, max: f32) -> f32 {
+        if max.is_nan() || min.is_nan() || max <= min {
+            return v;
+        }
+        min + (v - min).rem_euclid(max - min)
     }
     pub fn to_draggable_block() -> DraggableBlock {
         let mut draggable_block2 = DraggableBlock::default();
@@ -815,60 +2632,238 @@ impl RandOffSetBlock {
             BlockConnectionNode::new("source", Inputs),
             BlockConnectionNode::new_with_input_type("low", (-10.0).into(), Inputs),
             BlockConnectionNode::new_with_input_type("high", 10.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("wrap", false.into(), Inputs),
+            // a full turn of hue by default
+            BlockConnectionNode::new_with_input_type("wrap_min", 0.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("wrap_max", 360.0.into(), Inputs),
         ];
         draggable_block2.outputs = vec![
             BlockConnectionNode::new("value", Outputs)
         ];
         draggable_block2.name = format!("{} {}", draggable_block2.id, Self::NAME);
         draggable_block2.run_fn = Self::run;
+        draggable_block2.tall = true;
         draggable_block2.calculate_width();
         draggable_block2
     }
 }
 
-pub struct IterationBlock;
-impl IterationBlock {
-    const NAME: &'static str = "Iterate";
+pub struct JitterPointsBlock;
+impl JitterPointsBlock {
+    const NAME: &'static str = "JitterPoints";
 
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let pts = inputs[0].as_list_points();
+        let max_offset = inputs[1].as_f32().abs();
+        // gen_range panics on a NaN or infinite range
+        if !max_offset.is_finite() {
+            return Err(format!("max_offset must be a finite number, found {}", max_offset));
+        }
+        let out = if max_offset == 0.0 {
+            pts.clone()
+        } else {
+            pts.iter().map(|(x, y)| (
+                x + ctx.rng.gen_range(-max_offset..=max_offset),
+                y + ctx.rng.gen_range(-max_offset..=max_offset),
+            )).collect()
+        };
+        Ok(Some(vec![OutputResult::SingleValue(InputValue::ListPoints(out))]))
+    }
     pub fn to_draggable_block() -> DraggableBlock {
         let mut draggable_block = DraggableBlock::default();
+        // like PointConnection, this takes an already flattened list.
+        // put a FlattenPoints in front of it to jitter an iteration
         draggable_block.inputs = vec![
-            BlockConnectionNode::new_with_input_type("pass", 0.0.into(), Inputs),    
-            BlockConnectionNode::new_with_input_type("start", 0.0.into(), Inputs),
-            BlockConnectionNode::new_with_input_type("end", 100.0.into(), Inputs),
-            BlockConnectionNode::new_with_input_type("by", 10.0.into(), Inputs)
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Inputs).list_editable(),
+            BlockConnectionNode::new_with_input_type("max_offset", 5.0.into(), Inputs),
         ];
         draggable_block.outputs = vec![
-            BlockConnectionNode::new("pass", Outputs),    
-            BlockConnectionNode::new("value", Outputs),
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Outputs),
         ];
         draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
         draggable_block.run_fn = Self::run;
         draggable_block.calculate_width();
         draggable_block
     }
+}
+
+pub struct ScatterPointsBlock;
+impl ScatterPointsBlock {
+    const NAME: &'static str = "ScatterPoints";
+
+    /// if w or h is 0 (the default) the points are spread over the whole screen
     pub fn run(
         inputs: &Vec<&InputValue>,
         ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
-        let pass = inputs[0].as_f32();
-        let start = *&inputs[1].as_f32();
-        let end = *&inputs[2].as_f32();
-        let by = *&inputs[3].as_f32();
-        let mut out1 = vec![];
-        let mut out2 = vec![];
-        let mut value = start;
-        while value <= end {
-            out1.push(InputValue::Number(pass as _));
-            out2.push(InputValue::Number(value as _));
-            value += by;
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let count = inputs[0].as_f32().max(0.0) as usize;
+        if count > MAX_GENERATED_VALUES {
+            return Err(format!("count {} is more than the max of {}", count, MAX_GENERATED_VALUES));
         }
-    
-        Some(vec![OutputResult::Iteration(out1), OutputResult::Iteration(out2)])
+        let (mut x, mut y) = (inputs[1].as_f32(), inputs[2].as_f32());
+        let (mut w, mut h) = (inputs[3].as_f32(), inputs[4].as_f32());
+        // gen_range panics on NaN or infinite bounds, and w <= 0.0 is false for NaN
+        if [x, y, w, h].iter().any(|v| !v.is_finite()) {
+            return Err(format!("x, y, w and h must be finite numbers, found {}, {}, {} and {}", x, y, w, h));
+        }
+        if w <= 0.0 || h <= 0.0 {
+            (x, y) = (0.0, 0.0);
+            (w, h) = ctx.get_screen_space();
+        }
+        let pts = (0..count).map(|_| (
+            x + ctx.rng.gen_range(0.0..=w),
+            y + ctx.rng.gen_range(0.0..=h),
+        )).collect();
+        Ok(Some(vec![OutputResult::SingleValue(InputValue::ListPoints(pts))]))
     }
-}
-
-
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("count", 100.0.into(), Inputs).integer(),
+            BlockConnectionNode::new("x", Inputs),
+            BlockConnectionNode::new("y", Inputs),
+            BlockConnectionNode::new("w", Inputs),
+            BlockConnectionNode::new("h", Inputs),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct IterationBlock;
+impl IterationBlock {
+    const NAME: &'static str = "Iterate";
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("pass", 0.0.into(), Inputs),    
+            BlockConnectionNode::new_with_input_type("start", 0.0.into(), Inputs).integer(),
+            BlockConnectionNode::new_with_input_type("end", 100.0.into(), Inputs).integer(),
+            BlockConnectionNode::new_with_input_type("by", 10.0.into(), Inputs)
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("pass", Outputs),    
+            BlockConnectionNode::new("value", Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let pass = inputs[0].as_f32();
+        let start = *&inputs[1].as_f32();
+        let end = *&inputs[2].as_f32();
+        let by = *&inputs[3].as_f32();
+        // a step of 0 (or backwards) would never reach the end
+        if by.is_nan() || by <= 0.0 {
+            return Err(format!("by must be more than 0, found {}", by));
+        }
+        let count = if start <= end { ((end - start) as f64 / by as f64).floor() + 1.0 } else { 0.0 };
+        if count > MAX_GENERATED_VALUES as f64 {
+            return Err(format!("{} to {} by {} is more than the max of {} values", start, end, by, MAX_GENERATED_VALUES));
+        }
+        let mut out1 = vec![];
+        let mut out2 = vec![];
+        // counted, instead of adding up `by`, which stops changing
+        // the value once it's big enough
+        for i in 0..count as usize {
+            let value = start + i as f32 * by;
+            out1.push(InputValue::Number(pass as _));
+            out2.push(InputValue::Number(value as _));
+        }
+    
+        Ok(Some(vec![OutputResult::Iteration(out1), OutputResult::Iteration(out2)]))
+    }
+}
+
+
+pub struct RepeatBlock;
+impl RepeatBlock {
+    const NAME: &'static str = "Repeat";
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new("value", Inputs),
+            BlockConnectionNode::new_with_input_type("count", 10.0.into(), Inputs).integer(),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("value", Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let value = inputs[0].as_f64();
+        let count = inputs[1].as_f32().max(0.0) as usize;
+        if count > MAX_GENERATED_VALUES {
+            return Err(format!("count {} is more than the max of {}", count, MAX_GENERATED_VALUES));
+        }
+        let out = vec![InputValue::Number(value); count];
+        Ok(Some(vec![OutputResult::Iteration(out)]))
+    }
+}
+
+pub struct RangeBlock;
+impl RangeBlock {
+    const NAME: &'static str = "Range";
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("start", 0.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("end", 100.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("count", 10.0.into(), Inputs).integer(),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("value", Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+    /// outputs exactly `count` evenly spaced values, including both start and end
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let start = inputs[0].as_f64();
+        let end = inputs[1].as_f64();
+        let count = inputs[2].as_f32().max(0.0) as usize;
+        if count > MAX_GENERATED_VALUES {
+            return Err(format!("count {} is more than the max of {}", count, MAX_GENERATED_VALUES));
+        }
+        let out = match count {
+            0 => vec![],
+            1 => vec![InputValue::Number(start)],
+            _ => {
+                let step = (end - start) / (count - 1) as f64;
+                (0..count).map(|i| InputValue::Number(start + step * i as f64)).collect()
+            }
+        };
+        Ok(Some(vec![OutputResult::Iteration(out)]))
+    }
+}
+
 pub struct GridBlock;
 impl GridBlock {
     const NAME: &'static str = "Grid";
@@ -876,12 +2871,13 @@ impl GridBlock {
     pub fn to_draggable_block() -> DraggableBlock {
         let mut draggable_block = DraggableBlock::default();
         draggable_block.inputs = vec![
-            BlockConnectionNode::new_with_input_type("rows", 10.0.into(), Inputs),
-            BlockConnectionNode::new_with_input_type("cols", 10.0.into(), Inputs)
+            BlockConnectionNode::new_with_input_type("rows", 10.0.into(), Inputs).integer(),
+            BlockConnectionNode::new_with_input_type("cols", 10.0.into(), Inputs).integer(),
         ];
         draggable_block.outputs = vec![
             BlockConnectionNode::new("xi", Outputs),
             BlockConnectionNode::new("yi", Outputs),
+            BlockConnectionNode::new("t", Outputs),
         ];
         draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
         draggable_block.run_fn = Self::run;
@@ -891,20 +2887,28 @@ impl GridBlock {
     pub fn run(
         inputs: &Vec<&InputValue>,
         ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
+    ) -> Result<Option<Vec<OutputResult>>, String> {
         let rows = &inputs[0].as_f32();
         let cols = &inputs[1].as_f32();
         let (s_width, s_height) = ctx.get_screen_space();
         let height_per_row = s_height / rows;
         let width_per_col = s_width / cols;
+        // a negative amount of rows/cols doesnt make sense,
+        // so treat it as an empty grid.
+        if *rows < 0.0 || *cols < 0.0 {
+            return Ok(Some(vec![OutputResult::Iteration(vec![]), OutputResult::Iteration(vec![]), OutputResult::Iteration(vec![])]));
+        }
         let rows = *rows as u32;
         let cols = *cols as u32;
+        let count = rows as usize * cols as usize;
         let mut y = height_per_row / 2.0;
         let mut out1 = vec![];
         let mut out2 = vec![];
+        let mut out3 = vec![];
         for _ in 0..rows {
             let mut x = width_per_col / 2.0;
             for _ in 0..cols {
+                out3.push(InputValue::Number(Self::cell_t(out1.len(), count)));
                 out1.push(InputValue::Number(x as _));
                 out2.push(InputValue::Number(y as _));
                 x += width_per_col;
@@ -912,7 +2916,15 @@ impl GridBlock {
             y += height_per_row;
         }
     
-        Some(vec![OutputResult::Iteration(out1), OutputResult::Iteration(out2)])
+        Ok(Some(vec![OutputResult::Iteration(out1), OutputResult::Iteration(out2), OutputResult::Iteration(out3)]))
+    }
+    /// how far through the grid the cell at `index` is, from 0 for the first
+    /// cell to 1 for the last, going along the rows. for feeding colors
+    pub fn cell_t(index: usize, count: usize) -> f64 {
+        if count <= 1 {
+            return 0.0;
+        }
+        index as f64 / (count - 1) as f64
     }
 }
 
@@ -923,52 +2935,78 @@ impl SquareGridBlock {
     pub fn to_draggable_block() -> DraggableBlock {
         let mut draggable_block = DraggableBlock::default();
         draggable_block.inputs = vec![
-            BlockConnectionNode::new_with_input_type("dimension", 10.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("rows", 10.0.into(), Inputs).integer(),
+            BlockConnectionNode::new_with_input_type("cols", 10.0.into(), Inputs).integer(),
+            BlockConnectionNode::new("margin", Inputs),
         ];
         draggable_block.outputs = vec![
             BlockConnectionNode::new_with_input_type("pt0", InputValue::Point((0.0, 0.0)), Outputs),
             BlockConnectionNode::new_with_input_type("pt1", InputValue::Point((0.0, 0.0)), Outputs),
             BlockConnectionNode::new_with_input_type("pt2", InputValue::Point((0.0, 0.0)), Outputs),
             BlockConnectionNode::new_with_input_type("pt3", InputValue::Point((0.0, 0.0)), Outputs),
+            BlockConnectionNode::new("t", Outputs),
         ];
         draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
         draggable_block.run_fn = Self::run;
         draggable_block.calculate_width();
         draggable_block
     }
+    /// the tiles stay square, and are as big as possible while fitting
+    /// rows x cols of them in the screen minus the margin on every side.
+    /// with the default 10x10 and no margin, this is a grid over the
+    /// biggest square that fits the screen
     pub fn run(
         inputs: &Vec<&InputValue>,
         ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
-        let dimension = &inputs[0].as_f32();
-        let (s_width, s_height) = ctx.get_screen_space();
-        // we fix it into a square, so use the min size
-        let screen_size = s_width.min(s_height);
-        let size_per_tile = screen_size / dimension;
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let rows = inputs[0].as_f32();
+        let cols = inputs[1].as_f32();
+        let margin = inputs[2].as_f32();
         let mut out1 = vec![];
         let mut out2 = vec![];
         let mut out3 = vec![];
         let mut out4 = vec![];
-        let dim_u32 = *dimension as u32;
-        let mut y = 0.0;
-        for _ in 0..dim_u32 {
-            let mut x = 0.0;
-            for _ in 0..dim_u32 {
-                out1.push(InputValue::Point((x as _, y as _)));
-                out2.push(InputValue::Point((x + size_per_tile, y as _)));
+        let mut out5 = vec![];
+        // like GridBlock, less than one row/col is an empty grid
+        if rows < 1.0 || cols < 1.0 {
+            return Ok(Some(vec![
+                OutputResult::Iteration(out1),
+                OutputResult::Iteration(out2),
+                OutputResult::Iteration(out3),
+                OutputResult::Iteration(out4),
+                OutputResult::Iteration(out5),
+            ]));
+        }
+        let rows = rows as u32;
+        let cols = cols as u32;
+        if rows as usize * cols as usize > MAX_GENERATED_VALUES {
+            return Err(format!("{}x{} tiles is more than the max of {}", rows, cols, MAX_GENERATED_VALUES));
+        }
+        let (s_width, s_height) = ctx.get_screen_space();
+        let inner_width = (s_width - 2.0 * margin).max(0.0);
+        let inner_height = (s_height - 2.0 * margin).max(0.0);
+        let size_per_tile = (inner_width / cols as f32).min(inner_height / rows as f32);
+        let mut y = margin;
+        for _ in 0..rows {
+            let mut x = margin;
+            for _ in 0..cols {
+                out5.push(InputValue::Number(GridBlock::cell_t(out1.len(), rows as usize * cols as usize)));
+                out1.push(InputValue::Point((x, y)));
+                out2.push(InputValue::Point((x + size_per_tile, y)));
                 out3.push(InputValue::Point((x + size_per_tile, y + size_per_tile)));
                 out4.push(InputValue::Point((x, y + size_per_tile)));
                 x += size_per_tile;
             }
             y += size_per_tile;
         }
-    
-        Some(vec![
+
+        Ok(Some(vec![
             OutputResult::Iteration(out1),
             OutputResult::Iteration(out2),
             OutputResult::Iteration(out3),
             OutputResult::Iteration(out4),
-        ])
+            OutputResult::Iteration(out5),
+        ]))
     }
 }
 
@@ -988,6 +3026,15 @@ impl ClockBlock {
             ),
             draw::BlockConnectionNode::new_with_input_type("sigmoid sensitivity", 6.0.into(), draw::ConnectionType::Inputs),
             draw::BlockConnectionNode::new_with_input_type("scale_by", 10.0.into(), draw::ConnectionType::Inputs),
+            draw::BlockConnectionNode::new_with_input_type("mode",
+                [
+                    "once",
+                    "loop",
+                    "ping-pong",
+                ][..].into(),
+                draw::ConnectionType::Inputs
+            ),
+            draw::BlockConnectionNode::new_with_input_type("cycles", 1.0.into(), draw::ConnectionType::Inputs),
         ];
         draggable_block3.outputs = vec![
             draw::BlockConnectionNode::new("time", draw::ConnectionType::Outputs),
@@ -1000,8 +3047,12 @@ impl ClockBlock {
     pub fn run(
         inputs: &Vec<&InputValue>,
         ctx: &mut BlockRunContext,
-    ) -> Option<Vec<OutputResult>> {
-        let mut time = ctx.percentage;
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let cycles = inputs[4].as_f32();
+        if !cycles.is_finite() || cycles <= 0.0 {
+            return Err(format!("cycles must be a finite number more than 0, found {}", cycles));
+        }
+        let mut time = Self::apply_mode(inputs[3].as_str(), ctx.percentage, cycles);
         // default is linear, so use time as is
         if inputs[0].as_str() == "sigmoid" {
             let sigmoid_sensitivity = inputs[1].as_f32();
@@ -1012,7 +3063,265 @@ impl ClockBlock {
         // ie: to use time for stuff other than [0, 1]
         time *= inputs[2].as_f32();
     
-        Some(vec![OutputResult::SingleValue(time.into())])
+        Ok(Some(vec![OutputResult::SingleValue(time.into())]))
+    }
+    /// maps the item's percentage according to the clock mode:
+    /// once goes 0..1 over the whole item, loop goes 0..1 `cycles` times,
+    /// and ping-pong goes 0..1..0 `cycles` times
+    pub fn apply_mode(mode: &str, percentage: f32, cycles: f32) -> f32 {
+        let cycle = (percentage.clamp(0.0, 1.0) * cycles).fract();
+        match mode {
+            "loop" => cycle,
+            "ping-pong" => 1.0 - (2.0 * cycle - 1.0).abs(),
+            _ => percentage.clamp(0.0, 1.0),
+        }
+    }
+}
+
+pub struct EaseBlock;
+impl EaseBlock {
+    const NAME: &'static str = "Ease";
+    const PRESETS: [&'static str; 6] = [
+        "linear",
+        "ease-in-quad",
+        "ease-out-quad",
+        "ease-in-out-cubic",
+        "sine",
+        "bounce",
+    ];
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new("t", Inputs),
+            BlockConnectionNode::new_with_input_type("curve", Self::PRESETS[..].into(), Inputs),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("value", Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let t = Self::ease(inputs[1].as_str(), inputs[0].as_f32());
+        Ok(Some(vec![OutputResult::SingleValue(t.into())]))
+    }
+    /// t is clamped to 0..1. every curve starts at 0 and ends at 1
+    pub fn ease(curve: &str, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match curve {
+            "ease-in-quad" => t * t,
+            "ease-out-quad" => 1.0 - (1.0 - t) * (1.0 - t),
+            "ease-in-out-cubic" => if t < 0.5 {
+                4.0 * t * t * t
+            } else {
+                1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+            },
+            "sine" => -((std::f32::consts::PI * t).cos() - 1.0) / 2.0,
+            "bounce" => {
+                let n1 = 7.5625;
+                let d1 = 2.75;
+                if t < 1.0 / d1 {
+                    n1 * t * t
+                } else if t < 2.0 / d1 {
+                    let t = t - 1.5 / d1;
+                    n1 * t * t + 0.75
+                } else if t < 2.5 / d1 {
+                    let t = t - 2.25 / d1;
+                    n1 * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / d1;
+                    n1 * t * t + 0.984375
+                }
+            }
+            _ => t,
+        }
+    }
+}
+
+pub struct MathBlock;
+impl MathBlock {
+    const NAME: &'static str = "Math";
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new("a", Inputs),
+            BlockConnectionNode::new("b", Inputs),
+            BlockConnectionNode::new_with_input_type("operation",
+                [
+                    "add",
+                    "subtract",
+                    "multiply",
+                    "divide",
+                    "modulo",
+                    "power",
+                ][..].into(),
+                Inputs
+            ),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("value", Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let a = inputs[0].as_f32();
+        let b = inputs[1].as_f32();
+        let value = match inputs[2].as_str() {
+            "subtract" => a - b,
+            "multiply" => a * b,
+            // dividing by zero would give inf/NaN, so
+            // we just pass the numerator through instead
+            "divide" => if b == 0.0 { a } else { a / b },
+            "modulo" => if b == 0.0 { 0.0 } else { a % b },
+            "power" => a.powf(b),
+            _ => a + b,
+        };
+        // things like a negative base with a fractional power
+        // produce NaN, which we dont want reaching any draw calls
+        let value = if value.is_finite() { value } else { 0.0 };
+        Ok(Some(vec![OutputResult::SingleValue(value.into())]))
+    }
+}
+
+pub struct TrigBlock;
+impl TrigBlock {
+    const NAME: &'static str = "Trig";
+    /// tan goes to infinity near its asymptotes, so
+    /// its output is clamped to +/- this value
+    const TAN_LIMIT: f32 = 1000.0;
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new("t", Inputs),
+            BlockConnectionNode::new_with_input_type("amplitude", 1.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("frequency", 1.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("phase", 0.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("function",
+                [
+                    "sin",
+                    "cos",
+                    "tan",
+                ][..].into(),
+                Inputs
+            ),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("value", Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let t = inputs[0].as_f32();
+        let amplitude = inputs[1].as_f32();
+        let frequency = inputs[2].as_f32();
+        let phase = inputs[3].as_f32();
+        let x = frequency * t + phase;
+        let value = match inputs[4].as_str() {
+            "cos" => x.cos(),
+            "tan" => {
+                let tan = x.tan();
+                if tan.is_finite() {
+                    tan.clamp(-Self::TAN_LIMIT, Self::TAN_LIMIT)
+                } else {
+                    0.0
+                }
+            }
+            _ => x.sin(),
+        };
+        let value = amplitude * value;
+        let value = if value.is_finite() { value } else { 0.0 };
+        Ok(Some(vec![OutputResult::SingleValue(value.into())]))
+    }
+}
+
+pub struct ClampBlock;
+impl ClampBlock {
+    const NAME: &'static str = "Clamp";
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new("value", Inputs),
+            BlockConnectionNode::new_with_input_type("min", 0.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("max", 1.0.into(), Inputs),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("value", Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let value = inputs[0].as_f32();
+        let mut min = inputs[1].as_f32();
+        let mut max = inputs[2].as_f32();
+        // f32::clamp panics on a NaN bound, which min > max doesn't catch
+        if !min.is_finite() || !max.is_finite() {
+            return Err(format!("min and max must be finite numbers, found {} and {}", min, max));
+        }
+        // f32::clamp panics if min > max, so if the user
+        // entered them backwards, just swap them.
+        if min > max {
+            std::mem::swap(&mut min, &mut max);
+        }
+        let value = value.clamp(min, max);
+        Ok(Some(vec![OutputResult::SingleValue(value.into())]))
+    }
+}
+
+pub struct LerpBlock;
+impl LerpBlock {
+    const NAME: &'static str = "Lerp";
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("a", 0.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("b", 100.0.into(), Inputs),
+            BlockConnectionNode::new("t", Inputs),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("value", Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let a = inputs[0].as_f32();
+        let b = inputs[1].as_f32();
+        let t = inputs[2].as_f32();
+        let value = a + (b - a) * t;
+        Ok(Some(vec![OutputResult::SingleValue(value.into())]))
     }
 }
 
@@ -1036,51 +3345,2207 @@ async fn main() {
         (FlattenPointsBlock::to_draggable_block, FlattenPointsBlock::NAME),
         (PointConnectionBlock::to_draggable_block, PointConnectionBlock::NAME),
         (PtCombineBlock::to_draggable_block, PtCombineBlock::NAME),
+        (MathBlock::to_draggable_block, MathBlock::NAME),
+        (TrigBlock::to_draggable_block, TrigBlock::NAME),
+        (ClampBlock::to_draggable_block, ClampBlock::NAME),
+        (LerpBlock::to_draggable_block, LerpBlock::NAME),
+        (HueRotateBlock::to_draggable_block, HueRotateBlock::NAME),
+        (HsvColorBlock::to_draggable_block, HsvColorBlock::NAME),
+        (GradientFillBlock::to_draggable_block, GradientFillBlock::NAME),
+        (RepeatBlock::to_draggable_block, RepeatBlock::NAME),
+        (RangeBlock::to_draggable_block, RangeBlock::NAME),
+        (RectFillBlock::to_draggable_block, RectFillBlock::NAME),
+        (TextBlock::to_draggable_block, TextBlock::NAME),
+        (ArcBlock::to_draggable_block, ArcBlock::NAME),
+        (SpiralBlock::to_draggable_block, SpiralBlock::NAME),
+        (JitterPointsBlock::to_draggable_block, JitterPointsBlock::NAME),
+        (EaseBlock::to_draggable_block, EaseBlock::NAME),
+        (PaletteBlock::to_draggable_block, PaletteBlock::NAME),
+        (SampleImageBlock::to_draggable_block, SampleImageBlock::NAME),
+        (ScreenSizeBlock::to_draggable_block, ScreenSizeBlock::NAME),
+        (DistributePointsOnCircleBlock::to_draggable_block, DistributePointsOnCircleBlock::NAME),
+        (ScatterPointsBlock::to_draggable_block, ScatterPointsBlock::NAME),
+        (NearestNeighborsBlock::to_draggable_block, NearestNeighborsBlock::NAME),
+        (FillPolygonBlock::to_draggable_block, FillPolygonBlock::NAME),
+        (PushTransformBlock::to_draggable_block, PushTransformBlock::NAME),
+        (PopTransformBlock::to_draggable_block, PopTransformBlock::NAME),
+        (ColorFromHexBlock::to_draggable_block, ColorFromHexBlock::NAME),
+        (ListLengthBlock::to_draggable_block, ListLengthBlock::NAME),
+        (IndexBlock::to_draggable_block, IndexBlock::NAME),
+        (ReverseListBlock::to_draggable_block, ReverseListBlock::NAME),
+        (SortPointsBlock::to_draggable_block, SortPointsBlock::NAME),
+        (MirrorPointsBlock::to_draggable_block, MirrorPointsBlock::NAME),
+        (RadialRepeatBlock::to_draggable_block, RadialRepeatBlock::NAME),
+        (DelaunayBlock::to_draggable_block, DelaunayBlock::NAME),
+        (BlendModeBlock::to_draggable_block, BlendModeBlock::NAME),
+        (HashBlock::to_draggable_block, HashBlock::NAME),
+        (SwitchBlock::to_draggable_block, SwitchBlock::NAME),
+        (CompareBlock::to_draggable_block, CompareBlock::NAME),
+        (IfBlock::to_draggable_block, IfBlock::NAME),
+        (NoteBlock::to_draggable_block, NoteBlock::NAME),
+        (ExportPointsBlock::to_draggable_block, ExportPointsBlock::NAME),
+        (ImportSvgPathBlock::to_draggable_block, ImportSvgPathBlock::NAME),
+        (ParametricCurveBlock::to_draggable_block, ParametricCurveBlock::NAME),
+        (FrameBlock::to_draggable_block, FrameBlock::NAME),
+        (AudioBlock::to_draggable_block, AudioBlock::NAME),
+        (MidiCCBlock::to_draggable_block, MidiCCBlock::NAME),
     ];
     let block_context = draw::BlockContext::new([]);
     let mut errors = ErrorQueue::default();
-    // TODO: each item should have its own rand seed, and then no need to pass
-    // it to window
+    let mut images = ImageCache::default();
+    // the Audio block just outputs 0 without an input, so this isn't worth an error
+    let audio_input = match AudioInput::open() {
+        Ok(input) => Some(input),
+        Err(e) => {
+            info!("{}", e);
+            None
+        }
+    };
+    // same for MidiCC, without a device every controller is just 0
+    let midi_listener = match MidiListener::open() {
+        Ok(listener) => Some(listener),
+        Err(e) => {
+            info!("{}", e);
+            None
+        }
+    };
+    let mut live = LiveInputs::default();
+    match Layout::load(LAYOUT_PATH, Layout::from_ui(&window, &timeline)) {
+        Ok(Some(layout)) => layout
+            .clamped(screen_width(), screen_height(), timeline.min_height, timeline.max_height)
+            .apply(&mut window, &mut timeline),
+        Ok(None) => {}
+        Err(e) => errors.push(ErrorMessage::new(e)),
+    }
+    let mut layout_handles = LayoutHandles::default();
     let timeline_item = TimelineItem {
-        x: 100.0,
-        y: 700.0,
-        length: 150.0,
+        id: get_id(),
+        start_secs: 4.0,
+        lane: 0,
+        duration_secs: 5.0,
         blocks: block_context,
         color: RED,
+        seed: 101,
+        speed: 1.0,
+        playback_curve: 0,
+        loop_count: 1,
+        muted: false,
+        soloed: false,
+        seed_locked: false,
+        frozen: false,
+        frozen_draw: RefCell::new(None),
     };
     let mut timeline_items = vec![timeline_item];
     let mut open_item: Option<usize> = None;
-    let mut rand_seed: u64 = 101;
-    let mut global_rng = ChaCha8Rng::seed_from_u64(rand_seed);
+    let mut global_rng = ChaCha8Rng::seed_from_u64(101);
+    let mut perf_hud = PerfHud::default();
+    let mut gif_export: Option<GifExport> = None;
+    let debug_outputs = std::env::args().any(|arg| arg == DEBUG_OUTPUTS_FLAG);
     loop {
-        clear_background(WHITE);
+        clear_background(timeline.background);
 
-        timeline.handle_input(&mut open_item, &timeline_items);
+        let pointer_over_egui = egui_wants_pointer();
+        if layout_handles.update(&mut window, &mut timeline, pointer_over_egui) {
+            if let Err(e) = Layout::from_ui(&window, &timeline).save(LAYOUT_PATH) {
+                errors.push(ErrorMessage::new(e));
+            }
+        }
+        // while resizing a panel, nothing else gets the mouse
+        let pointer_over_egui = pointer_over_egui || layout_handles.is_active();
+        let pointer_blocked = pointer_over_egui || draw::mouse_within_bounds(window.dimensions(&timeline));
+        timeline.handle_input(&mut open_item, &mut timeline_items, pointer_blocked);
 
         let (x, _, _, h) = window.dimensions(&timeline);
-        timeline.run(&timeline_items, (x, h), &mut errors, &mut rand_seed);
+        let art_area = (x, h);
+        let screen = timeline.canvas_size.unwrap_or(art_area);
+        if let Some(canvas) = timeline.canvas_size {
+            set_camera(&canvas_camera(art_area, canvas));
+        }
+        live.audio = audio_input.as_ref().map_or(AudioLevels::default(), AudioInput::levels);
+        if let Some(listener) = &midi_listener {
+            listener.poll(&mut live.midi);
+        }
+        // R tries another seed for the open item. it's handled before the
+        // run, so the frame it's pressed on already shows the new one
+        if is_key_pressed(KeyCode::R) && !egui_wants_keyboard() {
+            if let Some(item) = open_item.and_then(|i| timeline_items.get_mut(i)) {
+                item.reseed(&mut global_rng);
+            }
+        }
+        for (i, item) in timeline_items.iter_mut().enumerate() {
+            item.blocks.keep_outputs = open_item == Some(i);
+        }
+        perf_hud.time_run(|| timeline.run(&timeline_items, screen, &mut images, &live, &mut errors));
+        if debug_outputs && is_key_pressed(KeyCode::F4) {
+            if let Some(item) = open_item.and_then(|i| timeline_items.get(i)) {
+                log_item_outputs(item, screen, &mut images);
+            }
+        }
+        if let Some(canvas) = timeline.canvas_size {
+            set_default_camera();
+            draw_letterbox_bars(art_area, canvas);
+        }
         if let Some(index) = open_item {
             if let Some(item) = timeline_items.get_mut(index) {
-                window.draw(&timeline, Some(item), &mut rand_seed, &mut global_rng, &available_blocks[..]);
+                window.draw(&mut timeline, Some(item), &mut global_rng, &available_blocks[..]);
             } else {
-                window.draw(&timeline, None, &mut rand_seed, &mut global_rng, &available_blocks[..]);
+                window.draw(&mut timeline, None, &mut global_rng, &available_blocks[..]);
             }
         } else {
-            window.draw(&timeline, None, &mut rand_seed, &mut global_rng, &available_blocks[..]);
+            window.draw(&mut timeline, None, &mut global_rng, &available_blocks[..]);
         }
-
-        // the timeline + art gets rendered below
-        timeline.draw(&timeline_items);
-        if let Some(item_index) = open_item {
-            // timeline_items[item_index].blocks.draw(100.0, 100.0);
-            let block_context = &mut timeline_items[item_index].blocks;
-            block_context.update();
-            block_context.draw();
+        if std::mem::take(&mut window.duplicate_requested) {
+            if let Some(index) = open_item {
+                // added at the end so that the other items keep their
+                // index, which their random values depend on
+                let copy = timeline_items[index].duplicate();
+                timeline_items.push(copy);
+                open_item = Some(timeline_items.len() - 1);
+            }
         }
-
-        // egui gets rendered on top
+        if let Some(lane) = window.lane_removal_requested.take() {
+            timeline.remove_lane(lane, &mut timeline_items);
+        }
+        if let Some(kind) = window.export_requested.take() {
+            let result = match kind {
+                ExportKind::Gif => GifExport::start(&timeline, &window.export_settings).map(|export| {
+                    gif_export = Some(export);
+                }),
+                ExportKind::Svg => export::export_svg(&timeline, &timeline_items, &mut images, &window.export_settings),
+            };
+            if let Err(e) = result {
+                errors.push(ErrorMessage::new(e));
+            }
+        }
+        if let Some(export) = &mut gif_export {
+            let finished = if std::mem::take(&mut window.export_cancel_requested) {
+                info!("Export aborted after {} frames", export.progress.frame);
+                true
+            } else {
+                match export.step(&timeline, &timeline_items, &mut images, GIF_FRAMES_PER_STEP) {
+                    Ok(done) => done,
+                    Err(e) => {
+                        errors.push(ErrorMessage::new(e));
+                        true
+                    }
+                }
+            };
+            if finished {
+                // dropping it finishes the file
+                gif_export = None;
+            }
+        }
+        window.export_progress = gif_export.as_ref().map(|export| export.progress);
+
+        // the timeline + art gets rendered below
+        timeline.draw(&timeline_items);
+        if let Some(item_index) = open_item {
+            // timeline_items[item_index].blocks.draw(100.0, 100.0);
+            let block_context = &mut timeline_items[item_index].blocks;
+            if let Err(e) = block_context.update(pointer_over_egui, egui_wants_keyboard()) {
+                errors.push(ErrorMessage::transient(e, 5.0));
+            }
+            for notice in block_context.notices.drain(..) {
+                errors.notify(ErrorMessage::transient(notice, 5.0));
+            }
+            if is_key_pressed(KeyCode::V) && !egui_wants_keyboard() {
+                block_context.show_values = !block_context.show_values;
+            }
+            if is_key_pressed(KeyCode::L) && !egui_wants_keyboard() {
+                block_context.show_port_labels = !block_context.show_port_labels;
+            }
+            // F frames every block in the space left of the editor window,
+            // above the timeline
+            if is_key_pressed(KeyCode::F) && !egui_wants_keyboard() {
+                let (_, timeline_y, _, _) = timeline.dimensions();
+                block_context.zoom_to_fit(Rect::new(0.0, 0.0, x, timeline_y));
+            }
+            block_context.draw();
+        }
+        layout_handles.draw(&window, &timeline);
+        perf_hud.draw(&timeline_items, open_item);
+
+        // egui gets rendered on top
         egui_macroquad::draw();
         errors.draw();
         next_frame().await
     }
 }
+
+/// the largest rect with the canvas' aspect ratio that fits
+/// in `area`, centered in it
+pub fn letterbox(area: (f32, f32), canvas: (f32, f32)) -> Rect {
+    let scale = (area.0 / canvas.0).min(area.1 / canvas.1).max(0.0);
+    let (w, h) = (canvas.0 * scale, canvas.1 * scale);
+    Rect::new((area.0 - w) / 2.0, (area.1 - h) / 2.0, w, h)
+}
+
+/// maps canvas coordinates (0, 0) - canvas onto the letterboxed
+/// rect within `area`, in screen space
+pub fn canvas_camera(area: (f32, f32), canvas: (f32, f32)) -> Camera2D {
+    let rect = letterbox(area, canvas);
+    let (s_width, s_height) = screen_size();
+    let scale = rect.w / canvas.0;
+    let center = rect.center();
+    Camera2D {
+        zoom: vec2(2.0 * scale / s_width, -2.0 * scale / s_height),
+        target: vec2(canvas.0 / 2.0, canvas.1 / 2.0),
+        offset: vec2(2.0 * center.x / s_width - 1.0, 1.0 - 2.0 * center.y / s_height),
+        ..Default::default()
+    }
+}
+
+/// covers whatever was drawn outside of the letterboxed canvas
+pub fn draw_letterbox_bars(area: (f32, f32), canvas: (f32, f32)) {
+    let rect = letterbox(area, canvas);
+    let bar_color = DARKGRAY;
+    draw_rectangle(0.0, 0.0, area.0, rect.y, bar_color);
+    draw_rectangle(0.0, rect.bottom(), area.0, area.1 - rect.bottom(), bar_color);
+    draw_rectangle(0.0, rect.y, rect.x, rect.h, bar_color);
+    draw_rectangle(rect.right(), rect.y, area.0 - rect.right(), rect.h, bar_color);
+}
+
+pub struct PaletteBlock;
+impl PaletteBlock {
+    const NAME: &'static str = "Palette";
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        let palette = vec![RED, ORANGE, GOLD, DARKGREEN, DARKBLUE];
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("palette", InputValue::ListColors(palette), Inputs).list_editable(),
+            BlockConnectionNode::new("index", Inputs).integer(),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("color", BLACK.into(), Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let color = Self::pick(inputs[0].as_list_colors(), inputs[1].as_f64());
+        Ok(Some(vec![OutputResult::SingleValue(color.into())]))
+    }
+    /// wraps around in both directions, so negative indices work too.
+    /// an empty palette is always black
+    pub fn pick(palette: &[Color], index: f64) -> Color {
+        if palette.is_empty() {
+            return BLACK;
+        }
+        let index = (index.round() as i64).rem_euclid(palette.len() as i64);
+        palette[index as usize]
+    }
+}
+
+pub struct SampleImageBlock;
+impl SampleImageBlock {
+    const NAME: &'static str = "SampleImage";
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("path", "image.png".into(), Inputs),
+            BlockConnectionNode::new_with_input_type("pt", (0.0, 0.0).into(), Inputs),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("color", BLACK.into(), Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+    /// pt is normalized: (0, 0) is the top left of the image, (1, 1) the bottom right
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let image = ctx.images.get(inputs[0].as_text())?;
+        let (u, v) = inputs[1].as_point();
+        let color = images::sample(image, u, v);
+        Ok(Some(vec![OutputResult::SingleValue(color.into())]))
+    }
+}
+
+pub struct ScreenSizeBlock;
+impl ScreenSizeBlock {
+    const NAME: &'static str = "ScreenSize";
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("width", Outputs),
+            BlockConnectionNode::new("height", Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+    pub fn run(
+        _inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let (width, height) = ctx.get_screen_space();
+        Ok(Some(vec![
+            OutputResult::SingleValue(width.into()),
+            OutputResult::SingleValue(height.into()),
+        ]))
+    }
+}
+
+/// how many values are in a list
+pub struct ListLengthBlock;
+impl ListLengthBlock {
+    const NAME: &'static str = "ListLength";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let length = match inputs[0] {
+            InputValue::ListPoints(l) => l.len(),
+            InputValue::ListNumbers(l) => l.len(),
+            InputValue::ListColors(l) => l.len(),
+            x => return Err(format!("Expected a list, found {}", x.type_name())),
+        };
+        Ok(Some(vec![OutputResult::SingleValue((length as f32).into())]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("list", InputValue::ListPoints(vec![]), Inputs).list_editable().any_list(),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("count", Outputs).integer(),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+/// one value out of a list. the index wraps around in both directions,
+/// and an empty list gives (0, 0), 0 or black, depending on its type
+pub struct IndexBlock;
+impl IndexBlock {
+    const NAME: &'static str = "Index";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let index = inputs[1].as_f32() as f64;
+        let value = match inputs[0] {
+            InputValue::ListPoints(l) => {
+                InputValue::Point(Self::wrap(l.len(), index).map_or((0.0, 0.0), |i| l[i]))
+            }
+            InputValue::ListNumbers(l) => {
+                InputValue::Number(Self::wrap(l.len(), index).map_or(0.0, |i| l[i]))
+            }
+            InputValue::ListColors(l) => {
+                InputValue::Color(Self::wrap(l.len(), index).map_or(BLACK, |i| l[i]))
+            }
+            x => return Err(format!("Expected a list, found {}", x.type_name())),
+        };
+        Ok(Some(vec![OutputResult::SingleValue(value)]))
+    }
+    /// the index into a list of `len` values, None if the list is empty
+    pub fn wrap(len: usize, index: f64) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        Some((index.round() as i64).rem_euclid(len as i64) as usize)
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("list", InputValue::ListPoints(vec![]), Inputs).list_editable().any_list(),
+            BlockConnectionNode::new("index", Inputs).integer(),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("value", (0.0, 0.0).into(), Outputs).follows_list(ListFollow::Element),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct ReverseListBlock;
+impl ReverseListBlock {
+    const NAME: &'static str = "ReverseList";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let reversed = match inputs[0] {
+            InputValue::ListPoints(l) => InputValue::ListPoints(l.iter().rev().copied().collect()),
+            InputValue::ListNumbers(l) => InputValue::ListNumbers(l.iter().rev().copied().collect()),
+            InputValue::ListColors(l) => InputValue::ListColors(l.iter().rev().copied().collect()),
+            x => return Err(format!("Expected a list, found {}", x.type_name())),
+        };
+        Ok(Some(vec![OutputResult::SingleValue(reversed)]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("list", InputValue::ListPoints(vec![]), Inputs).list_editable().any_list(),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("list", InputValue::ListPoints(vec![]), Outputs).follows_list(ListFollow::List),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+/// sorts points from smallest to largest key. the center used by the
+/// angle and distance keys is the average of all the points. sorting a
+/// scatter by angle turns it into a polygon that doesn't cross itself
+pub struct SortPointsBlock;
+impl SortPointsBlock {
+    const NAME: &'static str = "SortPoints";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let mut pts = inputs[0].as_list_points().clone();
+        Self::sort(&mut pts, inputs[1].as_str());
+        Ok(Some(vec![OutputResult::SingleValue(InputValue::ListPoints(pts))]))
+    }
+    pub fn sort(pts: &mut [(f32, f32)], key: &str) {
+        if pts.is_empty() {
+            return;
+        }
+        let n = pts.len() as f32;
+        let cx = pts.iter().map(|p| p.0).sum::<f32>() / n;
+        let cy = pts.iter().map(|p| p.1).sum::<f32>() / n;
+        let key_of = |(x, y): (f32, f32)| match key {
+            "y" => y,
+            "angle-from-center" => (y - cy).atan2(x - cx),
+            "distance-from-center" => (x - cx).hypot(y - cy),
+            _ => x,
+        };
+        // a stable sort, so points with the same key keep their order
+        pts.sort_by(|a, b| key_of(*a).total_cmp(&key_of(*b)));
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Inputs).list_editable(),
+            BlockConnectionNode::new_with_input_type("by",
+                [
+                    "x",
+                    "y",
+                    "angle-from-center",
+                    "distance-from-center",
+                ][..].into(),
+                Inputs
+            ),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct MirrorPointsBlock;
+impl MirrorPointsBlock {
+    const NAME: &'static str = "MirrorPoints";
+
+    /// reflects the points across the line through a and b, or across
+    /// the horizontal ("x") or vertical ("y") line through a
+    pub fn mirror(pts: &[(f32, f32)], axis: &str, a: (f32, f32), b: (f32, f32)) -> Result<Vec<(f32, f32)>, String> {
+        let b = match axis {
+            "x" => (a.0 + 1.0, a.1),
+            "y" => (a.0, a.1 + 1.0),
+            _ => b,
+        };
+        pts.iter()
+            .map(|p| geometry::reflect(*p, a, b))
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("a and b must be different points to make a line, both are {:?}", a))
+    }
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let pts = inputs[0].as_list_points();
+        let mut mirrored = Self::mirror(pts, inputs[1].as_str(), inputs[2].as_point(), inputs[3].as_point())?;
+        if inputs[4].as_bool() {
+            // backwards, so that a path continues from its last point
+            // into its reflection instead of jumping across
+            mirrored.reverse();
+            mirrored.splice(0..0, pts.iter().copied());
+        }
+        Ok(Some(vec![OutputResult::SingleValue(InputValue::ListPoints(mirrored))]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Inputs).list_editable(),
+            BlockConnectionNode::new_with_input_type("axis",
+                [
+                    "y",
+                    "x",
+                    "line",
+                ][..].into(),
+                Inputs
+            ),
+            BlockConnectionNode::new_with_input_type("a", (0.0, 0.0).into(), Inputs),
+            // only used by the line axis
+            BlockConnectionNode::new_with_input_type("b", (0.0, 1.0).into(), Inputs),
+            BlockConnectionNode::new_with_input_type("keep_original", false.into(), Inputs),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct RadialRepeatBlock;
+impl RadialRepeatBlock {
+    const NAME: &'static str = "RadialRepeat";
+
+    /// the points followed by `count - 1` copies, each turned another
+    /// 360 / count degrees around the center (clockwise on screen)
+    pub fn repeat(pts: &[(f32, f32)], center: (f32, f32), count: usize) -> Vec<(f32, f32)> {
+        let mut out = Vec::with_capacity(pts.len() * count.max(1));
+        out.extend_from_slice(pts);
+        for copy in 1..count {
+            let (sin, cos) = (std::f32::consts::TAU * copy as f32 / count as f32).sin_cos();
+            out.extend(pts.iter().map(|(x, y)| {
+                let (dx, dy) = (x - center.0, y - center.1);
+                (center.0 + dx * cos - dy * sin, center.1 + dx * sin + dy * cos)
+            }));
+        }
+        out
+    }
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let pts = inputs[0].as_list_points();
+        let count = inputs[2].as_f32().max(1.0) as usize;
+        if pts.len().saturating_mul(count) > MAX_GENERATED_VALUES {
+            return Err(format!("{} copies of {} points is more than the max of {}", count, pts.len(), MAX_GENERATED_VALUES));
+        }
+        let pts = Self::repeat(pts, inputs[1].as_point(), count);
+        Ok(Some(vec![OutputResult::SingleValue(InputValue::ListPoints(pts))]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Inputs).list_editable(),
+            BlockConnectionNode::new_with_input_type("center", (0.0, 0.0).into(), Inputs),
+            BlockConnectionNode::new_with_input_type("count", 6.0.into(), Inputs).integer(),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+/// connects the points with the edges of their delaunay triangulation,
+/// or draws the boundaries of their voronoi cells. points that are all
+/// on one line have neither, so nothing gets drawn
+pub struct DelaunayBlock;
+impl DelaunayBlock {
+    const NAME: &'static str = "Delaunay";
+    /// the triangulation is O(n^2) at worst
+    const MAX_POINTS: usize = 5_000;
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let pts = inputs[0].as_list_points();
+        let color = inputs[2].as_color();
+        let thickness = inputs[3].as_f32();
+        if pts.len() > Self::MAX_POINTS {
+            return Err(format!("{} points is more than the max of {}", pts.len(), Self::MAX_POINTS));
+        }
+        if inputs[1].as_str() == "voronoi" {
+            // long enough for the open cells to reach past the screen
+            let ray_length = 2.0 * ctx.screen_w.hypot(ctx.screen_h);
+            for (a, b) in geometry::voronoi_edges(pts, ray_length) {
+                ctx.draw.line(a.0, a.1, b.0, b.1, thickness, color);
+            }
+        } else {
+            let triangles = geometry::delaunay(pts);
+            for (a, b) in geometry::triangle_edges(&triangles) {
+                let (pa, pb) = (pts[a], pts[b]);
+                ctx.draw.line(pa.0, pa.1, pb.0, pb.1, thickness, color);
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Inputs).list_editable(),
+            BlockConnectionNode::new_with_input_type("mode",
+                [
+                    "delaunay",
+                    "voronoi",
+                ][..].into(),
+                Inputs
+            ),
+            BlockConnectionNode::new_with_input_type("color", BLACK.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("thickness", 1.0.into(), Inputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+/// how many values a Switch block chooses between
+pub const SWITCH_INPUTS: usize = 4;
+
+pub struct SwitchBlock;
+impl SwitchBlock {
+    const NAME: &'static str = "Switch";
+
+    /// outputs the value input picked by index, wrapping around when the
+    /// index is past the last one. numbers only for now, so that the
+    /// inputs can't disagree on their type
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let values = &inputs[1..];
+        let i = IndexBlock::wrap(values.len(), inputs[0].as_f32() as f64)
+            .ok_or("Switch has nothing to choose from")?;
+        Ok(Some(vec![OutputResult::SingleValue(values[i].as_f32().into())]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut inputs = vec![BlockConnectionNode::new("index", Inputs).integer()];
+        for i in 0..SWITCH_INPUTS {
+            inputs.push(BlockConnectionNode::new(i.to_string(), Inputs));
+        }
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = inputs;
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("value", Outputs)
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct CompareBlock;
+impl CompareBlock {
+    const NAME: &'static str = "Compare";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let out = Self::compare(inputs[0].as_f32(), inputs[1].as_f32(), inputs[2].as_str())?;
+        Ok(Some(vec![OutputResult::SingleValue(out.into())]))
+    }
+    pub fn compare(a: f32, b: f32, op: &str) -> Result<bool, String> {
+        Ok(match op {
+            "<" => a < b,
+            "<=" => a <= b,
+            "==" => a == b,
+            ">=" => a >= b,
+            ">" => a > b,
+            x => return Err(format!("Unknown comparison {:?}", x)),
+        })
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new("a", Inputs),
+            BlockConnectionNode::new("b", Inputs),
+            BlockConnectionNode::new_with_input_type("op",
+                [
+                    "<",
+                    "<=",
+                    "==",
+                    ">=",
+                    ">",
+                ][..].into(),
+                Inputs
+            ),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("result", false.into(), Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct IfBlock;
+impl IfBlock {
+    const NAME: &'static str = "If";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let out = if inputs[0].as_bool() { inputs[1] } else { inputs[2] };
+        Ok(Some(vec![OutputResult::SingleValue(out.as_f32().into())]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("cond", false.into(), Inputs),
+            BlockConnectionNode::new("then", Inputs),
+            BlockConnectionNode::new("else", Inputs),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("value", Outputs)
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct ExportPointsBlock;
+impl ExportPointsBlock {
+    const NAME: &'static str = "ExportPoints";
+
+    /// writes the points to a file for plotters and other tools when `write`
+    /// is clicked, so that typing a path doesn't leave a file per keystroke
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        if !inputs[3].as_bool() {
+            return Ok(None);
+        }
+        let pts = inputs[0].as_list_points();
+        let contents = match inputs[1].as_str() {
+            "csv" => export::points_csv(pts),
+            _ => export::points_svg_document(pts, ctx.screen_w, ctx.screen_h),
+        };
+        export::write_if_changed(inputs[2].as_text(), contents)?;
+        Ok(None)
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Inputs).list_editable(),
+            BlockConnectionNode::new_with_input_type("format",
+                [
+                    "svg path",
+                    "csv",
+                ][..].into(),
+                Inputs
+            ),
+            BlockConnectionNode::new_with_input_type("path", "points.svg".into(), Inputs),
+            BlockConnectionNode::new_with_input_type("write", false.into(), Inputs).button(),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct ImportSvgPathBlock;
+impl ImportSvgPathBlock {
+    const NAME: &'static str = "ImportSvgPath";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let segments = inputs[1].as_f32().max(1.0) as usize;
+        if segments > MAX_GENERATED_VALUES {
+            return Err(format!("segments {} is more than the max of {}", segments, MAX_GENERATED_VALUES));
+        }
+        let pts = svg_path::load(inputs[0].as_text(), segments)?;
+        Ok(Some(vec![OutputResult::SingleValue(InputValue::ListPoints(pts))]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("path", "path.svg".into(), Inputs),
+            // how many lines each curve is split into
+            BlockConnectionNode::new_with_input_type("segments", 16.0.into(), Inputs).integer(),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct ParametricCurveBlock;
+impl ParametricCurveBlock {
+    const NAME: &'static str = "ParametricCurve";
+
+    /// `samples` points, with t going from 0 to 1. the expressions can
+    /// also use screen_w and screen_h, see expr::parse for the rest
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let expr_x = expr::parse(inputs[0].as_text()).map_err(|e| format!("expr_x: {}", e))?;
+        let expr_y = expr::parse(inputs[1].as_text()).map_err(|e| format!("expr_y: {}", e))?;
+        // need at least 2 points to make a line
+        let samples = inputs[2].as_f32().max(2.0) as usize;
+        if samples > MAX_GENERATED_VALUES {
+            return Err(format!("samples {} is more than the max of {}", samples, MAX_GENERATED_VALUES));
+        }
+        let (screen_w, screen_h) = ctx.get_screen_space();
+        let mut pts = Vec::with_capacity(samples);
+        for i in 0..samples {
+            let t = i as f64 / (samples - 1) as f64;
+            let var = |name: &str| match name {
+                "t" => Some(t),
+                "screen_w" => Some(screen_w as f64),
+                "screen_h" => Some(screen_h as f64),
+                _ => None,
+            };
+            let x = expr_x.eval(&var).map_err(|e| format!("expr_x: {}", e))?;
+            let y = expr_y.eval(&var).map_err(|e| format!("expr_y: {}", e))?;
+            pts.push((x as f32, y as f32));
+        }
+        Ok(Some(vec![OutputResult::SingleValue(InputValue::ListPoints(pts))]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("expr_x", "screen_w / 2 + 100 * cos(t * 2 * pi)".into(), Inputs),
+            BlockConnectionNode::new_with_input_type("expr_y", "screen_h / 2 + 100 * sin(t * 4 * pi)".into(), Inputs),
+            BlockConnectionNode::new_with_input_type("samples", 200.0.into(), Inputs).integer(),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new_with_input_type("pts", InputValue::ListPoints(vec![]), Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct NoteBlock;
+impl NoteBlock {
+    const NAME: &'static str = "Note";
+
+    /// a sticky note for documenting the graph. it never runs
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.note = Some("Select this note to edit it".into());
+        draggable_block.color = draw::NOTE_COLOR;
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct FrameBlock;
+impl FrameBlock {
+    const NAME: &'static str = "Frame";
+
+    /// a whole number that goes up by one every frame, for stepping through
+    /// things one at a time. unlike Clock it doesn't depend on the item
+    pub fn run(
+        _inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        Ok(Some(vec![OutputResult::SingleValue((ctx.frame as f32).into())]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("frame", Outputs)
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct AudioBlock;
+impl AudioBlock {
+    const NAME: &'static str = "Audio";
+
+    /// the levels of the live audio input, times gain. exports have
+    /// no live audio, so everything is 0 in them
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let gain = inputs[0].as_f32();
+        let AudioLevels { amplitude, bass, mid, treble } = ctx.audio;
+        Ok(Some(
+            [amplitude, bass, mid, treble].iter()
+                .map(|level| OutputResult::SingleValue((level * gain).into()))
+                .collect()
+        ))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("gain", 1.0.into(), Inputs),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("amplitude", Outputs),
+            BlockConnectionNode::new("bass", Outputs),
+            BlockConnectionNode::new("mid", Outputs),
+            BlockConnectionNode::new("treble", Outputs),
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct MidiCCBlock;
+impl MidiCCBlock {
+    const NAME: &'static str = "MidiCC";
+
+    /// the latest value of a MIDI controller (eg: a knob), 0 - 1
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let cc = inputs[0].as_f32().round();
+        let value = if cc < 0.0 { None } else { ctx.midi.get(cc as usize) }
+            .ok_or_else(|| format!("CC must be 0 - 127, found {}", cc))?;
+        Ok(Some(vec![OutputResult::SingleValue(value.into())]))
+    }
+
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            // the mod wheel by default
+            BlockConnectionNode::new_with_input_type("cc", 1.0.into(), Inputs).integer(),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("value", Outputs)
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+pub struct HashBlock;
+impl HashBlock {
+    const NAME: &'static str = "Hash";
+
+    pub fn run(
+        inputs: &Vec<&InputValue>,
+        _ctx: &mut BlockRunContext,
+    ) -> Result<Option<Vec<OutputResult>>, String> {
+        let out = Self::hash(inputs[0].as_f32(), inputs[1].as_f32());
+        Ok(Some(vec![OutputResult::SingleValue(out.into())]))
+    }
+    /// a pseudo-random 0..1 that only depends on value and seed, unlike
+    /// RandomOffset which draws the next number from the rng
+    pub fn hash(value: f32, seed: f32) -> f32 {
+        // -0.0 and 0.0 are the same index
+        let bits = |f: f32| if f == 0.0 { 0 } else { f.to_bits() as u64 };
+        let mut x = bits(value) ^ bits(seed).rotate_left(32);
+        // splitmix64 finalizer
+        x = x.wrapping_add(0x9e3779b97f4a7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^= x >> 31;
+        // the top 24 bits fit exactly in an f32, so it stays below 1
+        (x >> 40) as f32 / (1u64 << 24) as f32
+    }
+    pub fn to_draggable_block() -> DraggableBlock {
+        let mut draggable_block = DraggableBlock::default();
+        draggable_block.inputs = vec![
+            BlockConnectionNode::new_with_input_type("value", 0.0.into(), Inputs),
+            BlockConnectionNode::new_with_input_type("seed", 0.0.into(), Inputs),
+        ];
+        draggable_block.outputs = vec![
+            BlockConnectionNode::new("value", Outputs)
+        ];
+        draggable_block.name = format!("{} {}", draggable_block.id, Self::NAME);
+        draggable_block.run_fn = Self::run;
+        draggable_block.calculate_width();
+        draggable_block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sink::{Primitive, RecordingSink};
+    use draw::RunFn;
+
+    fn test_ctx() -> BlockRunContext<'static> {
+        BlockRunContext {
+            screen_w: 800.0,
+            screen_h: 600.0,
+            percentage: 0.0,
+            rng: ChaCha8Rng::seed_from_u64(0),
+            // zero sized, so leaking it doesn't allocate
+            draw: TransformedSink::new(Box::leak(Box::new(MacroquadSink))),
+            images: Box::leak(Box::new(ImageCache::default())),
+            frame: 0,
+            audio: AudioLevels::default(),
+            midi: Box::leak(Box::new(MidiState::default())),
+        }
+    }
+
+    /// every output of the block, which should all be single values
+    fn run_values_in(run_fn: RunFn, inputs: &[InputValue], ctx: &mut BlockRunContext) -> Result<Vec<InputValue>, String> {
+        let inputs: Vec<&InputValue> = inputs.iter().collect();
+        let out = run_fn(&inputs, ctx)?.expect("block should produce an output");
+        Ok(out.into_iter().map(|o| match o {
+            OutputResult::SingleValue(v) => v,
+            x => panic!("Expected a single value, found {:?}", x),
+        }).collect())
+    }
+
+    fn run_single_value(run_fn: RunFn, inputs: &[InputValue]) -> InputValue {
+        run_values_in(run_fn, inputs, &mut test_ctx()).unwrap().remove(0)
+    }
+
+    fn run_single_number_in(run_fn: RunFn, inputs: &[InputValue], ctx: &mut BlockRunContext) -> Result<f32, String> {
+        run_values_in(run_fn, inputs, ctx).map(|out| out[0].as_f32())
+    }
+
+    fn run_single_number(run_fn: RunFn, inputs: &[InputValue]) -> f32 {
+        run_single_number_in(run_fn, inputs, &mut test_ctx()).unwrap()
+    }
+
+    fn run_list_points(run_fn: RunFn, inputs: &[InputValue]) -> Result<Vec<(f32, f32)>, String> {
+        match run_values_in(run_fn, inputs, &mut test_ctx())?.remove(0) {
+            InputValue::ListPoints(pts) => Ok(pts),
+            x => panic!("Expected a list of points, found {:?}", x),
+        }
+    }
+
+    #[test]
+    fn clamp_block_works() {
+        let run = ClampBlock::run;
+        assert_eq!(run_single_number(run, &[5.0.into(), 0.0.into(), 1.0.into()]), 1.0);
+        assert_eq!(run_single_number(run, &[(-5.0).into(), 0.0.into(), 1.0.into()]), 0.0);
+        assert_eq!(run_single_number(run, &[0.5.into(), 0.0.into(), 1.0.into()]), 0.5);
+        // min and max are swapped, should still clamp into [0, 1]
+        assert_eq!(run_single_number(run, &[5.0.into(), 1.0.into(), 0.0.into()]), 1.0);
+        assert_eq!(run_single_number(run, &[(-5.0).into(), 1.0.into(), 0.0.into()]), 0.0);
+        let nan_max: Vec<InputValue> = vec![5.0.into(), 0.0.into(), f32::NAN.into()];
+        assert!(run(&nan_max.iter().collect(), &mut test_ctx()).is_err());
+    }
+
+    #[test]
+    fn lerp_block_works() {
+        let run = LerpBlock::run;
+        assert_eq!(run_single_number(run, &[0.0.into(), 100.0.into(), 0.0.into()]), 0.0);
+        assert_eq!(run_single_number(run, &[0.0.into(), 100.0.into(), 0.25.into()]), 25.0);
+        assert_eq!(run_single_number(run, &[0.0.into(), 100.0.into(), 1.0.into()]), 100.0);
+        assert_eq!(run_single_number(run, &[10.0.into(), (-10.0).into(), 0.5.into()]), 0.0);
+    }
+
+    fn test_item(seed: u64) -> TimelineItem {
+        TimelineItem {
+            id: get_id(),
+            start_secs: 0.0,
+            lane: 0,
+            duration_secs: 10.0,
+            blocks: BlockContext::new([]),
+            color: RED,
+            seed,
+            speed: 1.0,
+            playback_curve: 0,
+            loop_count: 1,
+            muted: false,
+            soloed: false,
+            seed_locked: false,
+            frozen: false,
+            frozen_draw: RefCell::new(None),
+        }
+    }
+
+    #[test]
+    fn duplicate_item_goes_after_with_a_new_seed() {
+        let mut item = test_item(7);
+        item.start_secs = 5.0;
+        item.speed = 2.0;
+        let copy = item.duplicate();
+        assert_eq!((copy.start_secs, copy.duration_secs, copy.speed, copy.color), (15.0, 10.0, 2.0, RED));
+        assert_ne!(copy.seed, item.seed);
+    }
+
+    #[test]
+    fn item_playback_speed_and_curve() {
+        let mut item = test_item(0);
+        assert_eq!(item.playback_percentage(0.25), 0.25);
+        item.speed = 2.0;
+        // plays twice while the bar crosses it
+        assert_eq!(item.playback_percentage(0.25), 0.5);
+        assert_eq!(item.playback_percentage(0.75), 0.5);
+        item.speed = 0.5;
+        assert_eq!(item.playback_percentage(0.5), 0.25);
+        // never stops or goes backwards
+        item.speed = -3.0;
+        assert!(item.playback_percentage(0.5) > 0.0);
+        item.speed = 1.0;
+        item.playback_curve = 1;
+        assert_eq!(EaseBlock::PRESETS[1], "ease-in-quad");
+        assert_eq!(item.playback_percentage(0.5), 0.25);
+    }
+
+    #[test]
+    fn item_loops_within_its_span() {
+        let mut item = test_item(0);
+        item.loop_count = 5;
+        assert!((item.playback_percentage(0.1) - 0.5).abs() < 1e-5);
+        assert!((item.playback_percentage(0.3) - 0.5).abs() < 1e-5);
+        assert!(item.playback_percentage(0.4) < 1e-5);
+        item.loop_count = 0;
+        assert_eq!(item.playback_percentage(0.5), 0.5);
+    }
+
+    #[test]
+    fn timeline_item_drag_moves_and_resizes() {
+        // a second is 10 pixels wide, so the item spans 50 - 150 pixels
+        let width_per_second = 10.0;
+        let mut item = test_item(0);
+        item.start_secs = 5.0;
+        // body grab moves
+        let mut drag = ItemDrag::start(0, &item, 60.0, width_per_second);
+        assert_eq!(drag.kind, ItemDragKind::Move);
+        drag.apply(&mut item, 62.0, width_per_second, 0);
+        assert!(!drag.moved);
+        assert_eq!(item.start_secs, 5.0);
+        drag.apply(&mut item, 40.0, width_per_second, 0);
+        assert!(drag.moved);
+        assert_eq!(item.start_secs, 3.0);
+        drag.apply(&mut item, -100.0, width_per_second, 0);
+        assert_eq!(item.start_secs, 0.0);
+        // moving to another lane counts as moving, even without moving sideways
+        let mut drag = ItemDrag::start(0, &item, 10.0, width_per_second);
+        drag.apply(&mut item, 10.0, width_per_second, 2);
+        assert!(drag.moved);
+        assert_eq!((item.start_secs, item.lane), (0.0, 2));
+
+        // right edge grab resizes, down to a minimum
+        item.start_secs = 5.0;
+        let mut drag = ItemDrag::start(0, &item, 148.0, width_per_second);
+        assert_eq!(drag.kind, ItemDragKind::Resize);
+        drag.apply(&mut item, 198.0, width_per_second, 1);
+        assert_eq!(item.duration_secs, 15.0);
+        drag.apply(&mut item, 0.0, width_per_second, 1);
+        assert_eq!(item.duration_secs, ItemDrag::MIN_ITEM_DURATION_SECS);
+        // resizing leaves the lane alone
+        assert_eq!((item.start_secs, item.lane), (5.0, 2));
+    }
+
+    fn random_sequence(mut rng: ChaCha8Rng) -> Vec<u32> {
+        (0..10).map(|_| rng.gen_range(0..1000)).collect()
+    }
+
+    #[test]
+    fn item_rng_is_reproducible_but_decorrelated() {
+        let a = test_item(1);
+        let b = test_item(2);
+        // same item and pass always gives the same values
+        assert_eq!(random_sequence(a.rng(0)), random_sequence(a.rng(0)));
+        // different seeds give different values
+        assert_ne!(random_sequence(a.rng(0)), random_sequence(b.rng(0)));
+        // same seed, but different items in the timeline
+        let a2 = test_item(1);
+        assert_ne!(random_sequence(a.rng(0)), random_sequence(a2.rng(0)));
+        // each loop of an item gets its own values
+        assert_ne!(random_sequence(a.rng(0)), random_sequence(a.rng(1)));
+        // the stream doesn't depend on a hasher that can change between builds
+        assert_eq!(a.rng(3).get_stream(), (u64::from(a.id) << 32) | 3);
+    }
+
+    #[test]
+    fn item_passes_count_the_loops() {
+        let mut item = test_item(1);
+        assert_eq!([0.0, 0.5, 1.0].map(|linear| item.pass(linear)), [0, 0, 0]);
+        item.loop_count = 3;
+        assert_eq!([0.0, 0.34, 0.99, 1.0].map(|linear| item.pass(linear)), [0, 1, 2, 2]);
+    }
+
+    #[test]
+    fn repeat_block_caps_count() {
+        let run = |count: f32| {
+            let inputs: Vec<InputValue> = vec![3.0.into(), count.into()];
+            let inputs: Vec<&InputValue> = inputs.iter().collect();
+            RepeatBlock::run(&inputs, &mut test_ctx())
+        };
+        match &run(5.0).unwrap().unwrap()[0] {
+            OutputResult::Iteration(v) => {
+                assert_eq!(v.len(), 5);
+                assert!(v.iter().all(|x| x.as_f32() == 3.0));
+            }
+            x => panic!("Expected an iteration, found {:?}", x),
+        }
+        assert!(run((MAX_GENERATED_VALUES + 1) as f32).is_err());
+    }
+
+    #[test]
+    fn range_block_includes_endpoints() {
+        let run = |count: f32| -> Vec<f32> {
+            let inputs: Vec<InputValue> = vec![0.0.into(), 10.0.into(), count.into()];
+            let inputs: Vec<&InputValue> = inputs.iter().collect();
+            match &RangeBlock::run(&inputs, &mut test_ctx()).unwrap().unwrap()[0] {
+                OutputResult::Iteration(v) => v.iter().map(|x| x.as_f32()).collect(),
+                x => panic!("Expected an iteration, found {:?}", x),
+            }
+        };
+        assert_eq!(run(0.0), vec![]);
+        assert_eq!(run(1.0), vec![0.0]);
+        assert_eq!(run(2.0), vec![0.0, 10.0]);
+        assert_eq!(run(5.0), vec![0.0, 2.5, 5.0, 7.5, 10.0]);
+    }
+
+    #[test]
+    fn arc_points_cover_the_arc() {
+        let pts = ArcBlock::arc_points(10.0, 10.0, 5.0, 0.0, 90.0, 2);
+        assert_eq!(pts.len(), 3);
+        assert!((pts[0].0 - 15.0).abs() < 1e-4 && (pts[0].1 - 10.0).abs() < 1e-4);
+        assert!((pts[2].0 - 10.0).abs() < 1e-4 && (pts[2].1 - 15.0).abs() < 1e-4);
+        // 0 segments is treated as 1
+        assert_eq!(ArcBlock::arc_points(0.0, 0.0, 1.0, 0.0, 90.0, 0).len(), 2);
+    }
+
+    #[test]
+    fn spiral_block_spans_radii() {
+        let run = |turns: f32, points: f32| {
+            let inputs: Vec<InputValue> = vec![
+                0.0.into(), 0.0.into(), 0.0.into(), 10.0.into(), turns.into(), points.into(),
+            ];
+            let inputs: Vec<&InputValue> = inputs.iter().collect();
+            SpiralBlock::run(&inputs, &mut test_ctx())
+        };
+        let out = run(1.0, 0.0).unwrap().unwrap();
+        let pts = match &out[0] {
+            OutputResult::SingleValue(InputValue::ListPoints(pts)) => pts.clone(),
+            x => panic!("Expected a list of points, found {:?}", x),
+        };
+        // points gets bumped up to 2
+        assert_eq!(pts.len(), 2);
+        assert_eq!(pts[0], (0.0, 0.0));
+        assert!((pts[1].0 - 10.0).abs() < 1e-4 && pts[1].1.abs() < 1e-4);
+        assert!(run(0.0, 10.0).is_err());
+        assert!(run(f32::NAN, 10.0).is_err());
+        assert!(run(f32::INFINITY, 10.0).is_err());
+    }
+
+    #[test]
+    fn parametric_curve_samples_t() {
+        let run = |x: &str, y: &str| run_list_points(ParametricCurveBlock::run, &[x.into(), y.into(), 3.0.into()]);
+        assert_eq!(run("t * screen_w", "screen_h - t * 10"), Ok(vec![(0.0, 600.0), (400.0, 595.0), (800.0, 590.0)]));
+        // errors say which expression they came from
+        assert!(run("t +", "0").unwrap_err().starts_with("expr_x: "));
+        assert_eq!(run("t", "w").unwrap_err(), "expr_y: Unknown variable w");
+    }
+
+    #[test]
+    fn mirror_points_across_the_y_axis() {
+        let run = |axis: usize, keep_original: bool| run_list_points(MirrorPointsBlock::run, &[
+            InputValue::ListPoints(vec![(1.0, 2.0), (3.0, -4.0)]),
+            InputValue::Selection((axis, vec!["y".into(), "x".into(), "line".into()])),
+            (0.0, 0.0).into(),
+            (0.0, 0.0).into(),
+            keep_original.into(),
+        ]);
+        assert_eq!(run(0, false), Ok(vec![(-1.0, 2.0), (-3.0, -4.0)]));
+        assert_eq!(run(0, true), Ok(vec![(1.0, 2.0), (3.0, -4.0), (-3.0, -4.0), (-1.0, 2.0)]));
+        // across the x axis through (0, 1)
+        let pts = MirrorPointsBlock::mirror(&[(1.0, 2.0)], "x", (0.0, 1.0), (0.0, 0.0)).unwrap();
+        assert_eq!(pts, vec![(1.0, 0.0)]);
+        // a line needs two different points
+        assert!(run(2, false).is_err());
+    }
+
+    #[test]
+    fn radial_repeat_turns_copies_around_the_center() {
+        let run = |count: f32| run_list_points(RadialRepeatBlock::run, &[
+            InputValue::ListPoints(vec![(20.0, 10.0), (30.0, 10.0)]),
+            (10.0, 10.0).into(),
+            count.into(),
+        ]).unwrap();
+        let pts = run(4.0);
+        // every 90 degrees, clockwise on screen
+        let expected = [
+            (20.0, 10.0), (30.0, 10.0),
+            (10.0, 20.0), (10.0, 30.0),
+            (0.0, 10.0), (-10.0, 10.0),
+            (10.0, 0.0), (10.0, -10.0),
+        ];
+        assert_eq!(pts.len(), expected.len());
+        for (p, e) in pts.iter().zip(expected) {
+            assert!((p.0 - e.0).abs() < 1e-4 && (p.1 - e.1).abs() < 1e-4, "{:?} != {:?}", p, e);
+        }
+        // one copy or fewer is just the points
+        assert_eq!(run(1.0), vec![(20.0, 10.0), (30.0, 10.0)]);
+        assert_eq!(run(0.0), vec![(20.0, 10.0), (30.0, 10.0)]);
+    }
+
+    #[test]
+    fn points_on_circle_are_evenly_spaced() {
+        let run = |count: f32| {
+            let inputs: Vec<InputValue> = vec![10.0.into(), 20.0.into(), 5.0.into(), count.into()];
+            let inputs: Vec<&InputValue> = inputs.iter().collect();
+            match DistributePointsOnCircleBlock::run(&inputs, &mut test_ctx()).unwrap().unwrap().remove(0) {
+                OutputResult::SingleValue(InputValue::ListPoints(pts)) => pts,
+                x => panic!("Expected a list of points, found {:?}", x),
+            }
+        };
+        let pts = run(4.0);
+        let expected = [(15.0, 20.0), (10.0, 25.0), (5.0, 20.0), (10.0, 15.0)];
+        assert_eq!(pts.len(), 4);
+        for (pt, expected) in pts.iter().zip(expected) {
+            assert!((pt.0 - expected.0).abs() < 1e-4 && (pt.1 - expected.1).abs() < 1e-4, "{:?}", pt);
+        }
+        // count is at least 1
+        assert_eq!(run(0.0), vec![(15.0, 20.0)]);
+        assert_eq!(run(-5.0).len(), 1);
+    }
+
+    #[test]
+    fn scatter_points_stay_in_bounds() {
+        let run = |values: [f32; 5]| {
+            let inputs: Vec<InputValue> = values.iter().map(|v| (*v).into()).collect();
+            let inputs: Vec<&InputValue> = inputs.iter().collect();
+            ScatterPointsBlock::run(&inputs, &mut test_ctx()).map(|out| match out.unwrap().remove(0) {
+                OutputResult::SingleValue(InputValue::ListPoints(pts)) => pts,
+                x => panic!("Expected a list of points, found {:?}", x),
+            })
+        };
+        let pts = run([50.0, 10.0, 20.0, 30.0, 40.0]).unwrap();
+        assert_eq!(pts.len(), 50);
+        assert!(pts.iter().all(|(x, y)| (10.0..=40.0).contains(x) && (20.0..=60.0).contains(y)));
+        // same seed, same points
+        assert_eq!(pts, run([50.0, 10.0, 20.0, 30.0, 40.0]).unwrap());
+        // no box means the whole 800x600 test screen
+        let pts = run([50.0, 10.0, 20.0, 0.0, 0.0]).unwrap();
+        assert!(pts.iter().all(|(x, y)| (0.0..=800.0).contains(x) && (0.0..=600.0).contains(y)));
+        assert!(run([MAX_GENERATED_VALUES as f32 + 1.0, 0.0, 0.0, 0.0, 0.0]).is_err());
+        assert!(run([50.0, 0.0, 0.0, f32::NAN, 10.0]).is_err());
+        assert!(run([50.0, f32::INFINITY, 0.0, 10.0, 10.0]).is_err());
+    }
+
+    #[test]
+    fn transform_blocks_rotate_around_pivot() {
+        let mut sink = RecordingSink::default();
+        let mut ctx = BlockRunContext { draw: TransformedSink::new(&mut sink), ..test_ctx() };
+        // rotate 180 degrees around (10, 10)
+        let push: Vec<InputValue> = vec![1.0.into(), 180.0.into(), 10.0.into(), 10.0.into(), 0.0.into(), 0.0.into()];
+        PushTransformBlock::run(&push.iter().collect(), &mut ctx).unwrap();
+        let circle: Vec<InputValue> = vec![0.0.into(), 0.0.into(), 3.0.into(), RED.into()];
+        CircleBlock::run(&circle.iter().collect(), &mut ctx).unwrap();
+        let pass: Vec<InputValue> = vec![1.0.into()];
+        PopTransformBlock::run(&pass.iter().collect(), &mut ctx).unwrap();
+        // nothing left to pop
+        assert!(PopTransformBlock::run(&pass.iter().collect(), &mut ctx).is_err());
+        drop(ctx);
+        match sink.primitives[0] {
+            Primitive::Circle { x, y, .. } => assert!((x - 20.0).abs() < 1e-4 && (y - 20.0).abs() < 1e-4, "{} {}", x, y),
+            ref x => panic!("Expected a circle, found {:?}", x),
+        }
+    }
+
+    #[test]
+    fn color_from_hex_block_errors_on_bad_input() {
+        let run = |hex: &str| {
+            let inputs: Vec<InputValue> = vec![hex.into()];
+            ColorFromHexBlock::run(&inputs.iter().collect(), &mut test_ctx())
+        };
+        match &run("#00ff00").unwrap().unwrap()[0] {
+            OutputResult::SingleValue(v) => assert_eq!(v.as_color(), Color::from_rgba(0, 255, 0, 255)),
+            x => panic!("Expected a single value, found {:?}", x),
+        }
+        let err = run("green").unwrap_err();
+        assert!(err.contains("green"), "{}", err);
+    }
+
+    #[test]
+    fn nearest_neighbor_edges() {
+        let pts = [(0.0, 0.0), (1.0, 0.0), (10.0, 0.0), (12.0, 0.0)];
+        // both pairs pick each other, but each edge is only there once
+        assert_eq!(NearestNeighborsBlock::neighbor_edges(&pts, 1), vec![(0, 1), (2, 3)]);
+        assert_eq!(NearestNeighborsBlock::neighbor_edges(&pts, 2), vec![(0, 1), (0, 2), (1, 2), (1, 3), (2, 3)]);
+        assert_eq!(NearestNeighborsBlock::neighbor_edges(&pts, 0), vec![]);
+        // k >= n connects every pair
+        assert_eq!(NearestNeighborsBlock::neighbor_edges(&pts, 10).len(), 6);
+        assert_eq!(NearestNeighborsBlock::neighbor_edges(&[], 3), vec![]);
+    }
+
+    #[test]
+    fn jitter_points_is_bounded_and_deterministic() {
+        let pts = vec![(0.0, 0.0), (10.0, 10.0), (20.0, 0.0)];
+        let inputs: Vec<InputValue> = vec![InputValue::ListPoints(pts.clone()), 2.0.into()];
+        let inputs: Vec<&InputValue> = inputs.iter().collect();
+        let run = || match JitterPointsBlock::run(&inputs, &mut test_ctx()).unwrap().unwrap().remove(0) {
+            OutputResult::SingleValue(InputValue::ListPoints(out)) => out,
+            x => panic!("Expected a list of points, found {:?}", x),
+        };
+        let out = run();
+        assert_eq!(out, run());
+        assert_eq!(out.len(), pts.len());
+        for (a, b) in pts.iter().zip(out.iter()) {
+            assert!((a.0 - b.0).abs() <= 2.0 && (a.1 - b.1).abs() <= 2.0);
+        }
+        for offset in [f32::NAN, f32::INFINITY] {
+            let inputs: Vec<InputValue> = vec![InputValue::ListPoints(pts.clone()), offset.into()];
+            assert!(JitterPointsBlock::run(&inputs.iter().collect(), &mut test_ctx()).is_err());
+        }
+    }
+
+    #[test]
+    fn clock_ping_pong_mapping() {
+        assert_eq!(ClockBlock::apply_mode("ping-pong", 0.0, 1.0), 0.0);
+        assert_eq!(ClockBlock::apply_mode("ping-pong", 0.5, 1.0), 1.0);
+        assert_eq!(ClockBlock::apply_mode("ping-pong", 1.0, 1.0), 0.0);
+        assert_eq!(ClockBlock::apply_mode("ping-pong", 0.25, 1.0), 0.5);
+        // there and back twice
+        assert_eq!(ClockBlock::apply_mode("ping-pong", 0.25, 2.0), 1.0);
+        assert_eq!(ClockBlock::apply_mode("ping-pong", 0.5, 2.0), 0.0);
+    }
+
+    #[test]
+    fn clock_loops_repeat_within_the_item() {
+        let run = |mode: &str, percentage: f32, cycles: f32| {
+            let mut mode_input: InputValue = ["once", "loop", "ping-pong"][..].into();
+            if let InputValue::Selection((selected, options)) = &mut mode_input {
+                *selected = options.iter().position(|o| o == mode).unwrap();
+            }
+            let mut ctx = BlockRunContext { percentage, ..test_ctx() };
+            run_single_number_in(ClockBlock::run, &["none".into(), 6.0.into(), 1.0.into(), mode_input, cycles.into()], &mut ctx)
+        };
+        // 3 cycles, so a third of the way through the item starts over
+        assert_eq!(run("loop", 0.0, 3.0), Ok(0.0));
+        assert!((run("loop", 0.5, 3.0).unwrap() - 0.5).abs() < 1e-5);
+        assert!(run("loop", 0.34, 3.0).unwrap() < 0.05);
+        // once ignores the cycles
+        assert_eq!(run("once", 0.5, 3.0), Ok(0.5));
+        assert_eq!(run("loop", 0.5, 1.0), Ok(0.5));
+        for cycles in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(run("loop", 0.5, cycles).is_err(), "{} cycles", cycles);
+        }
+    }
+
+    #[test]
+    fn ease_presets_hit_endpoints() {
+        for curve in EaseBlock::PRESETS {
+            assert!(EaseBlock::ease(curve, 0.0).abs() < 1e-5, "{} at 0", curve);
+            assert!((EaseBlock::ease(curve, 1.0) - 1.0).abs() < 1e-5, "{} at 1", curve);
+        }
+        assert_eq!(EaseBlock::ease("ease-in-quad", 0.5), 0.25);
+        assert_eq!(EaseBlock::ease("ease-out-quad", 0.5), 0.75);
+        assert!((EaseBlock::ease("ease-in-out-cubic", 0.5) - 0.5).abs() < 1e-5);
+        assert!((EaseBlock::ease("sine", 0.5) - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn error_message_expiry() {
+        let permanent = ErrorMessage::new("a".into());
+        assert!(!permanent.is_expired(1e9));
+        let transient = ErrorMessage { e: "b".into(), created_at: 10.0, ttl: Some(5.0), repeats: 0 };
+        assert!(!transient.is_expired(14.0));
+        assert!(transient.is_expired(15.5));
+    }
+
+    #[test]
+    fn error_queue_dedupes() {
+        let mut queue = ErrorQueue::default();
+        for _ in 0..42 {
+            queue.push(ErrorMessage::new("same".into()));
+        }
+        queue.push(ErrorMessage::new("other".into()));
+        assert_eq!(queue.errors.len(), 2);
+        assert_eq!(queue.errors[0].text(), "same (x42)");
+        assert_eq!(queue.errors[1].text(), "other");
+
+        // notices are kept apart, so they don't pause the preview
+        let mut queue = ErrorQueue::default();
+        queue.notify(ErrorMessage::new("Press Delete again".into()));
+        queue.notify(ErrorMessage::new("Press Delete again".into()));
+        assert!(!queue.has_errors());
+        assert_eq!(queue.notices.len(), 1);
+    }
+
+    #[test]
+    fn circle_block_skips_nan() {
+        let mut sink = RecordingSink::default();
+        let inputs: Vec<InputValue> = vec![f32::NAN.into(), 2.0.into(), 3.0.into(), RED.into()];
+        let inputs: Vec<&InputValue> = inputs.iter().collect();
+        CircleBlock::run(&inputs, &mut BlockRunContext { draw: TransformedSink::new(&mut sink), ..test_ctx() }).unwrap();
+        assert!(sink.primitives.is_empty());
+    }
+
+    #[test]
+    fn circle_block_draws_one_circle() {
+        let mut sink = RecordingSink::default();
+        let inputs: Vec<InputValue> = vec![1.0.into(), 2.0.into(), 3.0.into(), RED.into()];
+        let inputs: Vec<&InputValue> = inputs.iter().collect();
+        CircleBlock::run(&inputs, &mut BlockRunContext { draw: TransformedSink::new(&mut sink), ..test_ctx() }).unwrap();
+        assert_eq!(sink.primitives, vec![Primitive::Circle { x: 1.0, y: 2.0, radius: 3.0, color: RED }]);
+    }
+
+    #[test]
+    fn grid_block_rejects_negative_counts() {
+        let grid_len = |rows: f32, cols: f32| {
+            let inputs: Vec<InputValue> = vec![rows.into(), cols.into()];
+            let inputs: Vec<&InputValue> = inputs.iter().collect();
+            let out = GridBlock::run(&inputs, &mut test_ctx()).unwrap().unwrap();
+            match &out[0] {
+                OutputResult::Iteration(v) => v.len(),
+                x => panic!("Expected an iteration, found {:?}", x),
+            }
+        };
+        assert_eq!(grid_len(3.0, 4.0), 12);
+        assert_eq!(grid_len(-3.0, 4.0), 0);
+        assert_eq!(grid_len(3.0, -4.0), 0);
+    }
+
+    #[test]
+    fn square_grid_respects_margin_and_shape() {
+        // test_ctx's screen is 800x600
+        let corners = |rows: f32, cols: f32, margin: f32| {
+            let inputs: Vec<InputValue> = vec![rows.into(), cols.into(), margin.into()];
+            let inputs: Vec<&InputValue> = inputs.iter().collect();
+            let out = SquareGridBlock::run(&inputs, &mut test_ctx()).unwrap().unwrap();
+            match (&out[0], &out[2]) {
+                (OutputResult::Iteration(top_left), OutputResult::Iteration(bottom_right)) => {
+                    let top_left: Vec<(f32, f32)> = top_left.iter().map(|v| v.as_point()).collect();
+                    let bottom_right: Vec<(f32, f32)> = bottom_right.iter().map(|v| v.as_point()).collect();
+                    (top_left, bottom_right)
+                }
+                x => panic!("Expected iterations, found {:?}", x),
+            }
+        };
+        // the old behavior: a square over the smaller screen side
+        let (top_left, bottom_right) = corners(10.0, 10.0, 0.0);
+        assert_eq!(top_left.len(), 100);
+        assert_eq!(top_left[0], (0.0, 0.0));
+        assert_eq!(bottom_right[99], (600.0, 600.0));
+
+        let (top_left, bottom_right) = corners(2.0, 4.0, 50.0);
+        assert_eq!(top_left.len(), 8);
+        assert_eq!(top_left[0], (50.0, 50.0));
+        // 700 / 4 is smaller than 500 / 2, so the width decides the tile size
+        assert_eq!(bottom_right[7], (750.0, 400.0));
+
+        assert_eq!(corners(-1.0, 4.0, 0.0).0.len(), 0);
+    }
+
+    #[test]
+    fn grid_cells_have_a_normalized_t() {
+        let t_values = |out: &[OutputResult], i: usize| match &out[i] {
+            OutputResult::Iteration(v) => v.iter().map(|v| v.as_f32()).collect::<Vec<_>>(),
+            x => panic!("Expected an iteration, found {:?}", x),
+        };
+        let inputs: Vec<InputValue> = vec![2.0.into(), 3.0.into()];
+        let out = GridBlock::run(&inputs.iter().collect(), &mut test_ctx()).unwrap().unwrap();
+        assert_eq!(t_values(&out, 2), vec![0.0, 0.2, 0.4, 0.6, 0.8, 1.0]);
+        // as long as the positions, so they iterate together
+        assert_eq!(t_values(&out, 0).len(), 6);
+
+        let inputs: Vec<InputValue> = vec![1.0.into(), 1.0.into(), 0.0.into()];
+        let out = SquareGridBlock::run(&inputs.iter().collect(), &mut test_ctx()).unwrap().unwrap();
+        assert_eq!(t_values(&out, 4), vec![0.0]);
+        let inputs: Vec<InputValue> = vec![2.0.into(), 2.0.into(), 0.0.into()];
+        let out = SquareGridBlock::run(&inputs.iter().collect(), &mut test_ctx()).unwrap().unwrap();
+        assert_eq!(t_values(&out, 4).len(), 4);
+        assert_eq!(t_values(&out, 4)[3], 1.0);
+    }
+
+    #[test]
+    fn iteration_block_rejects_runaway_steps() {
+        let run = |start: f32, end: f32, by: f32| {
+            let inputs: Vec<InputValue> = vec![1.0.into(), start.into(), end.into(), by.into()];
+            IterationBlock::run(&inputs.iter().collect(), &mut test_ctx()).map(|out| match &out.unwrap()[1] {
+                OutputResult::Iteration(v) => v.iter().map(|v| v.as_f32()).collect::<Vec<_>>(),
+                x => panic!("Expected an iteration, found {:?}", x),
+            })
+        };
+        assert_eq!(run(0.0, 30.0, 10.0).unwrap(), vec![0.0, 10.0, 20.0, 30.0]);
+        assert_eq!(run(0.0, 25.0, 10.0).unwrap(), vec![0.0, 10.0, 20.0]);
+        assert_eq!(run(10.0, 0.0, 10.0).unwrap(), Vec::<f32>::new());
+        assert!(run(0.0, 100.0, 0.0).is_err());
+        assert!(run(0.0, 100.0, -1.0).is_err());
+        assert!(run(0.0, 100.0, f32::NAN).is_err());
+        assert!(run(0.0, 1e9, 1.0).unwrap_err().contains("max"));
+    }
+
+    #[test]
+    fn letterbox_keeps_aspect_and_centers() {
+        // wider area than the canvas: bars on the left and right
+        let rect = letterbox((1000.0, 500.0), (400.0, 200.0));
+        assert_eq!((rect.x, rect.y, rect.w, rect.h), (0.0, 0.0, 1000.0, 500.0));
+        let rect = letterbox((1000.0, 500.0), (100.0, 100.0));
+        assert_eq!((rect.x, rect.y, rect.w, rect.h), (250.0, 0.0, 500.0, 500.0));
+        // taller area: bars on the top and bottom
+        let rect = letterbox((400.0, 800.0), (200.0, 100.0));
+        assert_eq!((rect.x, rect.y, rect.w, rect.h), (0.0, 300.0, 400.0, 200.0));
+    }
+
+    #[test]
+    fn screen_size_block_outputs_both_sides() {
+        let out = ScreenSizeBlock::run(&vec![], &mut test_ctx()).unwrap().unwrap();
+        let sizes: Vec<f32> = out.iter().map(|o| match o {
+            OutputResult::SingleValue(v) => v.as_f32(),
+            x => panic!("Expected a single value, found {:?}", x),
+        }).collect();
+        assert_eq!(sizes, vec![800.0, 600.0]);
+    }
+
+    #[test]
+    fn palette_wraps_index() {
+        let palette = [RED, GREEN, BLUE];
+        assert_eq!(PaletteBlock::pick(&palette, 0.0), RED);
+        assert_eq!(PaletteBlock::pick(&palette, 4.0), GREEN);
+        assert_eq!(PaletteBlock::pick(&palette, -1.0), BLUE);
+        assert_eq!(PaletteBlock::pick(&[], 3.0), BLACK);
+    }
+
+    #[test]
+    fn list_length_and_index() {
+        let pts = InputValue::ListPoints(vec![(1.0, 1.0), (2.0, 2.0), (3.0, 3.0)]);
+        let single = |out: Option<Vec<OutputResult>>| match out.unwrap().remove(0) {
+            OutputResult::SingleValue(v) => v,
+            x => panic!("Expected a single value, found {:?}", x),
+        };
+        let length = single(ListLengthBlock::run(&vec![&pts], &mut test_ctx()).unwrap());
+        assert_eq!(length.as_f32(), 3.0);
+        let empty = InputValue::ListPoints(vec![]);
+        assert_eq!(single(ListLengthBlock::run(&vec![&empty], &mut test_ctx()).unwrap()).as_f32(), 0.0);
+
+        let index = |list: &InputValue, i: f32| {
+            let i: InputValue = i.into();
+            single(IndexBlock::run(&vec![list, &i], &mut test_ctx()).unwrap())
+        };
+        assert_eq!(index(&pts, 1.0).as_point(), (2.0, 2.0));
+        assert_eq!(index(&pts, 4.0).as_point(), (2.0, 2.0));
+        assert_eq!(index(&pts, -1.0).as_point(), (3.0, 3.0));
+        assert_eq!(index(&empty, 2.0).as_point(), (0.0, 0.0));
+        let numbers = InputValue::ListNumbers(vec![5.0, 6.0]);
+        assert_eq!(index(&numbers, 3.0).as_f32(), 6.0);
+    }
+
+    #[test]
+    fn reverse_list_block() {
+        let pts = InputValue::ListPoints(vec![(1.0, 1.0), (2.0, 2.0)]);
+        let out = ReverseListBlock::run(&vec![&pts], &mut test_ctx()).unwrap().unwrap();
+        match &out[0] {
+            OutputResult::SingleValue(v) => assert_eq!(v.as_list_points(), &vec![(2.0, 2.0), (1.0, 1.0)]),
+            x => panic!("Expected a single value, found {:?}", x),
+        }
+        assert!(ReverseListBlock::run(&vec![&1.0.into()], &mut test_ctx()).is_err());
+    }
+
+    #[test]
+    fn sort_points_by_angle_is_convex() {
+        // the corners and edge midpoints of a square, shuffled
+        let mut pts = vec![
+            (10.0, 0.0), (0.0, 10.0), (5.0, 10.0), (10.0, 10.0),
+            (0.0, 0.0), (10.0, 5.0), (0.0, 5.0), (5.0, 0.0),
+        ];
+        SortPointsBlock::sort(&mut pts, "angle-from-center");
+        // walking the points in order only ever turns one way
+        // (or goes straight, at the midpoints)
+        for i in 0..pts.len() {
+            let (a, b, c) = (pts[i], pts[(i + 1) % pts.len()], pts[(i + 2) % pts.len()]);
+            assert!(geometry::cross(a, b, c) >= 0.0, "{:?}", pts);
+        }
+        assert!(geometry::polygon_area2(&pts) > 0.0);
+
+        SortPointsBlock::sort(&mut pts, "x");
+        assert!(pts.windows(2).all(|w| w[0].0 <= w[1].0));
+        SortPointsBlock::sort(&mut pts, "distance-from-center");
+        // the midpoints are closer to the center than the corners
+        assert!(pts[..4].iter().all(|p| p.0 == 5.0 || p.1 == 5.0));
+    }
+
+    #[test]
+    fn import_svg_path_caps_its_segments() {
+        let inputs: Vec<InputValue> = vec!["path.svg".into(), (MAX_GENERATED_VALUES as f32 + 1.0).into()];
+        let err = ImportSvgPathBlock::run(&inputs.iter().collect(), &mut test_ctx()).unwrap_err();
+        assert!(err.starts_with("segments"), "{}", err);
+    }
+
+    #[test]
+    fn fill_polygon_caps_its_points() {
+        let run = |count: usize| {
+            let pts = (0..count).map(|i| (i as f32, (i % 2) as f32)).collect();
+            let inputs: Vec<InputValue> = vec![InputValue::ListPoints(pts), BLACK.into()];
+            let mut sink = RecordingSink::default();
+            let mut ctx = BlockRunContext { draw: TransformedSink::new(&mut sink), ..test_ctx() };
+            FillPolygonBlock::run(&inputs.iter().collect(), &mut ctx)
+        };
+        assert!(run(3).is_ok());
+        assert!(run(FillPolygonBlock::MAX_POINTS + 1).unwrap_err().contains("more than the max"));
+    }
+
+    #[test]
+    fn delaunay_block_draws_edges() {
+        let square = InputValue::ListPoints(vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]);
+        let draw_count = |mode: &str| {
+            let mut mode_input: InputValue = ["delaunay", "voronoi"][..].into();
+            if let InputValue::Selection((selected, options)) = &mut mode_input {
+                *selected = options.iter().position(|o| o == mode).unwrap();
+            }
+            let inputs: Vec<InputValue> = vec![square.clone(), mode_input, BLACK.into(), 1.0.into()];
+            let inputs: Vec<&InputValue> = inputs.iter().collect();
+            let mut sink = RecordingSink::default();
+            let mut ctx = BlockRunContext { draw: TransformedSink::new(&mut sink), ..test_ctx() };
+            DelaunayBlock::run(&inputs, &mut ctx).unwrap();
+            sink.primitives.len()
+        };
+        assert_eq!(draw_count("delaunay"), 5);
+        // both triangles share a circumcenter, so there's only
+        // a ray for each side
+        assert_eq!(draw_count("voronoi"), 4);
+    }
+
+    #[test]
+    fn blend_mode_block_sets_the_mode_and_passes_through() {
+        let mut mode: InputValue = BlendMode::NAMES[..].into();
+        if let InputValue::Selection((selected, _)) = &mut mode {
+            *selected = 1;
+        }
+        let pass: InputValue = 3.0.into();
+        let mut sink = RecordingSink::default();
+        let mut ctx = BlockRunContext { draw: TransformedSink::new(&mut sink), ..test_ctx() };
+        let out = BlendModeBlock::run(&vec![&pass, &mode], &mut ctx).unwrap().unwrap();
+        match &out[0] {
+            OutputResult::SingleValue(v) => assert_eq!(v.as_f32(), 3.0),
+            x => panic!("Expected a single value, found {:?}", x),
+        }
+        assert_eq!(sink.primitives, vec![Primitive::Blend(BlendMode::Additive)]);
+    }
+
+    #[test]
+    fn random_offset_wraps_into_range() {
+        assert_eq!(RandOffSetBlock::wrap(370.0, 0.0, 360.0), 10.0);
+        assert_eq!(RandOffSetBlock::wrap(-30.0, 0.0, 360.0), 330.0);
+        assert_eq!(RandOffSetBlock::wrap(5.0, 10.0, 20.0), 15.0);
+        // an empty range doesn't wrap
+        assert_eq!(RandOffSetBlock::wrap(400.0, 10.0, 10.0), 400.0);
+
+        // low == high is an empty random range, so the offset is just low
+        let run = |wrap: bool| run_single_number(
+            RandOffSetBlock::run,
+            &[350.0.into(), 20.0.into(), 20.0.into(), wrap.into(), 0.0.into(), 360.0.into()],
+        );
+        assert_eq!(run(false), 370.0);
+        assert_eq!(run(true), 10.0);
+    }
+
+    #[test]
+    fn switch_picks_the_index_and_wraps() {
+        let run = |index: f32| run_single_number(
+            SwitchBlock::run,
+            &[index.into(), 10.0.into(), 20.0.into(), 30.0.into(), 40.0.into()],
+        );
+        assert_eq!(run(0.0), 10.0);
+        assert_eq!(run(3.0), 40.0);
+        assert_eq!(run(5.0), 20.0);
+        assert_eq!(run(-1.0), 40.0);
+    }
+
+    #[test]
+    fn compare_truth_table() {
+        // (op, 1 vs 2, 2 vs 2, 3 vs 2)
+        let table = [
+            ("<", [true, false, false]),
+            ("<=", [true, true, false]),
+            ("==", [false, true, false]),
+            (">=", [false, true, true]),
+            (">", [false, false, true]),
+        ];
+        for (op, expected) in table {
+            for (a, expected) in [1.0, 2.0, 3.0].into_iter().zip(expected) {
+                assert_eq!(CompareBlock::compare(a, 2.0, op), Ok(expected), "{} {} 2", a, op);
+            }
+        }
+        assert!(CompareBlock::compare(1.0, 2.0, "!=").is_err());
+
+        let op: InputValue = ["<", "<=", "==", ">=", ">"][..].into();
+        assert!(run_single_value(CompareBlock::run, &[1.0.into(), 2.0.into(), op]).as_bool());
+    }
+
+    #[test]
+    fn if_chooses_by_condition() {
+        let run = |cond: bool| run_single_number(IfBlock::run, &[cond.into(), 1.0.into(), 2.0.into()]);
+        assert_eq!(run(true), 1.0);
+        assert_eq!(run(false), 2.0);
+    }
+
+    #[test]
+    fn export_points_writes_svg_and_csv() {
+        let pts = vec![(1.0, 2.0), (3.5, -4.0)];
+        assert_eq!(export::points_svg_path(&pts), "M 1 2 L 3.5 -4");
+        assert_eq!(export::points_csv(&pts), "x,y\n1,2\n3.5,-4\n");
+
+        let dir = std::env::temp_dir();
+        let run = |format: &str, path: &std::path::Path, write: bool| {
+            let mut format_input: InputValue = ["svg path", "csv"][..].into();
+            if let InputValue::Selection((selected, options)) = &mut format_input {
+                *selected = options.iter().position(|o| o == format).unwrap();
+            }
+            let inputs: Vec<InputValue> = vec![
+                InputValue::ListPoints(pts.clone()), format_input, path.to_str().unwrap().into(), write.into(),
+            ];
+            let inputs: Vec<&InputValue> = inputs.iter().collect();
+            ExportPointsBlock::run(&inputs, &mut test_ctx())
+        };
+        let csv_path = dir.join(format!("export_points_{}.csv", std::process::id()));
+        run("csv", &csv_path, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&csv_path).unwrap(), export::points_csv(&pts));
+        std::fs::remove_file(&csv_path).unwrap();
+
+        let svg_path = dir.join(format!("export_points_{}.svg", std::process::id()));
+        // nothing is written until write is checked
+        run("svg path", &svg_path, false).unwrap();
+        assert!(!svg_path.exists());
+        run("svg path", &svg_path, true).unwrap();
+        assert!(std::fs::read_to_string(&svg_path).unwrap().contains("<path d=\"M 1 2 L 3.5 -4\""));
+        // the same contents aren't written again, unless the file went away
+        let contents = std::fs::read_to_string(&svg_path).unwrap();
+        assert_eq!(export::write_if_changed(svg_path.to_str().unwrap(), contents.clone()), Ok(false));
+        std::fs::remove_file(&svg_path).unwrap();
+        assert_eq!(export::write_if_changed(svg_path.to_str().unwrap(), contents), Ok(true));
+        std::fs::remove_file(&svg_path).unwrap();
+
+        let err = run("csv", &dir.join("no_such_dir").join("pts.csv"), true).unwrap_err();
+        assert!(err.starts_with("Failed to write"), "{}", err);
+    }
+
+    #[test]
+    fn frames_match_between_preview_and_export() {
+        let (width, total_time_secs) = (900.0, 30.0);
+        assert_eq!(Timeline::frame_at(0.0, width, total_time_secs), 0);
+        assert_eq!(Timeline::frame_at(width, width, total_time_secs), 30 * 60);
+        // the preview moves the playhead a frame at a time
+        let step = width / total_time_secs / FRAMES_PER_SECOND;
+        let mut bar_pos = 0.0;
+        for frame in 0..300 {
+            assert_eq!(Timeline::frame_at(bar_pos, width, total_time_secs), frame);
+            bar_pos += step;
+        }
+        // a 25 fps export lands on the same frames as the preview would at
+        // the same positions, 2.4 preview frames apart
+        let frame_count = 30 * 25;
+        for i in 0..frame_count {
+            let bar_pos = width * i as f32 / frame_count as f32;
+            let expected = (i as f64 * 60.0 / 25.0).floor() as u64;
+            assert_eq!(Timeline::frame_at(bar_pos, width, total_time_secs), expected, "export frame {}", i);
+        }
+
+        let mut ctx = BlockRunContext { frame: 42, ..test_ctx() };
+        assert_eq!(run_single_number_in(FrameBlock::run, &[], &mut ctx), Ok(42.0));
+    }
+
+    #[test]
+    fn audio_block_scales_the_levels() {
+        let levels = AudioLevels { amplitude: 0.5, bass: 0.25, mid: 0.1, treble: 0.0 };
+        let mut ctx = BlockRunContext { audio: levels, ..test_ctx() };
+        let run = |ctx: &mut BlockRunContext| -> Vec<f32> {
+            run_values_in(AudioBlock::run, &[2.0.into()], ctx).unwrap().iter().map(|v| v.as_f32()).collect()
+        };
+        assert_eq!(run(&mut ctx), vec![1.0, 0.5, 0.2, 0.0]);
+        // no input is all zeros
+        assert_eq!(run(&mut test_ctx()), vec![0.0; 4]);
+    }
+
+    #[test]
+    fn midi_cc_reads_the_controller() {
+        let midi = Box::leak(Box::new(MidiState::default()));
+        midi.apply(&[0xB0, 74, 127]);
+        let run = |cc: f32| {
+            let mut ctx = BlockRunContext { midi: &*midi, ..test_ctx() };
+            run_single_number_in(MidiCCBlock::run, &[cc.into()], &mut ctx)
+        };
+        assert_eq!(run(74.0), Ok(1.0));
+        assert_eq!(run(1.0), Ok(0.0));
+        assert!(run(128.0).is_err());
+        assert!(run(-1.0).is_err());
+    }
+
+    #[test]
+    fn total_time_change_keeps_the_bar_time() {
+        let mut timeline = Timeline::new(0.25);
+        timeline.bar_pos = 150.0;
+        // twice as long, so a second takes half the pixels
+        timeline.set_total_time(60.0);
+        assert_eq!((timeline.total_time_secs, timeline.bar_pos), (60.0, 75.0));
+
+        timeline.set_total_time(1.0);
+        assert_eq!((timeline.total_time_secs, timeline.bar_pos), (Timeline::MIN_TOTAL_TIME_SECS, 900.0));
+        timeline.set_total_time(f32::NAN);
+        assert_eq!((timeline.total_time_secs, timeline.bar_pos), (Timeline::MIN_TOTAL_TIME_SECS, 900.0));
+    }
+
+    #[test]
+    fn items_keep_their_times_on_any_width() {
+        let mut item = test_item(0);
+        item.start_secs = 2.0;
+        item.duration_secs = 3.0;
+        assert_eq!(item.end_secs(), 5.0);
+        assert_eq!(item.span_px(10.0), (20.0, 30.0));
+        // a wider timeline only changes the pixels
+        assert_eq!(item.span_px(20.0), (40.0, 60.0));
+        assert_eq!(Timeline::secs_at(450.0, 900.0, 30.0), 15.0);
+        assert_eq!(Timeline::secs_at(450.0, 0.0, 30.0), 0.0);
+    }
+
+    #[test]
+    fn lanes_snap_and_shift_on_removal() {
+        let mut timeline = Timeline::new(0.25);
+        assert_eq!(timeline.lanes.len(), 3);
+        assert_eq!(timeline.lane_at_offset(0.0), 0);
+        assert_eq!(timeline.lane_at_offset(Timeline::lane_offset(1) + 1.0), 1);
+        // past the last lane is the last lane
+        assert_eq!(timeline.lane_at_offset(1000.0), 2);
+
+        let mut items = [test_item(0), test_item(1), test_item(2)];
+        for (i, item) in items.iter_mut().enumerate() {
+            item.lane = i;
+        }
+        timeline.remove_lane(1, &mut items);
+        assert_eq!(timeline.lanes.iter().map(|l| l.name.as_str()).collect::<Vec<_>>(), ["Lane 1", "Lane 3"]);
+        assert_eq!(items.iter().map(|i| i.lane).collect::<Vec<_>>(), [0, 0, 1]);
+        timeline.remove_lane(0, &mut items);
+        assert_eq!(items.iter().map(|i| i.lane).collect::<Vec<_>>(), [0, 0, 0]);
+        // the last lane stays
+        timeline.remove_lane(0, &mut items);
+        assert_eq!(timeline.lanes.len(), 1);
+
+        timeline.add_lane();
+        timeline.lanes[1].visible = false;
+        assert!(timeline.is_lane_visible(0));
+        assert!(!timeline.is_lane_visible(1));
+    }
+
+    #[test]
+    fn mute_and_solo_pick_the_playing_items() {
+        let mut item = test_item(0);
+        assert!(item.plays(false));
+        // another item is soloed
+        assert!(!item.plays(true));
+        ItemToggle::Solo.toggle(&mut item);
+        assert!(item.soloed && item.plays(true));
+        // muting wins over soloing
+        ItemToggle::Mute.toggle(&mut item);
+        assert!(!item.plays(true) && !item.plays(false));
+
+        assert_eq!(ItemToggle::at((3.0, 3.0)), Some(ItemToggle::Mute));
+        assert_eq!(ItemToggle::at((ItemToggle::SIZE + 5.0, 3.0)), Some(ItemToggle::Solo));
+        assert_eq!(ItemToggle::at((3.0, ItemToggle::SIZE + 5.0)), None);
+        assert_eq!(ItemToggle::at((100.0, 3.0)), None);
+    }
+
+    thread_local! {
+        static COUNTED_RUNS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    fn counted_circle(inputs: &Vec<&InputValue>, ctx: &mut BlockRunContext) -> Result<Option<Vec<OutputResult>>, String> {
+        COUNTED_RUNS.with(|runs| runs.set(runs.get() + 1));
+        ctx.draw.circle(0.0, 0.0, inputs[0].as_f32(), RED);
+        Ok(None)
+    }
+
+    #[test]
+    fn frozen_items_keep_their_key_until_their_blocks_change() {
+        let block = DraggableBlock::for_test(
+            vec![BlockConnectionNode::new_with_input_type("radius", 5.0.into(), Inputs)],
+            vec![],
+            counted_circle,
+        );
+        let mut item = test_item(0);
+        item.blocks = BlockContext::new([block]);
+        item.frozen = true;
+        let key = item.freeze_key((800.0, 600.0));
+        assert_eq!(item.freeze_key((800.0, 600.0)), key);
+        // a new input value, screen size or seed runs it again
+        assert_ne!(item.freeze_key((400.0, 300.0)), key);
+        item.blocks.blocks[0].as_mut().unwrap().inputs[0].value = 6.0.into();
+        let changed = item.freeze_key((800.0, 600.0));
+        assert_ne!(changed, key);
+        item.blocks.blocks[0].as_mut().unwrap().inputs[0].value = InputValue::Expression("5".into());
+        assert_ne!(item.freeze_key((800.0, 600.0)), changed);
+        item.seed += 1;
+        assert_ne!(item.freeze_key((800.0, 600.0)), changed);
+
+        // sinks that can't draw textures get the shapes on every run
+        let items = [item];
+        let timeline = Timeline::new(0.25);
+        let mut images = ImageCache::default();
+        for _ in 0..2 {
+            let mut sink = RecordingSink::default();
+            timeline.run_items_at(1.0, &LiveInputs::default(), &items, (800.0, 600.0), &mut sink, &mut images).unwrap();
+            assert_eq!(sink.primitives.iter().filter(|p| matches!(p, Primitive::Circle { .. })).count(), 1);
+        }
+        assert_eq!(COUNTED_RUNS.with(|r| r.get()), 2);
+    }
+
+    fn random_circle(_inputs: &Vec<&InputValue>, ctx: &mut BlockRunContext) -> Result<Option<Vec<OutputResult>>, String> {
+        let x = ctx.rng.gen_range(0.0..100.0);
+        ctx.draw.circle(x, 0.0, 1.0, RED);
+        Ok(None)
+    }
+
+    #[test]
+    fn seed_edits_apply_on_the_next_run() {
+        let block = DraggableBlock::for_test(vec![], vec![], random_circle);
+        let mut item = test_item(0);
+        item.blocks = BlockContext::new([block]);
+        let mut items = [item];
+        let timeline = Timeline::new(0.25);
+        let mut images = ImageCache::default();
+        // the bar doesn't move between runs
+        let mut run = |items: &[TimelineItem]| {
+            let mut sink = RecordingSink::default();
+            timeline.run_items_at(1.0, &LiveInputs::default(), items, (800.0, 600.0), &mut sink, &mut images).unwrap();
+            sink.primitives
+        };
+
+        let first = run(&items);
+        assert_eq!(run(&items), first);
+        items[0].reseed(&mut ChaCha8Rng::seed_from_u64(1));
+        let reseeded = run(&items);
+        assert_ne!(reseeded, first);
+        assert_eq!(run(&items), reseeded);
+        // frozen items too
+        items[0].frozen = true;
+        assert_eq!(run(&items), reseeded);
+        items[0].seed = 0;
+        assert_eq!(run(&items), first);
+    }
+
+    #[test]
+    fn output_previews_for_colors_and_points() {
+        let single = OutputResult::SingleValue(RED.into());
+        assert_eq!(OutputPreview::of(&single), Some(OutputPreview::Swatches(vec![RED])));
+        let pts = OutputResult::Iteration(vec![(0.0, 0.0).into(), (10.0, 5.0).into()]);
+        assert_eq!(OutputPreview::of(&pts), Some(OutputPreview::Plot(vec![(0.0, 0.0), (10.0, 5.0)], 2)));
+        // long lists are thinned out, but remember how long they were
+        let many: Vec<(f32, f32)> = (0..2500).map(|i| (i as f32, 0.0)).collect();
+        match OutputPreview::of(&OutputResult::SingleValue(InputValue::ListPoints(many))) {
+            Some(OutputPreview::Plot(pts, total)) => {
+                assert_eq!(total, 2500);
+                assert!(pts.len() <= OutputPreview::MAX_PLOT_POINTS, "{} points", pts.len());
+                assert_eq!(pts[..2], [(0.0, 0.0), (3.0, 0.0)]);
+            }
+            x => panic!("Expected a plot, found {:?}", x),
+        }
+        assert_eq!(OutputPreview::of(&OutputResult::SingleValue(3.0.into())), None);
+        assert_eq!(OutputPreview::of(&OutputResult::Iteration(vec![])), None);
+        // mixed iterations have no preview
+        assert_eq!(OutputPreview::of(&OutputResult::Iteration(vec![RED.into(), 1.0.into()])), None);
+
+        // the wide side fills the box, the other is centered
+        let fitted = OutputPreview::fit(&[(0.0, 0.0), (10.0, 5.0), (f32::NAN, 1.0)], (100.0, 100.0));
+        assert_eq!(fitted, vec![(0.0, 25.0), (100.0, 75.0)]);
+        // a single point ends up in the middle
+        assert_eq!(OutputPreview::fit(&[(3.0, 3.0)], (100.0, 50.0)), vec![(50.0, 25.0)]);
+    }
+
+    #[test]
+    fn locked_seeds_only_change_by_bumping() {
+        let mut item = test_item(5);
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        item.bump_seed(1);
+        assert_eq!(item.seed, 6);
+        item.bump_seed(-7);
+        assert_eq!(item.seed, u64::MAX);
+
+        item.seed_locked = true;
+        item.reseed(&mut rng);
+        assert_eq!(item.seed, u64::MAX);
+        item.bump_seed(1);
+        assert_eq!(item.seed, 0);
+        item.seed_locked = false;
+        item.reseed(&mut rng);
+        assert_ne!(item.seed, 0);
+    }
+
+    #[test]
+    fn hash_is_stable_per_input() {
+        let run = |value: f32, seed: f32| run_single_number(HashBlock::run, &[value.into(), seed.into()]);
+        for i in 0..100 {
+            let v = run(i as f32, 7.0);
+            assert_eq!(v, run(i as f32, 7.0));
+            assert!((0.0..1.0).contains(&v), "{} out of range", v);
+        }
+        // neighbouring indices and seeds don't give the same value
+        assert_ne!(run(1.0, 7.0), run(2.0, 7.0));
+        assert_ne!(run(1.0, 7.0), run(1.0, 8.0));
+        assert_ne!(run(1.0, 2.0), run(2.0, 1.0));
+    }
+
+    #[test]
+    fn sample_image_block_reads_cached_image() {
+        let mut image = Image::gen_image_color(2, 1, BLACK);
+        image.set_pixel(1, 0, WHITE);
+        let mut images = ImageCache::default();
+        images.insert("two_pixels.png", image);
+        let inputs: Vec<InputValue> = vec!["two_pixels.png".into(), (1.0, 0.0).into()];
+        let inputs: Vec<&InputValue> = inputs.iter().collect();
+        let mut sink = RecordingSink::default();
+        let mut ctx = BlockRunContext { draw: TransformedSink::new(&mut sink), images: &mut images, ..test_ctx() };
+        let out = SampleImageBlock::run(&inputs, &mut ctx).unwrap().unwrap();
+        match &out[0] {
+            OutputResult::SingleValue(v) => assert_eq!(v.as_color(), WHITE),
+            x => panic!("Expected a single value, found {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+/// the status byte of a control change, without the channel
+const CONTROL_CHANGE: u8 = 0xB0;
+
+/// the latest value of every MIDI continuous controller, 0 - 1.
+/// controllers that haven't moved yet are 0
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiState {
+    cc: [f32; 128],
+}
+
+impl Default for MidiState {
+    fn default() -> Self {
+        Self { cc: [0.0; 128] }
+    }
+}
+
+impl MidiState {
+    /// None if `cc` isn't a controller number
+    pub fn get(&self, cc: usize) -> Option<f32> {
+        self.cc.get(cc).copied()
+    }
+    /// updates the controller if `message` is a control change, on any
+    /// channel. every other message is ignored
+    pub fn apply(&mut self, message: &[u8]) {
+        if let [status, controller, value] = *message {
+            if status & 0xF0 == CONTROL_CHANGE && controller < 128 && value < 128 {
+                self.cc[controller as usize] = value as f32 / 127.0;
+            }
+        }
+    }
+}
+
+/// listens for MIDI input. there's no MIDI backend yet, the usual ones
+/// link libasound a second time next to macroquad's own copy. so `open`
+/// always fails and every controller stays at 0
+pub struct MidiListener;
+
+impl MidiListener {
+    pub fn open() -> Result<Self, String> {
+        Err("MIDI input isn't supported yet".into())
+    }
+    /// applies every message that came in since the last poll
+    pub fn poll(&self, _state: &mut MidiState) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_changes_update_their_controller() {
+        let mut state = MidiState::default();
+        // cc 7 on channel 1, then cc 1 on channel 16
+        state.apply(&[0xB0, 7, 127]);
+        state.apply(&[0xBF, 1, 0x40]);
+        assert_eq!(state.get(7), Some(1.0));
+        assert_eq!(state.get(1), Some(64.0 / 127.0));
+        assert_eq!(state.get(2), Some(0.0));
+        assert_eq!(state.get(128), None);
+
+        // a note on, a short message and an invalid value change nothing
+        let before = state.clone();
+        state.apply(&[0x90, 7, 10]);
+        state.apply(&[0xB0, 7]);
+        state.apply(&[0xB0, 7, 200]);
+        assert_eq!(state, before);
+    }
+}
@@ -0,0 +1,508 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use macroquad::miniquad::{BlendFactor, BlendState, BlendValue, Equation};
+use macroquad::prelude::*;
+
+/// coordinates/sizes beyond this are almost certainly a math mistake
+/// (eg: tan near 90 degrees) and can make macroquad hang or glitch
+pub const MAX_DRAW_VALUE: f32 = 1e6;
+
+static WARNED_INVALID: AtomicBool = AtomicBool::new(false);
+
+/// false if any value is NaN, infinite or too big to draw. the first
+/// time that happens, a warning is logged
+fn valid(values: &[f32]) -> bool {
+    let is_valid = values.iter().all(|v| v.is_finite() && v.abs() <= MAX_DRAW_VALUE);
+    if !is_valid && !WARNED_INVALID.swap(true, Ordering::Relaxed) {
+        macroquad::logging::warn!("Skipping a draw call with invalid values {:?}. Further ones are skipped silently", values);
+    }
+    is_valid
+}
+
+/// how what gets drawn combines with what's already there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// regular alpha blending
+    #[default]
+    Normal,
+    /// colors add up, so overlapping translucent shapes glow
+    Additive,
+    /// colors multiply, so overlapping shapes get darker
+    Multiply,
+}
+
+impl BlendMode {
+    pub const NAMES: [&'static str; 3] = ["normal", "additive", "multiply"];
+
+    /// unknown names are Normal
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "additive" => BlendMode::Additive,
+            "multiply" => BlendMode::Multiply,
+            _ => BlendMode::Normal,
+        }
+    }
+}
+
+/// where shape blocks send what they draw. this lets the same graph
+/// be drawn to the screen, or into other formats like svg
+pub trait DrawSink {
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color);
+    fn circle(&mut self, x: f32, y: f32, radius: f32, color: Color);
+    fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color);
+    fn rect_lines(&mut self, x: f32, y: f32, w: f32, h: f32, thickness: f32, color: Color);
+    fn triangle(&mut self, a: (f32, f32), b: (f32, f32), c: (f32, f32), color: Color);
+    /// x, y is the left of the text's baseline, like macroquad's draw_text
+    fn text(&mut self, text: &str, x: f32, y: f32, size: f32, color: Color);
+    /// applies to everything drawn afterwards, until it's set again
+    fn set_blend_mode(&mut self, mode: BlendMode);
+    /// whether `texture` draws anything. frozen timeline items are only
+    /// drawn into a texture once on sinks that can draw it
+    fn draws_textures(&self) -> bool {
+        false
+    }
+    /// draws `texture` stretched from 0, 0 to `w`, `h`
+    fn texture(&mut self, _texture: Texture2D, _w: f32, _h: f32) {}
+}
+
+/// same as macroquad's default shader. only the blending differs
+const BLEND_VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}"#;
+
+const BLEND_FRAGMENT_SHADER: &str = r#"#version 100
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+
+void main() {
+    gl_FragColor = color * texture2D(Texture, uv);
+}"#;
+
+thread_local! {
+    /// the additive and multiply materials. made the first time
+    /// they're used, because that needs the gl context
+    static BLEND_MATERIALS: RefCell<Option<(Material, Material)>> = const { RefCell::new(None) };
+}
+
+fn blend_material(color_blend: BlendState) -> Material {
+    let pipeline_params = PipelineParams {
+        color_blend: Some(color_blend),
+        // keep the target's alpha, like regular drawing does
+        alpha_blend: Some(BlendState::new(Equation::Add, BlendFactor::Zero, BlendFactor::One)),
+        ..Default::default()
+    };
+    load_material(BLEND_VERTEX_SHADER, BLEND_FRAGMENT_SHADER, MaterialParams { pipeline_params, ..Default::default() })
+        .expect("the blend shaders are fixed, so they always compile")
+}
+
+/// draws straight to the screen (or the current camera's render target)
+pub struct MacroquadSink;
+
+impl DrawSink for MacroquadSink {
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        draw_line(x1, y1, x2, y2, thickness, color);
+    }
+    fn circle(&mut self, x: f32, y: f32, radius: f32, color: Color) {
+        draw_circle(x, y, radius, color);
+    }
+    fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        draw_rectangle(x, y, w, h, color);
+    }
+    fn rect_lines(&mut self, x: f32, y: f32, w: f32, h: f32, thickness: f32, color: Color) {
+        draw_rectangle_lines(x, y, w, h, thickness, color);
+    }
+    fn triangle(&mut self, a: (f32, f32), b: (f32, f32), c: (f32, f32), color: Color) {
+        draw_triangle(vec2(a.0, a.1), vec2(b.0, b.1), vec2(c.0, c.1), color);
+    }
+    fn text(&mut self, text: &str, x: f32, y: f32, size: f32, color: Color) {
+        draw_text(text, x, y, size, color);
+    }
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        if mode == BlendMode::Normal {
+            return gl_use_default_material();
+        }
+        let (additive, multiply) = BLEND_MATERIALS.with(|materials| {
+            *materials.borrow_mut().get_or_insert_with(|| {
+                let additive = blend_material(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::One,
+                ));
+                let multiply = blend_material(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::DestinationColor),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                ));
+                (additive, multiply)
+            })
+        });
+        gl_use_material(if mode == BlendMode::Additive { additive } else { multiply });
+    }
+    fn draws_textures(&self) -> bool {
+        true
+    }
+    fn texture(&mut self, texture: Texture2D, w: f32, h: f32) {
+        let params = DrawTextureParams { dest_size: Some(vec2(w, h)), ..Default::default() };
+        draw_texture_ex(texture, 0.0, 0.0, WHITE, params);
+    }
+}
+
+/// what blocks draw through (`BlockRunContext::draw`). keeps a stack of
+/// transforms, see PushTransformBlock, and applies the top one to every
+/// primitive before passing it on to the actual sink. primitives with
+/// NaN, infinite or huge values are dropped
+pub struct TransformedSink<'a> {
+    sink: &'a mut dyn DrawSink,
+    stack: Vec<Affine2>,
+}
+
+impl<'a> TransformedSink<'a> {
+    pub fn new(sink: &'a mut dyn DrawSink) -> Self {
+        Self { sink, stack: vec![] }
+    }
+    pub fn current(&self) -> Affine2 {
+        self.stack.last().copied().unwrap_or(Affine2::IDENTITY)
+    }
+    /// `transform` is applied first, then the one that was current before
+    pub fn push_transform(&mut self, transform: Affine2) {
+        self.stack.push(self.current() * transform);
+    }
+    /// returns false if there was nothing to pop
+    pub fn pop_transform(&mut self) -> bool {
+        self.stack.pop().is_some()
+    }
+    fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        let p = self.current().transform_point2(vec2(x, y));
+        (p.x, p.y)
+    }
+}
+
+impl DrawSink for TransformedSink<'_> {
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        let ((x1, y1), (x2, y2)) = (self.point(x1, y1), self.point(x2, y2));
+        if valid(&[x1, y1, x2, y2, thickness]) {
+            self.sink.line(x1, y1, x2, y2, thickness, color);
+        }
+    }
+    fn circle(&mut self, x: f32, y: f32, radius: f32, color: Color) {
+        let scale = self.current().matrix2.determinant().abs().sqrt();
+        let (x, y) = self.point(x, y);
+        let radius = radius * scale;
+        if valid(&[x, y, radius]) {
+            self.sink.circle(x, y, radius, color);
+        }
+    }
+    fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        if !valid(&[x, y, w, h]) {
+            return;
+        }
+        let transform = self.current();
+        if transform.matrix2 == Mat2::IDENTITY {
+            let t = transform.translation;
+            return self.sink.rect(x + t.x, y + t.y, w, h, color);
+        }
+        // once rotated, it's no longer an axis aligned rect
+        let corners = [self.point(x, y), self.point(x + w, y), self.point(x + w, y + h), self.point(x, y + h)];
+        self.sink.triangle(corners[0], corners[1], corners[2], color);
+        self.sink.triangle(corners[0], corners[2], corners[3], color);
+    }
+    fn rect_lines(&mut self, x: f32, y: f32, w: f32, h: f32, thickness: f32, color: Color) {
+        if !valid(&[x, y, w, h, thickness]) {
+            return;
+        }
+        let transform = self.current();
+        if transform.matrix2 == Mat2::IDENTITY {
+            let t = transform.translation;
+            return self.sink.rect_lines(x + t.x, y + t.y, w, h, thickness, color);
+        }
+        let corners = [self.point(x, y), self.point(x + w, y), self.point(x + w, y + h), self.point(x, y + h)];
+        for i in 0..4 {
+            let (a, b) = (corners[i], corners[(i + 1) % 4]);
+            self.sink.line(a.0, a.1, b.0, b.1, thickness, color);
+        }
+    }
+    fn triangle(&mut self, a: (f32, f32), b: (f32, f32), c: (f32, f32), color: Color) {
+        let (a, b, c) = (self.point(a.0, a.1), self.point(b.0, b.1), self.point(c.0, c.1));
+        if valid(&[a.0, a.1, b.0, b.1, c.0, c.1]) {
+            self.sink.triangle(a, b, c, color);
+        }
+    }
+    /// only the position is transformed, the text itself stays upright
+    fn text(&mut self, text: &str, x: f32, y: f32, size: f32, color: Color) {
+        let (x, y) = self.point(x, y);
+        if valid(&[x, y, size]) {
+            self.sink.text(text, x, y, size, color);
+        }
+    }
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.sink.set_blend_mode(mode);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Primitive {
+    Line { x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color },
+    Circle { x: f32, y: f32, radius: f32, color: Color },
+    Rect { x: f32, y: f32, w: f32, h: f32, color: Color },
+    RectLines { x: f32, y: f32, w: f32, h: f32, thickness: f32, color: Color },
+    Triangle { a: (f32, f32), b: (f32, f32), c: (f32, f32), color: Color },
+    Text { text: String, x: f32, y: f32, size: f32, color: Color },
+    Blend(BlendMode),
+}
+
+/// keeps everything that was drawn, without needing a window
+#[derive(Default)]
+pub struct RecordingSink {
+    pub primitives: Vec<Primitive>,
+}
+
+impl DrawSink for RecordingSink {
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        self.primitives.push(Primitive::Line { x1, y1, x2, y2, thickness, color });
+    }
+    fn circle(&mut self, x: f32, y: f32, radius: f32, color: Color) {
+        self.primitives.push(Primitive::Circle { x, y, radius, color });
+    }
+    fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        self.primitives.push(Primitive::Rect { x, y, w, h, color });
+    }
+    fn rect_lines(&mut self, x: f32, y: f32, w: f32, h: f32, thickness: f32, color: Color) {
+        self.primitives.push(Primitive::RectLines { x, y, w, h, thickness, color });
+    }
+    fn triangle(&mut self, a: (f32, f32), b: (f32, f32), c: (f32, f32), color: Color) {
+        self.primitives.push(Primitive::Triangle { a, b, c, color });
+    }
+    fn text(&mut self, text: &str, x: f32, y: f32, size: f32, color: Color) {
+        self.primitives.push(Primitive::Text { text: text.to_string(), x, y, size, color });
+    }
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.primitives.push(Primitive::Blend(mode));
+    }
+}
+
+/// collects svg elements, see `to_document`
+pub struct SvgSink {
+    pub width: f32,
+    pub height: f32,
+    pub background: Color,
+    pub elements: Vec<String>,
+    /// elements drawn with a blend mode go in a group with a
+    /// mix-blend-mode style. true while that group is open
+    pub blend_group_open: bool,
+}
+
+impl SvgSink {
+    pub fn new(width: f32, height: f32, background: Color) -> Self {
+        Self { width, height, background, elements: vec![], blend_group_open: false }
+    }
+    /// a complete svg file, with the background drawn first like the preview
+    pub fn to_document(&self) -> String {
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+            w = self.width, h = self.height,
+        );
+        let (fill, opacity) = svg_color(self.background);
+        out.push_str(&format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"{}\"/>\n",
+            self.width, self.height, fill, opacity,
+        ));
+        for element in self.elements.iter() {
+            out.push_str(element);
+            out.push('\n');
+        }
+        if self.blend_group_open {
+            out.push_str("</g>\n");
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+/// svg color and opacity attribute values
+fn svg_color(color: Color) -> (String, f32) {
+    let rgb = format!(
+        "rgb({},{},{})",
+        (color.r * 255.0).round() as u8,
+        (color.g * 255.0).round() as u8,
+        (color.b * 255.0).round() as u8,
+    );
+    (rgb, color.a)
+}
+
+impl DrawSink for SvgSink {
+    fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        let (stroke, opacity) = svg_color(color);
+        self.elements.push(format!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-opacity=\"{}\" stroke-width=\"{}\"/>",
+            x1, y1, x2, y2, stroke, opacity, thickness,
+        ));
+    }
+    fn circle(&mut self, x: f32, y: f32, radius: f32, color: Color) {
+        let (fill, opacity) = svg_color(color);
+        self.elements.push(format!(
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\" fill-opacity=\"{}\"/>",
+            x, y, radius, fill, opacity,
+        ));
+    }
+    fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        let (fill, opacity) = svg_color(color);
+        self.elements.push(format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"{}\"/>",
+            x, y, w, h, fill, opacity,
+        ));
+    }
+    fn rect_lines(&mut self, x: f32, y: f32, w: f32, h: f32, thickness: f32, color: Color) {
+        let (stroke, opacity) = svg_color(color);
+        self.elements.push(format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"{}\" stroke-opacity=\"{}\" stroke-width=\"{}\"/>",
+            x, y, w, h, stroke, opacity, thickness,
+        ));
+    }
+    fn triangle(&mut self, a: (f32, f32), b: (f32, f32), c: (f32, f32), color: Color) {
+        let (fill, opacity) = svg_color(color);
+        self.elements.push(format!(
+            "<polygon points=\"{},{} {},{} {},{}\" fill=\"{}\" fill-opacity=\"{}\"/>",
+            a.0, a.1, b.0, b.1, c.0, c.1, fill, opacity,
+        ));
+    }
+    fn text(&mut self, text: &str, x: f32, y: f32, size: f32, color: Color) {
+        let (fill, opacity) = svg_color(color);
+        let escaped = text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        self.elements.push(format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"{}\" fill=\"{}\" fill-opacity=\"{}\">{}</text>",
+            x, y, size, fill, opacity, escaped,
+        ));
+    }
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        if self.blend_group_open {
+            self.elements.push("</g>".into());
+            self.blend_group_open = false;
+        }
+        let css = match mode {
+            BlendMode::Normal => return,
+            BlendMode::Additive => "plus-lighter",
+            BlendMode::Multiply => "multiply",
+        };
+        self.elements.push(format!("<g style=\"mix-blend-mode:{}\">", css));
+        self.blend_group_open = true;
+    }
+}
+
+/// runs the graph without a window, and returns everything it drew
+#[cfg(test)]
+pub fn run_headless(
+    ctx: &crate::draw::BlockContext,
+    seed: u64,
+    screen: (f32, f32),
+    percentage: f32,
+) -> Result<Vec<Primitive>, String> {
+    use ::rand::SeedableRng;
+    let mut sink = RecordingSink::default();
+    let mut images = crate::images::ImageCache::default();
+    let midi = crate::midi::MidiState::default();
+    let mut run_ctx = crate::BlockRunContext {
+        screen_w: screen.0,
+        screen_h: screen.1,
+        percentage,
+        rng: rand_chacha::ChaCha8Rng::seed_from_u64(seed),
+        draw: TransformedSink::new(&mut sink),
+        images: &mut images,
+        frame: 0,
+        audio: Default::default(),
+        midi: &midi,
+    };
+    ctx.run(&mut run_ctx)?;
+    Ok(sink.primitives)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transforms_apply_to_primitives() {
+        let mut recording = RecordingSink::default();
+        {
+            let mut sink = TransformedSink::new(&mut recording);
+            sink.push_transform(Affine2::from_translation(vec2(10.0, 0.0)));
+            sink.rect(0.0, 0.0, 1.0, 1.0, RED);
+            sink.push_transform(Affine2::from_angle(std::f32::consts::FRAC_PI_2));
+            sink.circle(1.0, 0.0, 2.0, RED);
+            assert!(sink.pop_transform());
+            assert!(sink.pop_transform());
+            assert!(!sink.pop_transform());
+            sink.line(0.0, 0.0, 1.0, 1.0, 1.0, RED);
+        }
+        assert_eq!(recording.primitives[0], Primitive::Rect { x: 10.0, y: 0.0, w: 1.0, h: 1.0, color: RED });
+        match recording.primitives[1] {
+            // rotated by 90 degrees, then moved right by 10
+            Primitive::Circle { x, y, radius, .. } => {
+                assert!((x - 10.0).abs() < 1e-5 && (y - 1.0).abs() < 1e-5, "{} {}", x, y);
+                assert!((radius - 2.0).abs() < 1e-5);
+            }
+            ref x => panic!("Expected a circle, found {:?}", x),
+        }
+        assert_eq!(recording.primitives[2], Primitive::Line { x1: 0.0, y1: 0.0, x2: 1.0, y2: 1.0, thickness: 1.0, color: RED });
+    }
+
+    #[test]
+    fn invalid_values_are_not_drawn() {
+        let mut recording = RecordingSink::default();
+        {
+            let mut sink = TransformedSink::new(&mut recording);
+            sink.circle(f32::NAN, 0.0, 1.0, RED);
+            sink.circle(0.0, 0.0, f32::INFINITY, RED);
+            sink.line(0.0, 0.0, 1e9, 0.0, 1.0, RED);
+            sink.rect(0.0, f32::NEG_INFINITY, 1.0, 1.0, RED);
+            sink.circle(1.0, 2.0, 3.0, RED);
+        }
+        assert_eq!(recording.primitives, vec![Primitive::Circle { x: 1.0, y: 2.0, radius: 3.0, color: RED }]);
+    }
+
+    #[test]
+    fn svg_document_has_elements() {
+        let mut svg = SvgSink::new(100.0, 50.0, BLACK);
+        svg.circle(10.0, 20.0, 5.0, RED);
+        svg.line(0.0, 0.0, 10.0, 10.0, 2.0, Color::new(0.0, 0.0, 1.0, 0.5));
+        let doc = svg.to_document();
+        assert!(doc.starts_with("<svg "));
+        assert!(doc.contains("viewBox=\"0 0 100 50\""));
+        assert!(doc.contains("<rect width=\"100\" height=\"50\" fill=\"rgb(0,0,0)\" fill-opacity=\"1\"/>"));
+        assert!(doc.contains("<circle cx=\"10\" cy=\"20\" r=\"5\" fill=\"rgb(230,41,56)\" fill-opacity=\"1\"/>"));
+        assert!(doc.contains("stroke=\"rgb(0,0,255)\" stroke-opacity=\"0.5\" stroke-width=\"2\""));
+        assert!(doc.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn svg_blend_modes_group_elements() {
+        let mut svg = SvgSink::new(100.0, 50.0, BLACK);
+        svg.set_blend_mode(BlendMode::Additive);
+        svg.circle(10.0, 20.0, 5.0, RED);
+        svg.set_blend_mode(BlendMode::Normal);
+        svg.circle(10.0, 20.0, 5.0, RED);
+        svg.set_blend_mode(BlendMode::Multiply);
+        svg.circle(10.0, 20.0, 5.0, RED);
+        assert_eq!(svg.elements[0], "<g style=\"mix-blend-mode:plus-lighter\">");
+        assert_eq!(svg.elements[2], "</g>");
+        assert_eq!(svg.elements[4], "<g style=\"mix-blend-mode:multiply\">");
+        // the last group gets closed by the document
+        let doc = svg.to_document();
+        assert!(doc.trim_end().ends_with("</g>\n</svg>"), "{}", doc);
+    }
+}
@@ -0,0 +1,255 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::iter::Peekable;
+
+thread_local! {
+    /// the `d` of the first path of every svg file that was loaded, keyed by
+    /// file path. failed loads aren't kept, so a file that's missing (or a
+    /// path that's still being typed) is tried again on the next run
+    static PATH_DATA: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Command(char),
+    Number(f32),
+}
+
+/// the points along the first path in the svg file at `path`. the file is
+/// only read the first time, blocks run every frame
+pub fn load(path: &str, curve_segments: usize) -> Result<Vec<(f32, f32)>, String> {
+    let cached = PATH_DATA.with(|cache| cache.borrow().get(path).cloned());
+    let d = match cached {
+        Some(d) => d,
+        None => {
+            let svg = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read svg {}: {}", path, e))?;
+            let d = first_path_data(&svg).map_err(|e| format!("{}: {}", path, e))?.to_string();
+            PATH_DATA.with(|cache| cache.borrow_mut().insert(path.to_string(), d.clone()));
+            d
+        }
+    };
+    parse(&d, curve_segments).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// the `d` attribute of the first `<path>` element
+pub fn first_path_data(svg: &str) -> Result<&str, String> {
+    let mut from = 0;
+    let tag = loop {
+        let start = from + svg[from..].find("<path").ok_or("No <path> element found")?;
+        from = start + "<path".len();
+        // not <pathology> or similar
+        if svg[from..].starts_with(char::is_whitespace) {
+            let end = svg[from..].find('>').ok_or("Unclosed <path> element")?;
+            break &svg[from..from + end];
+        }
+    };
+    let mut from = 0;
+    let value_start = loop {
+        let i = from + tag[from..].find("d=").ok_or("The first <path> has no d attribute")?;
+        from = i + "d=".len();
+        // only a whole attribute name, not the end of one like id=
+        if tag[..i].ends_with(char::is_whitespace) {
+            break from;
+        }
+    };
+    let quote = tag[value_start..].chars().next()
+        .filter(|c| *c == '"' || *c == '\'')
+        .ok_or("The path's d attribute isn't quoted")?;
+    let value = &tag[value_start + 1..];
+    let end = value.find(quote).ok_or("The path's d attribute isn't closed")?;
+    Ok(&value[..end])
+}
+
+fn tokenize(d: &str) -> Result<Vec<Token>, String> {
+    let bytes = d.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_whitespace() || c == b',' {
+            i += 1;
+        } else if c.is_ascii_alphabetic() {
+            tokens.push(Token::Command(c as char));
+            i += 1;
+        } else if c.is_ascii_digit() || c == b'-' || c == b'+' || c == b'.' {
+            let start = i;
+            if c == b'-' || c == b'+' {
+                i += 1;
+            }
+            let mut seen_dot = false;
+            while i < bytes.len() && (bytes[i].is_ascii_digit() || (bytes[i] == b'.' && !seen_dot)) {
+                seen_dot |= bytes[i] == b'.';
+                i += 1;
+            }
+            if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                i += 1;
+                if i < bytes.len() && (bytes[i] == b'-' || bytes[i] == b'+') {
+                    i += 1;
+                }
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            let number = &d[start..i];
+            let value = number.parse::<f32>()
+                .map_err(|_| format!("Invalid number {:?} in path", number))?;
+            tokens.push(Token::Number(value));
+        } else {
+            return Err(format!("Unexpected {:?} in path", d[i..].chars().next().unwrap_or_default()));
+        }
+    }
+    Ok(tokens)
+}
+
+fn numbers<const N: usize, I: Iterator<Item = Token>>(tokens: &mut Peekable<I>, command: char) -> Result<[f32; N], String> {
+    let mut out = [0.0; N];
+    for v in out.iter_mut() {
+        *v = match tokens.next() {
+            Some(Token::Number(n)) => n,
+            _ => return Err(format!("Expected {} numbers after {}", N, command)),
+        };
+    }
+    Ok(out)
+}
+
+fn cubic(p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32), t: f32) -> (f32, f32) {
+    let u = 1.0 - t;
+    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+    (
+        a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+        a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+    )
+}
+
+/// the points along svg path data. supports the M, L, H, V, C and Z commands,
+/// absolute and relative. curves become `curve_segments` straight lines.
+/// every sub path ends up in the same list, one after the other
+pub fn parse(d: &str, curve_segments: usize) -> Result<Vec<(f32, f32)>, String> {
+    let curve_segments = curve_segments.max(1);
+    let mut tokens = tokenize(d)?.into_iter().peekable();
+    let mut pts = vec![];
+    let mut current = (0.0, 0.0);
+    let mut subpath_start = (0.0, 0.0);
+    // numbers without a command in front of them repeat the last command
+    let mut last_command: Option<char> = None;
+    while let Some(token) = tokens.peek().copied() {
+        let command = match token {
+            Token::Command(c) => {
+                tokens.next();
+                c
+            }
+            Token::Number(_) => last_command.ok_or("Expected a command before the numbers in path")?,
+        };
+        let origin = if command.is_ascii_lowercase() { current } else { (0.0, 0.0) };
+        let offset = |(x, y): (f32, f32)| (origin.0 + x, origin.1 + y);
+        match command.to_ascii_uppercase() {
+            'M' => {
+                let [x, y] = numbers(&mut tokens, command)?;
+                current = offset((x, y));
+                subpath_start = current;
+                pts.push(current);
+                // any more pairs after a move are lines
+                last_command = Some(if command == 'm' { 'l' } else { 'L' });
+            }
+            'L' => {
+                let [x, y] = numbers(&mut tokens, command)?;
+                current = offset((x, y));
+                pts.push(current);
+                last_command = Some(command);
+            }
+            'H' => {
+                let [x] = numbers(&mut tokens, command)?;
+                current.0 = origin.0 + x;
+                pts.push(current);
+                last_command = Some(command);
+            }
+            'V' => {
+                let [y] = numbers(&mut tokens, command)?;
+                current.1 = origin.1 + y;
+                pts.push(current);
+                last_command = Some(command);
+            }
+            'C' => {
+                let [x1, y1, x2, y2, x, y] = numbers(&mut tokens, command)?;
+                let (p1, p2, p3) = (offset((x1, y1)), offset((x2, y2)), offset((x, y)));
+                for i in 1..=curve_segments {
+                    let t = i as f32 / curve_segments as f32;
+                    pts.push(cubic(current, p1, p2, p3, t));
+                }
+                // exactly the end point, whatever rounding the last t had
+                if let Some(last) = pts.last_mut() {
+                    *last = p3;
+                }
+                current = p3;
+                last_command = Some(command);
+            }
+            'Z' => {
+                if pts.last() != Some(&subpath_start) {
+                    pts.push(subpath_start);
+                }
+                current = subpath_start;
+                last_command = None;
+            }
+            _ => return Err(format!("Unsupported path command {}", command)),
+        }
+    }
+    Ok(pts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_first_path() {
+        let svg = r#"<svg><pathology/><path id="a" fill='none' d='M 0 0 L 1 1'/><path d="M 5 5"/></svg>"#;
+        assert_eq!(first_path_data(svg), Ok("M 0 0 L 1 1"));
+        assert!(first_path_data("<svg><rect/></svg>").is_err());
+        assert!(first_path_data(r#"<svg><path id="x"/></svg>"#).is_err());
+        assert!(first_path_data(r#"<svg><path d="M 0 0</svg>"#).is_err());
+    }
+
+    #[test]
+    fn parses_lines_and_closes() {
+        let pts = parse("M10,20 L30 20 h-10v5 z", 8).unwrap();
+        assert_eq!(pts, vec![(10.0, 20.0), (30.0, 20.0), (20.0, 20.0), (20.0, 25.0), (10.0, 20.0)]);
+        // relative moves, implicit lines after a move, numbers without separators
+        let pts = parse("m1 1 2 0-1.5.5", 8).unwrap();
+        assert_eq!(pts, vec![(1.0, 1.0), (3.0, 1.0), (1.5, 1.5)]);
+        assert_eq!(parse("M 1e1 -2E-1", 8).unwrap(), vec![(10.0, -0.2)]);
+    }
+
+    #[test]
+    fn samples_curves() {
+        let pts = parse("M 0 0 C 0 10 10 10 10 0", 4).unwrap();
+        assert_eq!(pts.len(), 5);
+        assert_eq!(pts[0], (0.0, 0.0));
+        assert_eq!(pts[2], (5.0, 7.5));
+        assert_eq!(pts[4], (10.0, 0.0));
+        // relative control points are from the start of the curve
+        let relative = parse("M 0 0 c 0 10 10 10 10 0", 4).unwrap();
+        assert_eq!(relative, pts);
+    }
+
+    #[test]
+    fn malformed_paths_are_errors() {
+        assert!(parse("10 10", 8).is_err());
+        assert!(parse("M 10", 8).is_err());
+        assert!(parse("M 0 0 Q 1 1 2 2", 8).unwrap_err().contains("Unsupported"));
+        assert!(parse("M 0 0 L 1 #", 8).is_err());
+        assert!(load("no_such_file.svg", 8).unwrap_err().starts_with("Failed to read svg"));
+    }
+
+    #[test]
+    fn missing_files_load_once_they_exist() {
+        let path = std::env::temp_dir().join(format!("svg_path_test_{}.svg", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(load(path_str, 8).is_err());
+        std::fs::write(&path, r#"<svg><path d="M 1 2 L 3 4"/></svg>"#).unwrap();
+        let loaded = load(path_str, 8);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, Ok(vec![(1.0, 2.0), (3.0, 4.0)]));
+    }
+}